@@ -1,21 +1,45 @@
+pub mod autosave;
+pub mod clipboard;
 pub mod core;
 pub mod file_browser;
 pub mod graphics;
+pub mod keys;
+pub mod layout;
+pub mod migrate;
+pub mod notify;
+pub mod preferences;
+pub mod recent;
+pub mod router;
 pub mod serialize;
+pub mod svg;
 pub mod systems;
 pub mod ui;
 pub mod undo;
+pub mod unsaved;
 
 use crate::gate::{
+    autosave::*,
+    clipboard::*,
     core::*,
+    keys::Keymap,
     graphics::{
-        background::*, clk::*, connection_line::*, connector::*, gate::*, highlight::*,
-        light_bulb::*, selector::*, toggle_switch::*, segment_display::*,
+        ascii_keyboard::*, background::*, clk::*, connection_line::*, connector::*, dip_switch::*,
+        dot_matrix::*, gate::*, hex_keypad::*, highlight::*, led_bar::*, light_bulb::*,
+        net_label::*, pattern_generator::*, push_button::*, selector::*, terminal::*,
+        toggle_switch::*, segment_display::*,
     },
+    layout::*,
+    migrate::LoadReport,
+    notify::Log,
+    preferences::*,
+    recent::*,
+    router::*,
     serialize::*,
+    svg::*,
     systems::*,
     ui::*,
     undo::*,
+    unsaved::*,
 };
 use crate::rmenu::*;
 use bevy::prelude::*;
@@ -29,13 +53,26 @@ const CONNECTOR_GROUP: u32 = 2;
 
 impl Plugin for LogicComponentSystem {
     fn build(&self, app: &mut App) {
+        // Messages about files read while starting are added to
+        // those of the preferences.
+        let mut log = app.world.remove_resource::<Log>().unwrap_or_default();
+        let keymap = Keymap::load(&mut log);
+        let recent = RecentFiles::load(&mut log);
+        let recovery = Recovery::load(&mut log);
+
         app.add_event::<ConnectEvent>()
             .add_event::<ChangeInput>()
             .add_event::<DisconnectEvent>()
             .add_event::<SaveEvent>()
             .add_event::<LoadEvent>()
+            .add_event::<ImportEvent>()
+            .add_event::<ExportEvent>()
             .add_event::<InsertGateEvent>()
             .add_event::<NewConnectionEstablishedEvent>()
+            .add_event::<AutoRouteEvent>()
+            .add_event::<ArrangeEvent>()
+            .add_event::<ClipboardEvent>()
+            .add_event::<FileAction>()
             .add_plugin(GateMenuPlugin)
             .add_plugin(UndoPlugin)
             .insert_resource(LineResource {
@@ -47,7 +84,33 @@ impl Plugin for LogicComponentSystem {
                 option: GuiMenuOptions::None,
                 open: false,
             })
-            .add_startup_system(update_ui_scale_factor)
+            .insert_resource(KeyCapture(false))
+            .insert_resource(TypingFocus(None))
+            .insert_resource(ToolWindows::default())
+            .insert_resource(KeymapCapture(None))
+            .insert_resource(keymap)
+            .insert_resource(recent)
+            .insert_resource(recovery)
+            .insert_resource(log)
+            .insert_resource(UnsavedChanges::default())
+            .insert_resource(LoadReport::default())
+            .insert_resource(WireStyle::Bezier)
+            .insert_resource(WaypointDrag::default())
+            .insert_resource(Clipboard::default())
+            .insert_resource(SvgOptions::default())
+            .insert_resource(ImportPosition::default())
+            .add_system(apply_preferences_system)
+            .add_system(recent_files_system)
+            // The exit is requested during the update stage and the
+            // application closes right after it.
+            .add_system(track_changes_system)
+            .add_system(window_title_system)
+            // Components are spawned by many systems, ids are given to
+            // them once all of these have run.
+            .add_system_to_stage(CoreStage::PostUpdate, assign_component_id_system)
+            .add_system_to_stage(CoreStage::Last, confirm_close_system.label("confirm_close"))
+            .add_system_to_stage(CoreStage::Last, save_session_system.after("confirm_close"))
+            .add_system_to_stage(CoreStage::Last, remove_recovery_system.after("confirm_close"))
             .add_startup_system(load_gui_assets)
             .add_system_set(
                 SystemSet::on_update(GameState::InGame)
@@ -57,12 +120,28 @@ impl Plugin for LogicComponentSystem {
                     .with_system(ui_top_panel_system.label("ui_panel"))
                     .with_system(ui_scroll_system.label("ui_scroll"))
                     .with_system(ui_gui_about.label("ui_about"))
+                    .with_system(ui_history_system.label("ui_history"))
+                    .with_system(ui_keymap_system.label("ui_keymap"))
+                    .with_system(ui_preferences_system.label("ui_preferences"))
+                    .with_system(ui_recovery_system.label("ui_recovery"))
+                    .with_system(ui_unsaved_system.label("ui_unsaved"))
+                    .with_system(ui_load_report_system.label("ui_load_report"))
+                    .with_system(ui_toast_system.label("ui_toast"))
+                    .with_system(ui_log_system.label("ui_log"))
                     .with_system(
                         ui_reset_input
                             .after("ui_info")
                             .after("ui_panel")
                             .after("ui_scroll")
                             .after("ui_about")
+                            .after("ui_history")
+                            .after("ui_keymap")
+                            .after("ui_preferences")
+                            .after("ui_recovery")
+                            .after("ui_unsaved")
+                            .after("ui_load_report")
+                            .after("ui_toast")
+                            .after("ui_log")
                     )
             )
             .add_system_set(
@@ -77,13 +156,24 @@ impl Plugin for LogicComponentSystem {
                     .with_system(delete_gate_system.system().after("disconnect"))
                     .with_system(change_input_system.system().after("disconnect"))
                     .with_system(delete_line_system.system().after("disconnect"))
+                    // Pasted components are connected by the reconnect system
+                    // of the undo plugin, which must see the spawned entities.
+                    .with_system(clipboard_shortcut_system.label("clipboard_shortcut"))
+                    .with_system(
+                        clipboard_system
+                            .after("disconnect")
+                            .after("reconnect")
+                            .after("clipboard_shortcut"),
+                    )
                     .with_system(transition_system.system().label("transition"))
-                    .with_system(propagation_system.system().after("transition"))
+                    .with_system(net_label_system.label("net").after("transition"))
+                    .with_system(propagation_system.system().after("transition").after("net"))
                     .with_system(highlight_connector_system.system())
                     .with_system(drag_gate_system.system())
                     .with_system(drag_connector_system.system().label("drag_conn_system"))
                     .with_system(connect_event_system.system().after("drag_conn_system"))
                     .with_system(insert_gate_system.after("handle_rad_event"))
+                    .with_system(snap_anchor_system)
                     // Draw Line inserts a new bundle into an entity that might has been
                     // deleted by delete_line_system, i.e. we run it before any deletions
                     // to prevent an segfault.
@@ -94,12 +184,26 @@ impl Plugin for LogicComponentSystem {
                             .before("disconnect"),
                     )
                     //.with_system(draw_data_flow.system().after("draw_line"))
+                    .with_system(waypoint_system.before("draw_line"))
+                    .with_system(auto_route_system.before("draw_line"))
+                    .with_system(arrange_system)
                     .with_system(highlight_system.before("disconnect"))
                     .with_system(remove_highlight_system.before("disconnect"))
                     .with_system(change_highlight_system.before("disconnect"))
                     .with_system(light_bulb_system.system().before("disconnect"))
                     .with_system(segment_system.before("disconnect"))
                     .with_system(toggle_switch_system.system().before("disconnect"))
+                    .with_system(push_button_system.before("disconnect"))
+                    .with_system(dip_switch_system.before("disconnect"))
+                    .with_system(hex_keypad_system.before("disconnect"))
+                    .with_system(led_bar_system.before("disconnect"))
+                    .with_system(dot_matrix_system.before("disconnect"))
+                    .with_system(segment_direct_system.before("disconnect"))
+                    .with_system(hex_display_system.before("disconnect"))
+                    .with_system(terminal_system.before("disconnect"))
+                    .with_system(ascii_keyboard_system.before("disconnect"))
+                    .with_system(pattern_generator_system.before("disconnect"))
+                    .with_system(net_label_text_system.before("disconnect"))
                     .with_system(line_selection_system.system().after("draw_line"))
                     .with_system(draw_background_grid_system)
                     .with_system(clk_system),
@@ -109,7 +213,8 @@ impl Plugin for LogicComponentSystem {
                     .label("level1_node_set")
                     .after("level2_node_set")
                     .with_system(selector_system)
-                    .with_system(save_event_system.before("new_file"))
+                    .with_system(save_event_system.label("save").before("new_file"))
+                    .with_system(file_action_system.after("save"))
                     // The link_gates_system requires entities spawned by the
                     // load_event_system. To make sure the entities can be
                     // queried the link_gates_system must always run before the
@@ -117,12 +222,20 @@ impl Plugin for LogicComponentSystem {
                     // have been inserted into the world.
                     .with_system(link_gates_system.label("link_gates_system"))
                     .with_system(load_event_system.after("link_gates_system"))
+                    // Imported components are connected by the reconnect
+                    // system of the undo plugin during the next frame.
+                    .with_system(import_position_system)
+                    .with_system(import_event_system)
+                    .with_system(export_svg_system)
                     .with_system(shortcut_system)
+                    .with_system(rotate_gate_system)
+                    .with_system(autosave_system)
                     .with_system(update_lock),
             )
             .add_system_set(
                 SystemSet::on_enter(GameState::InGame)
                     .with_system(setup.system())
+                    .with_system(restore_session_system)
             );
 
         info!("NodePlugin loaded");
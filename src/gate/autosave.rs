@@ -0,0 +1,143 @@
+use crate::gate::{
+    core::*,
+    file_browser::CurrentlyOpen,
+    notify::Log,
+    preferences::Preferences,
+    serialize::*,
+    undo::{LineQuery, NodeQuery},
+};
+use bevy::app::AppExit;
+use bevy::prelude::*;
+use chrono::prelude::*;
+use ron::ser::{to_string_pretty, PrettyConfig};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+/// The circuit autosaved by a session that hasn't been closed properly.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RecoveryFile {
+    pub time: DateTime<chrono::Local>,
+    /// Path of the file that was open, if any.
+    pub path: Option<String>,
+    pub save: NodusSave,
+}
+
+/// The recovery file found at startup, waiting for the user to
+/// either restore or discard it.
+pub struct Recovery(pub Option<RecoveryFile>);
+
+impl RecoveryFile {
+    const FILE: &'static str = "recovery.ron";
+
+    /// Get the path of the recovery file within the data directory
+    /// of nodus, e.g. `~/.local/share/nodus/recovery.ron`.
+    pub fn path() -> Option<PathBuf> {
+        dirs::data_dir().map(|dir| dir.join("nodus").join(Self::FILE))
+    }
+
+    pub fn load(log: &mut Log) -> Option<Self> {
+        let text = fs::read_to_string(Self::path()?).ok()?;
+        match ron::from_str(&text) {
+            Ok(recovery) => Some(recovery),
+            Err(e) => {
+                log.error(format!("unable to read recovery file: {}", e));
+                None
+            }
+        }
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        let path = Self::path()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no data directory"))?;
+        let text = to_string_pretty(self, PrettyConfig::new().depth_limit(6))
+            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, text)
+    }
+
+    /// Delete the recovery file, if there is one.
+    pub fn remove() -> io::Result<()> {
+        match Self::path().map(fs::remove_file) {
+            Some(Err(e)) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl Recovery {
+    pub fn load(log: &mut Log) -> Self {
+        Self(RecoveryFile::load(log))
+    }
+}
+
+/// Periodically write the current circuit to the recovery file.
+///
+/// Nothing is written while a recovery file from a previous session
+/// is waiting to be restored, or while the circuit has no unsaved
+/// changes.
+pub fn autosave_system(
+    time: Res<Time>,
+    prefs: Res<Preferences>,
+    recovery: Res<Recovery>,
+    curr_open: Res<CurrentlyOpen>,
+    mut last: Local<f64>,
+    mut written: Local<bool>,
+    mut log: ResMut<Log>,
+    q_node: NodeQuery,
+    children: Query<&Children>,
+    q_connectors: Query<&Connections>,
+    q_line: LineQuery,
+    q_parent: Query<&Parent>,
+) {
+    let now = time.seconds_since_startup();
+    if recovery.0.is_some()
+        || prefs.autosave_interval == 0
+        || now - *last < prefs.autosave_interval as f64
+    {
+        return;
+    }
+    *last = now;
+
+    // A saved or empty circuit has nothing worth recovering.
+    let res = if !curr_open.dirty || q_node.is_empty() {
+        if !*written {
+            return;
+        }
+        *written = false;
+        RecoveryFile::remove()
+    } else {
+        *written = true;
+        RecoveryFile {
+            time: chrono::Local::now(),
+            path: curr_open.path.clone(),
+            save: to_save(&q_node, &children, &q_connectors, &q_line, &q_parent),
+        }
+        .save()
+    };
+
+    if let Err(e) = res {
+        log.error(format!("unable to autosave: {}", e));
+    }
+}
+
+/// Delete the recovery file when nodus is closed properly, unless
+/// it still waits to be restored.
+///
+/// Must run after all systems that might request the exit.
+pub fn remove_recovery_system(
+    mut ev_exit: EventReader<AppExit>,
+    recovery: Res<Recovery>,
+    mut log: ResMut<Log>,
+) {
+    if ev_exit.iter().count() == 0 || recovery.0.is_some() {
+        return;
+    }
+
+    if let Err(e) = RecoveryFile::remove() {
+        log.error(format!("unable to remove recovery file: {}", e));
+    }
+}
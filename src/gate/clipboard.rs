@@ -0,0 +1,405 @@
+use crate::gate::{
+    core::*,
+    keys::{Keymap, Shortcut},
+    migrate::{self, LoadError},
+    notify::Log,
+    serialize::*,
+    undo::*,
+};
+use crate::FontAssets;
+use bevy::prelude::*;
+use bevy_egui::EguiClipboard;
+use nodus::world2d::camera2d::MouseWorldPos;
+use nodus::world2d::interaction2d::Selected;
+use nodus::world2d::Lock;
+use ron::ser::{to_string_pretty, PrettyConfig};
+use std::collections::{HashMap, HashSet};
+
+/// Distance between duplicated components and the originals.
+const DUPLICATE_OFFSET: f32 = 64.;
+
+/// Components copied to the clipboard.
+///
+/// The positions of the components are relative to their center.
+#[derive(Debug, Clone, Default)]
+pub struct Clipboard(pub Vec<NodusComponent>);
+
+/// Request to the [`clipboard_system`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClipboardEvent {
+    Copy,
+    Cut,
+    Paste,
+    Duplicate,
+    /// Copy the selected components to the system clipboard as RON text.
+    CopyText,
+    /// Paste components from RON text found on the system clipboard.
+    PasteText,
+}
+
+/// Position the given components and their waypoints relative to
+/// their center.
+///
+/// Returns the center.
+pub fn center(components: &mut [NodusComponent]) -> Vec2 {
+    if components.is_empty() {
+        return Vec2::ZERO;
+    }
+
+    let center = components
+        .iter()
+        .fold(Vec2::ZERO, |c, nc| c + nc.position)
+        / components.len() as f32;
+    for nc in components.iter_mut() {
+        nc.position -= center;
+        for wire in nc.wires.iter_mut() {
+            for p in wire.via.iter_mut() {
+                *p -= center;
+            }
+        }
+    }
+    center
+}
+
+/// Capture the given components.
+///
+/// Connections to components that aren't part of `entities` are
+/// dropped. Returns the captured components, positioned relative to
+/// their center, and the center itself.
+pub fn copy(
+    entities: &[Entity],
+    q_node: &NodeQuery,
+    children: &Query<&Children>,
+    q_connectors: &Query<&Connections>,
+    q_line: &LineQuery,
+    q_parent: &Query<&Parent>,
+) -> (Vec<NodusComponent>, Vec2) {
+    let mut components: Vec<NodusComponent> = entities
+        .iter()
+        .filter_map(|&e| snapshot(e, q_node, children, q_connectors, q_line, q_parent))
+        .collect();
+    let set: HashSet<ComponentId> = components.iter().map(|nc| nc.id).collect();
+
+    let center = center(&mut components);
+    for nc in components.iter_mut() {
+        if let Some(targets) = nc.targets.as_mut() {
+            for map in targets.iter_mut() {
+                map.retain(|e, _| set.contains(e));
+            }
+        }
+        nc.wires.retain(|w| set.contains(&w.target));
+    }
+
+    (components, center)
+}
+
+/// Spawn the given components, positioned relative to their center,
+/// around `position` and restore the connections between them.
+///
+/// The spawned components get new ids, i.e. the same components
+/// can be pasted more than once.
+///
+/// Returns the spawned entities.
+pub fn paste(
+    commands: &mut Commands,
+    font: Handle<Font>,
+    components: &[NodusComponent],
+    position: Vec2,
+    ev_conn: &mut EventWriter<ReconnectGates>,
+) -> Vec<Entity> {
+    let mut components: Vec<NodusComponent> = components
+        .iter()
+        .cloned()
+        .map(|mut nc| {
+            nc.position += position;
+            for wire in nc.wires.iter_mut() {
+                for p in wire.via.iter_mut() {
+                    *p += position;
+                }
+            }
+            nc
+        })
+        .collect();
+    renumber(&mut components);
+
+    let entities = match insert(commands, font, components.clone()) {
+        Some(entities) => entities,
+        None => return Vec::new(),
+    };
+
+    // Components that can't be spawned are skipped, i.e. the
+    // connections can only be restored if all of them exist.
+    if entities.len() == components.len() {
+        let ids: HashMap<ComponentId, Entity> = components
+            .iter()
+            .map(|nc| nc.id)
+            .zip(entities.iter().copied())
+            .collect();
+
+        let mut connections = Vec::new();
+        for nc in components.iter() {
+            if let Some(targets) = &nc.targets {
+                for (i, map) in targets.iter().enumerate() {
+                    for (gate, indices) in map.iter() {
+                        if let Some(&input) = ids.get(gate) {
+                            for &j in indices.iter() {
+                                let (via, auto) = nc
+                                    .wires
+                                    .iter()
+                                    .find(|w| w.output == i && w.target == *gate && w.input == j)
+                                    .map(|w| (w.via.clone(), w.auto))
+                                    .unwrap_or_default();
+
+                                connections.push(Link {
+                                    output: ConnInfo { entity: ids[&nc.id], index: i },
+                                    input: ConnInfo { entity: input, index: j },
+                                    // The connections are new, i.e. there is no previous
+                                    // connection line whose id must be replaced.
+                                    line: None,
+                                    via,
+                                    auto,
+                                });
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        ev_conn.send(ReconnectGates(connections, None));
+    }
+
+    entities
+}
+
+/// Parse components from RON text, i.e. a list of entities as
+/// found in a save file.
+///
+/// The components are positioned relative to their center and
+/// connections to components missing from the text are dropped.
+/// Components that can't be spawned, e.g. gates without a number
+/// of inputs, are rejected like those of an invalid file.
+pub fn from_text(text: &str) -> Result<Vec<NodusComponent>, LoadError> {
+    let mut components: Vec<NodusComponent> = ron::from_str(text).map_err(LoadError::Parse)?;
+    let ids: HashSet<ComponentId> = components.iter().map(|nc| nc.id).collect();
+
+    center(&mut components);
+    for nc in components.iter_mut() {
+        if let Some(targets) = nc.targets.as_mut() {
+            for map in targets.iter_mut() {
+                map.retain(|e, _| ids.contains(e));
+            }
+        }
+        nc.wires.retain(|w| ids.contains(&w.target));
+    }
+
+    let problems = migrate::validate(&components);
+    if problems.is_empty() {
+        Ok(components)
+    } else {
+        Err(LoadError::Invalid(problems))
+    }
+}
+
+impl ClipboardEvent {
+    pub const ALL: [ClipboardEvent; 6] = [
+        ClipboardEvent::Cut,
+        ClipboardEvent::Copy,
+        ClipboardEvent::Paste,
+        ClipboardEvent::Duplicate,
+        ClipboardEvent::CopyText,
+        ClipboardEvent::PasteText,
+    ];
+
+    /// The keyboard shortcut triggering the event.
+    pub fn shortcut(&self) -> Shortcut {
+        match self {
+            ClipboardEvent::Copy => Shortcut::Copy,
+            ClipboardEvent::Cut => Shortcut::Cut,
+            ClipboardEvent::Paste => Shortcut::Paste,
+            ClipboardEvent::Duplicate => Shortcut::Duplicate,
+            ClipboardEvent::CopyText => Shortcut::CopyText,
+            ClipboardEvent::PasteText => Shortcut::PasteText,
+        }
+    }
+}
+
+/// Translate keyboard shortcuts into [`ClipboardEvent`]s.
+///
+/// By default `ctrl + c`, `ctrl + x`, `ctrl + v` and `ctrl + d` copy, cut,
+/// paste and duplicate components. Holding `shift` while copying or pasting
+/// uses RON text on the system clipboard instead.
+pub fn clipboard_shortcut_system(
+    keyboard: Res<Input<KeyCode>>,
+    lock: Res<Lock>,
+    keymap: Res<Keymap>,
+    mut ev_clipboard: EventWriter<ClipboardEvent>,
+) {
+    if lock.0 {
+        return;
+    }
+
+    if let Some(&ev) = ClipboardEvent::ALL
+        .iter()
+        .find(|ev| keymap.just_pressed(ev.shortcut(), &keyboard))
+    {
+        ev_clipboard.send(ev);
+    }
+}
+
+/// Copy, cut, paste and duplicate the selected components.
+///
+/// Pasted components appear at the mouse position and are selected.
+/// Cutting, pasting and duplicating can be undone as a single action.
+///
+/// Components on the system clipboard are exchanged as RON text using
+/// the schema of the entities of a save file, i.e. fragments can be
+/// moved between windows or pasted into bug reports.
+pub fn clipboard_system(
+    mut commands: Commands,
+    mut ev_clipboard: EventReader<ClipboardEvent>,
+    mut os_clipboard: ResMut<EguiClipboard>,
+    mw: Res<MouseWorldPos>,
+    font: Res<FontAssets>,
+    mut clipboard: ResMut<Clipboard>,
+    mut stack: ResMut<UndoStack>,
+    q_node: NodeQuery,
+    q_selected: Query<(Entity, Option<&NodeType>), With<Selected>>,
+    children: Query<&Children>,
+    q_connectors: Query<&Connections>,
+    q_line: LineQuery,
+    q_parent: Query<&Parent>,
+    mut ev_disconnect: EventWriter<DisconnectEvent>,
+    mut ev_conn: EventWriter<ReconnectGates>,
+    mut log: ResMut<Log>,
+) {
+    for &ev in ev_clipboard.iter() {
+        let selected: Vec<Entity> = q_selected
+            .iter()
+            .filter(|(_, nt)| nt.is_some())
+            .map(|(e, _)| e)
+            .collect();
+
+        let pasted = match ev {
+            ClipboardEvent::Copy | ClipboardEvent::Cut => {
+                if !selected.is_empty() {
+                    clipboard.0 = copy(&selected, &q_node, &children, &q_connectors, &q_line, &q_parent).0;
+                }
+
+                if ev == ClipboardEvent::Cut {
+                    if let Some(ncs) = remove(
+                        &mut commands,
+                        selected,
+                        &q_node,
+                        &children,
+                        &q_connectors,
+                        &q_line,
+                        &q_parent,
+                        &mut ev_disconnect,
+                    ) {
+                        stack.push(Action::Insert(ncs));
+                    }
+                }
+                continue;
+            }
+            ClipboardEvent::CopyText => {
+                let (components, _) = copy(&selected, &q_node, &children, &q_connectors, &q_line, &q_parent);
+                if !components.is_empty() {
+                    let pretty = PrettyConfig::new()
+                        .depth_limit(5)
+                        .separate_tuple_members(true)
+                        .enumerate_arrays(true);
+                    match to_string_pretty(&components, pretty) {
+                        Ok(text) => os_clipboard.set_contents(&text),
+                        Err(e) => log.error(format!("unable to copy components: {}", e)),
+                    }
+                }
+                continue;
+            }
+            ClipboardEvent::Paste => paste(
+                &mut commands,
+                font.main.clone(),
+                &clipboard.0,
+                Vec2::new(mw.x, mw.y),
+                &mut ev_conn,
+            ),
+            ClipboardEvent::PasteText => {
+                let text = os_clipboard.get_contents().unwrap_or_default();
+                match from_text(&text) {
+                    Ok(components) => paste(
+                        &mut commands,
+                        font.main.clone(),
+                        &components,
+                        Vec2::new(mw.x, mw.y),
+                        &mut ev_conn,
+                    ),
+                    Err(LoadError::Parse(e)) => {
+                        log.warn(format!("clipboard doesn't contain components: {}", e));
+                        continue;
+                    }
+                    Err(e) => {
+                        log.error(format!(
+                            "unable to paste components: {}",
+                            e.problems().join("; ")
+                        ));
+                        continue;
+                    }
+                }
+            }
+            ClipboardEvent::Duplicate => {
+                let (components, center) = copy(&selected, &q_node, &children, &q_connectors, &q_line, &q_parent);
+                paste(
+                    &mut commands,
+                    font.main.clone(),
+                    &components,
+                    center + Vec2::new(DUPLICATE_OFFSET, -DUPLICATE_OFFSET),
+                    &mut ev_conn,
+                )
+            }
+        };
+
+        if !pasted.is_empty() {
+            for (entity, _) in q_selected.iter() {
+                commands.entity(entity).remove::<Selected>();
+            }
+            for &entity in pasted.iter() {
+                commands.entity(entity).insert(Selected);
+            }
+
+            stack.push(Action::Remove(pasted));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_text() {
+        let components = from_text(
+            "[(id: 1, name: \"NOT Gate\", inputs: Some(1), outputs: Some(1), \
+               targets: Some(([({2: ([0]), 3: ([0])})])), position: (10.0, 0.0), \
+               rotation: None, ntype: Not, state: None), \
+              (id: 2, name: \"NOT Gate\", inputs: Some(1), outputs: Some(1), \
+               targets: None, position: (30.0, 0.0), rotation: None, ntype: Not, state: None)]",
+        )
+        .unwrap();
+        assert_eq!(components[0].position, Vec2::new(-10., 0.));
+        // The connection to the component missing from the text is dropped.
+        assert_eq!(components[0].targets.as_ref().unwrap()[0].len(), 1);
+
+        let invalid = from_text(
+            "[(id: 1, name: \"AND Gate\", inputs: None, outputs: Some(1), targets: None, \
+               position: (0.0, 0.0), rotation: None, ntype: And, state: None)]",
+        );
+        match invalid {
+            Err(LoadError::Invalid(problems)) => {
+                assert_eq!(problems, vec!["AND Gate (1): number of inputs missing".to_string()]);
+            }
+            res => panic!("expected problems, got {:?}", res.map(|ncs| ncs.len())),
+        }
+
+        assert!(matches!(from_text("hello"), Err(LoadError::Parse(_))));
+    }
+}
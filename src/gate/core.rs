@@ -1,3 +1,4 @@
+use crate::gate::router::AutoRoute;
 use bevy::prelude::*;
 use serde::{Deserialize, Serialize};
 use std::{
@@ -378,14 +379,17 @@ pub struct ConnInfo {
 
 /// A connection between a output and a input.
 ///
-/// The `via` vector can be used to store path coordinates
-/// between two gates which can be helpful when visualizing
-/// the connection.
+/// The `via` vector stores the waypoints placed by the user
+/// between the output and the input. The line is drawn from
+/// the output through all waypoints to the input.
 #[derive(Debug, Clone, PartialEq, Component)]
 pub struct ConnectionLine {
     pub output: ConnInfo,
     pub via: Vec<Vec2>,
     pub input: ConnInfo,
+    /// The drawn line approximated by a polyline, used to check
+    /// if the user clicked on the connection.
+    pub path: Vec<Vec2>,
 }
 
 impl ConnectionLine {
@@ -393,19 +397,16 @@ impl ConnectionLine {
         commands: &mut Commands,
         output: ConnInfo,
         input: ConnInfo,
+        via: Vec<Vec2>,
         positions: (Vec3, Vec3),
     ) -> Entity {
         commands
             .spawn()
             .insert(ConnectionLine {
                 output,
-                via: ConnectionLine::calculate_nodes(
-                    positions.0.x,
-                    positions.0.y,
-                    positions.1.x,
-                    positions.1.y,
-                ),
+                via,
                 input,
+                path: vec![positions.0.truncate(), positions.1.truncate()],
             })
             .id()
     }
@@ -467,6 +468,47 @@ pub fn propagation_system(
     }
 }
 
+/// A named net label (wire tunnel).
+///
+/// All labels with the same name form one net, i.e. a signal
+/// driving the input of one label is present at the outputs of
+/// all labels with the same name.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Component)]
+pub struct NetLabel(pub String);
+
+/// Resolve the state of a net from the states driving it.
+///
+/// The net is [`State::High`] if any driver is high, [`State::Low`] if
+/// any driver is low and [`State::None`] if the net isn't driven at all.
+pub fn resolve_net(drivers: impl Iterator<Item = State>) -> State {
+    drivers.fold(State::None, |net, s| match (net, s) {
+        (State::High, _) | (_, State::High) => State::High,
+        (State::Low, _) | (_, State::Low) => State::Low,
+        _ => State::None,
+    })
+}
+
+/// System for connecting net labels with the same name.
+///
+/// The input of each label drives the net and the resolved state
+/// of the net is written to the output of each label. This system
+/// must run before the [`propagation_system`].
+pub fn net_label_system(mut query: Query<(&NetLabel, &Inputs, &mut Outputs)>) {
+    let mut nets: HashMap<String, State> = HashMap::new();
+
+    for (label, inputs, _) in query.iter() {
+        let net = nets.entry(label.0.clone()).or_insert(State::None);
+        *net = resolve_net([*net, inputs[0]].into_iter());
+    }
+
+    for (label, _, mut outputs) in query.iter_mut() {
+        let net = nets[&label.0];
+        if outputs[0] != net {
+            outputs[0] = net;
+        }
+    }
+}
+
 /// Event that asks the [`connect_event_system`] to connect
 /// the specified `output` to the given `input`.
 #[derive(Debug, Clone, PartialEq)]
@@ -475,6 +517,10 @@ pub struct ConnectEvent {
     pub output_index: usize,
     pub input: Entity,
     pub input_index: usize,
+    /// Waypoints of the new connection line.
+    pub via: Vec<Vec2>,
+    /// Route the new connection line automatically, see [`AutoRoute`].
+    pub auto: bool,
     pub signal_success: bool, // Should signal success via NewConnectionEstablishedEvent.
 }
 
@@ -522,6 +568,7 @@ pub fn connect(
             entity: ev.input,
             index: ev.input_index,
         },
+        ev.via.clone(),
         (
             // The points are not relevant for now and
             // can be updated later on.
@@ -529,6 +576,9 @@ pub fn connect(
             Vec3::new(0., 0., 0.),
         ),
     );
+    if ev.auto {
+        commands.entity(line).insert(AutoRoute);
+    }
 
     // Add the new connection line to the set of lines already connected to the gate.
     let input_parent = if let Ok((parent, mut connections)) = q_conns.get_mut(ev.input) {
@@ -685,6 +735,37 @@ mod tests {
     use super::{State, *};
     use bevy::ecs::event::Events;
 
+    #[test]
+    fn test_net_label() {
+        assert_eq!(resolve_net(vec![].into_iter()), State::None);
+        assert_eq!(resolve_net(vec![State::None, State::Low].into_iter()), State::Low);
+        assert_eq!(
+            resolve_net(vec![State::Low, State::High, State::None].into_iter()),
+            State::High
+        );
+
+        let mut world = World::default();
+        let mut stage = SystemStage::parallel();
+        stage.add_system(net_label_system.system());
+
+        let mut label = |name: &str, input: State| {
+            world
+                .spawn()
+                .insert(NetLabel(name.to_string()))
+                .insert(Inputs(vec![input]))
+                .insert(Outputs(vec![State::None]))
+                .id()
+        };
+        let clk1 = label("CLK", State::High);
+        let clk2 = label("CLK", State::None);
+        let rst = label("RST", State::None);
+
+        stage.run(&mut world);
+        assert_eq!(world.entity(clk1).get::<Outputs>().unwrap()[0], State::High);
+        assert_eq!(world.entity(clk2).get::<Outputs>().unwrap()[0], State::High);
+        assert_eq!(world.entity(rst).get::<Outputs>().unwrap()[0], State::None);
+    }
+
     #[test]
     fn test_connect() {
         // Setup world
@@ -1,12 +1,15 @@
 use crate::gate::core::*;
+use crate::gate::preferences::Preferences;
 use crate::gate::serialize::*;
+use crate::gate::svg::ExportEvent;
+use crate::gate::undo::UndoStack;
 use bevy::prelude::*;
 use bevy_egui::{egui, egui::RichText, EguiContext};
 use dirs;
 use std::ffi::OsString;
 use std::fs::{self};
 use std::io;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 pub struct EguiFileBrowserPlugin;
 
@@ -16,6 +19,7 @@ impl Plugin for EguiFileBrowserPlugin {
         app.add_event::<NewFileEvent>();
         app.insert_resource(FileBrowser {
             open: false,
+            root: dirs::home_dir().expect("home dir to exist"),
             path: dirs::home_dir()
                 .expect("home dir to exist")
                 .into_os_string(),
@@ -24,7 +28,10 @@ impl Plugin for EguiFileBrowserPlugin {
             title: String::from(""),
             action: BrowserAction::Open,
         });
-        app.insert_resource(CurrentlyOpen { path: None });
+        app.insert_resource(CurrentlyOpen {
+            path: None,
+            dirty: false,
+        });
         app.add_system(draw_browser_system);
         app.add_system(open_browser_event_system);
         app.add_system(new_file_event_system.label("new_file"));
@@ -35,29 +42,50 @@ impl Plugin for EguiFileBrowserPlugin {
 pub enum BrowserAction {
     Open,
     Save,
+    /// Choose a file whose components are added to the current circuit.
+    Import,
+    /// Choose where to write the drawing of the circuit.
+    Export,
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct OpenBrowserEvent(pub BrowserAction);
 
-fn open_browser_event_system(mut ev: EventReader<OpenBrowserEvent>, mut fb: ResMut<FileBrowser>) {
+fn open_browser_event_system(
+    mut ev: EventReader<OpenBrowserEvent>,
+    mut fb: ResMut<FileBrowser>,
+    prefs: Res<Preferences>,
+) {
     for ev in ev.iter() {
+        fb.root = prefs.browser_dir();
         match ev.0 {
             BrowserAction::Open => {
                 fb.open = true;
-                fb.path = dirs::home_dir()
-                    .expect("home dir to exist")
-                    .into_os_string();
+                fb.path = fb.root.clone().into_os_string();
                 fb.title = String::from("Open File");
                 fb.action = BrowserAction::Open;
+                fb.file_type = FileType::Ron;
             }
             BrowserAction::Save => {
                 fb.open = true;
-                fb.path = dirs::home_dir()
-                    .expect("home dir to exist")
-                    .into_os_string();
+                fb.path = fb.root.clone().into_os_string();
                 fb.title = String::from("Save File As...");
                 fb.action = BrowserAction::Save;
+                fb.file_type = FileType::Ron;
+            }
+            BrowserAction::Import => {
+                fb.open = true;
+                fb.path = fb.root.clone().into_os_string();
+                fb.title = String::from("Import File");
+                fb.action = BrowserAction::Import;
+                fb.file_type = FileType::Ron;
+            }
+            BrowserAction::Export => {
+                fb.open = true;
+                fb.path = fb.root.clone().into_os_string();
+                fb.title = String::from("Export SVG");
+                fb.action = BrowserAction::Export;
+                fb.file_type = FileType::Svg;
             }
         }
     }
@@ -66,27 +94,34 @@ fn open_browser_event_system(mut ev: EventReader<OpenBrowserEvent>, mut fb: ResM
 #[derive(Debug, Clone, PartialEq)]
 enum FileType {
     Ron,
+    Svg,
 }
 
 impl FileType {
     fn to_string(&self) -> String {
         match self {
             FileType::Ron => Self::RON.to_string(),
+            FileType::Svg => Self::SVG.to_string(),
         }
     }
 
     fn ending(&self) -> &str {
         match self {
             FileType::Ron => Self::RON_ENDING,
+            FileType::Svg => Self::SVG_ENDING,
         }
     }
 
     const RON: &'static str = "Rusty Object Notation";
     const RON_ENDING: &'static str = "ron";
+    const SVG: &'static str = "Scalable Vector Graphics";
+    const SVG_ENDING: &'static str = "svg";
 }
 
 pub struct FileBrowser {
     pub open: bool,
+    /// The directory whose content is listed.
+    root: PathBuf,
     path: OsString,
     fname: String,
     file_type: FileType,
@@ -96,6 +131,8 @@ pub struct FileBrowser {
 
 pub struct CurrentlyOpen {
     pub path: Option<String>,
+    /// The circuit has changed since it has been opened or saved.
+    pub dirty: bool,
 }
 
 fn draw_browser_system(
@@ -103,6 +140,8 @@ fn draw_browser_system(
     mut fb: ResMut<FileBrowser>,
     mut ev_save: EventWriter<SaveEvent>,
     mut ev_open: EventWriter<LoadEvent>,
+    mut ev_import: EventWriter<ImportEvent>,
+    mut ev_export: EventWriter<ExportEvent>,
 ) {
     if !fb.open {
         return;
@@ -125,6 +164,7 @@ fn draw_browser_system(
                     .clicked()
                 {
                     if let Some(home_dir) = dirs::home_dir() {
+                        fb.root = home_dir.clone();
                         if let Ok(home_dir_str) = home_dir.into_os_string().into_string() {
                             s = home_dir_str;
                         }
@@ -151,7 +191,7 @@ fn draw_browser_system(
                             .max_width(640.)
                             .show(ui, |ui| {
                                 ui.set_min_width(640.);
-                                let path = dirs::home_dir().expect("home dir to exist");
+                                let path = fb.root.clone();
                                 let ftype = fb.file_type.clone();
                                 visit_dirs(&path, ui, 0, &mut s, &mut fb.fname, ftype.ending());
                             });
@@ -177,6 +217,21 @@ fn draw_browser_system(
                             ev_save.send(SaveEvent(p.into_os_string().into_string().unwrap()));
                             fb.open = false;
                         }
+                    } else if fb.action == BrowserAction::Export {
+                        let mut p = Path::new(&s).join(&fb.fname);
+
+                        if ui.add(egui::Button::new("Export")).clicked() {
+                            p.set_extension(fb.file_type.ending());
+                            ev_export.send(ExportEvent(p.into_os_string().into_string().unwrap()));
+                            fb.open = false;
+                        }
+                    } else if fb.action == BrowserAction::Import {
+                        let p = Path::new(&s).join(&fb.fname);
+
+                        if ui.add(egui::Button::new("Import")).clicked() {
+                            ev_import.send(ImportEvent(p.into_os_string().into_string().unwrap()));
+                            fb.open = false;
+                        }
                     } else {
                         let p = Path::new(&s).join(&fb.fname);
 
@@ -192,7 +247,8 @@ fn draw_browser_system(
                         .selected_text(format!("{}", fb.file_type.to_string()))
                         .width(320.0)
                         .show_ui(ui, |ui| {
-                            ui.selectable_value(&mut fb.file_type, FileType::Ron, FileType::RON);
+                            let ftype = fb.file_type.clone();
+                            ui.selectable_value(&mut fb.file_type, ftype.clone(), ftype.to_string());
                         });
                     if ui.add(egui::Button::new("cancle")).clicked() {
                         fb.open = false;
@@ -270,11 +326,14 @@ pub fn new_file_event_system(
     mut nev: EventReader<NewFileEvent>,
     q_all: Query<Entity, Or<(With<NodeType>, With<ConnectionLine>)>>,
     mut curr_open: ResMut<CurrentlyOpen>,
+    mut stack: ResMut<UndoStack>,
 ) {
     for _ev in nev.iter() {
         for e in q_all.iter() {
             commands.entity(e).despawn_recursive();
         }
         curr_open.path = None;
+        curr_open.dirty = false;
+        stack.reset();
     }
 }
@@ -1,11 +1,20 @@
+pub mod ascii_keyboard;
 pub mod background;
 pub mod clk;
 pub mod connection_line;
 pub mod connector;
+pub mod dip_switch;
+pub mod dot_matrix;
 pub mod gate;
+pub mod hex_keypad;
 pub mod highlight;
+pub mod led_bar;
 pub mod light_bulb;
+pub mod net_label;
+pub mod pattern_generator;
+pub mod push_button;
 pub mod selector;
+pub mod terminal;
 pub mod toggle_switch;
 pub mod segment_display;
 
@@ -0,0 +1,272 @@
+use super::*;
+use crate::gate::core::{State, *};
+use crate::gate::serialize::*;
+use crate::gate::ui::TypingFocus;
+use bevy::prelude::*;
+use bevy::window::ReceivedCharacter;
+use bevy_prototype_lyon::prelude::*;
+use nodus::world2d::interaction2d::{Draggable, Interactable, Selectable, Selected};
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::Ordering;
+
+/// Maximum number of characters the keyboard can buffer.
+const BUFFER_SIZE: usize = 64;
+/// Number of buffered characters shown on the display.
+const VISIBLE: usize = 12;
+
+/// Index of the ready output.
+const READY: usize = 7;
+/// Index of the acknowledge input.
+const ACK: usize = 8;
+
+/// An ASCII keyboard.
+///
+/// Characters typed while the keyboard has the typing focus are
+/// appended to a buffer. The oldest buffered character is presented
+/// on the seven data outputs (d0 is the least significant bit) and the
+/// ready output is [`State::High`] as long as the buffer isn't empty.
+/// A rising edge on the acknowledge input removes the presented
+/// character from the buffer.
+#[derive(Debug, Clone, PartialEq, Component)]
+pub struct AsciiKeyboard {
+    buffer: VecDeque<u8>,
+    /// Text entity displaying the buffer.
+    text: Entity,
+    /// Last known state of the acknowledge input.
+    ack: State,
+}
+
+impl AsciiKeyboard {
+    /// Get the persistent state of the keyboard.
+    pub fn state(&self) -> NodeState {
+        NodeState::AsciiKeyboard(self.buffer.iter().map(|&c| c as char).collect())
+    }
+
+    /// Present the oldest buffered character on the given states.
+    fn present(&self, states: &mut [State]) {
+        let c = self.buffer.front().copied();
+
+        for i in 0..7 {
+            states[i] = match c {
+                Some(c) if c & (1 << i) != 0 => State::High,
+                _ => State::Low,
+            };
+        }
+        states[READY] = if c.is_some() { State::High } else { State::Low };
+    }
+
+    /// Get the text shown on the display of the keyboard.
+    fn display(&self, focused: bool) -> String {
+        let skip = self.buffer.len().saturating_sub(VISIBLE);
+        let mut text: String = self
+            .buffer
+            .iter()
+            .skip(skip)
+            .map(|&c| if c.is_ascii_graphic() || c == b' ' { c as char } else { '·' })
+            .collect();
+
+        if focused {
+            text.push('_');
+        }
+        text
+    }
+
+    /// Create a new keyboard at the specified position with the given
+    /// characters already buffered.
+    pub fn spawn(
+        commands: &mut Commands,
+        position: Vec2,
+        rotation: Quat,
+        buffer: String,
+        font: Handle<Font>,
+    ) -> Entity {
+        let z = Z_INDEX.fetch_add(1, Ordering::Relaxed) as f32;
+        let size = Vec2::new(GATE_SIZE * 2.25, GATE_SIZE * 2.25);
+
+        let label = commands
+            .spawn_bundle(Text2dBundle {
+                text: Text::with_section(
+                    "",
+                    TextStyle {
+                        font,
+                        font_size: 20.0,
+                        color: Color::GREEN,
+                    },
+                    TextAlignment {
+                        horizontal: HorizontalAlign::Left,
+                        vertical: VerticalAlign::Center,
+                    },
+                ),
+                transform: Transform::from_xyz(-size.x / 2. + 24., size.y / 4., 1.),
+                ..Default::default()
+            })
+            .id();
+
+        let keyboard = AsciiKeyboard {
+            buffer: buffer.bytes().filter(|c| c.is_ascii()).take(BUFFER_SIZE).collect(),
+            text: label,
+            ack: State::None,
+        };
+
+        let mut states = vec![State::Low; 9];
+        keyboard.present(&mut states);
+
+        let mut functions: Vec<Box<dyn Fn(&[State]) -> State + Send + Sync>> = Vec::new();
+        for i in 0..8 {
+            functions.push(Box::new(move |inputs| inputs[i]));
+        }
+
+        let parent = commands
+            .spawn_bundle(Gate::body(
+                Vec3::new(position.x, position.y, z),
+                rotation,
+                size,
+            ))
+            .insert(keyboard)
+            .insert(Name("ASCII Keyboard".to_string()))
+            .insert(Inputs(states.clone()))
+            .insert(Outputs(states[..8].to_vec()))
+            .insert(Transitions(functions))
+            .insert(Targets(vec![TargetMap::from(HashMap::new()); 8]))
+            .insert(NodeType::AsciiKeyboard)
+            .insert(Interactable::new(Vec2::new(0., 0.), size, 1))
+            .insert(Selectable)
+            .insert(Draggable { update: true })
+            .id();
+
+        let display = commands
+            .spawn_bundle(GeometryBuilder::build_as(
+                &shapes::Rectangle {
+                    extents: Vec2::new(size.x - 32., size.y / 4.),
+                    ..shapes::Rectangle::default()
+                },
+                DrawMode::Outlined {
+                    fill_mode: FillMode::color(Color::BLACK),
+                    outline_mode: StrokeMode::new(Color::BLACK, 4.0),
+                },
+                Transform::from_xyz(0., size.y / 4., 0.1),
+            ))
+            .id();
+
+        let mut entvec = vec![display, label];
+
+        // Some keys for decoration.
+        let key = (size.x - 32.) / 8.;
+        for row in 0..3 {
+            for col in 0..(8 - row) {
+                entvec.push(
+                    commands
+                        .spawn_bundle(GeometryBuilder::build_as(
+                            &shapes::Rectangle {
+                                extents: Vec2::new(key * 0.8, key * 0.8),
+                                ..shapes::Rectangle::default()
+                            },
+                            DrawMode::Outlined {
+                                fill_mode: FillMode::color(Color::WHITE),
+                                outline_mode: StrokeMode::new(Color::BLACK, 3.0),
+                            },
+                            Transform::from_xyz(
+                                (col as f32 - (7 - row) as f32 / 2.) * key,
+                                -(row as f32 + 0.5) * key,
+                                0.1,
+                            ),
+                        ))
+                        .id(),
+                );
+            }
+        }
+
+        let step = -(size.y / 9.);
+        for i in 0..8 {
+            let name = if i == READY {
+                "rdy".to_string()
+            } else {
+                format!("d{}", i)
+            };
+
+            entvec.push(Connector::with_line(
+                commands,
+                Vec3::new(
+                    size.x / 2. + GATE_SIZE * 0.2,
+                    size.y / 2. + (i + 1) as f32 * step,
+                    0.,
+                ),
+                GATE_SIZE * 0.1,
+                ConnectorType::Out,
+                i,
+                name,
+            ));
+        }
+
+        entvec.push(Connector::with_line(
+            commands,
+            Vec3::new(-(size.x / 2. + GATE_SIZE * 0.2), 0., 0.),
+            GATE_SIZE * 0.1,
+            ConnectorType::In,
+            ACK,
+            "ack".to_string(),
+        ));
+
+        commands.entity(parent).push_children(&entvec);
+
+        parent
+    }
+}
+
+/// Buffer the characters typed while a keyboard has the typing focus and
+/// present the oldest buffered character on its outputs.
+pub fn ascii_keyboard_system(
+    mut focus: ResMut<TypingFocus>,
+    mut chars: EventReader<ReceivedCharacter>,
+    keyboard: Res<Input<KeyCode>>,
+    mut q_keyboard: Query<(Entity, &mut AsciiKeyboard, &mut Inputs, Option<&Selected>)>,
+    mut q_text: Query<&mut Text>,
+) {
+    // The focus is lost as soon as the keyboard isn't selected anymore.
+    if let Some(entity) = focus.0 {
+        let selected = matches!(q_keyboard.get(entity), Ok((_, _, _, Some(_))));
+        if !selected || keyboard.just_pressed(KeyCode::Escape) {
+            focus.0 = None;
+        }
+    }
+
+    let typed: Vec<u8> = chars
+        .iter()
+        .filter_map(|ev| match ev.char {
+            '\r' => Some(b'\n'),
+            c if c.is_ascii() => Some(c as u8),
+            _ => None,
+        })
+        .collect();
+
+    for (entity, mut kb, mut inputs, _) in q_keyboard.iter_mut() {
+        let focused = focus.0 == Some(entity);
+        let mut changed = false;
+
+        if focused {
+            for &c in typed.iter() {
+                if kb.buffer.len() < BUFFER_SIZE {
+                    kb.buffer.push_back(c);
+                    changed = true;
+                }
+            }
+        }
+
+        let ack = inputs[ACK];
+        if ack == State::High && kb.ack != State::High {
+            changed |= kb.buffer.pop_front().is_some();
+        }
+        kb.ack = ack;
+
+        if changed {
+            kb.present(&mut inputs.0);
+        }
+
+        if let Ok(mut text) = q_text.get_mut(kb.text) {
+            let display = kb.display(focused);
+            if text.sections[0].value != display {
+                text.sections[0].value = display;
+            }
+        }
+    }
+}
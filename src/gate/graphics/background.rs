@@ -1,37 +1,62 @@
 use bevy::prelude::*;
 use bevy_prototype_lyon::prelude::*;
 use nodus::world2d::camera2d::*;
+use nodus::world2d::GridSnap;
 
 #[derive(Debug, Clone, Copy, PartialEq, Component)]
 pub struct BackgroundGrid;
 
+/// Preferred distance between two lines of the background grid.
+const LINE_SPACING: f32 = 120.;
+
+/// Get the distance between two lines of the background grid, i.e.
+/// the multiple of the snap grid size closest to [`LINE_SPACING`].
+fn line_spacing(snap: &GridSnap) -> f32 {
+    snap.size * (LINE_SPACING / snap.size).round().max(1.)
+}
+
+/// Draw the background grid.
+///
+/// The lines of the grid lie on multiples of the snap grid size, i.e.
+/// components snapped to the grid line up with the background.
 pub fn draw_background_grid_system(
     mut commands: Commands,
     wnds: Res<Windows>,
+    snap: Res<GridSnap>,
     mut q: QuerySet<(
-        QueryState<&mut Transform, (With<MainCamera>, Or<(Added<Transform>, Changed<Transform>)>)>,
+        QueryState<(&Transform, ChangeTrackers<Transform>), With<MainCamera>>,
         QueryState<(Entity, &mut Transform), With<BackgroundGrid>>,
     )>,
 ) {
-    if let Ok(transform) = q.q0().get_single() {
-        let mut x = transform.translation.x;
-        let mut y = transform.translation.y;
-        let mut x_sign = 1.;
-        let mut y_sign = 1.;
-        if x < 0.0 { x *= -1.; x_sign = -1.; }
-        if y < 0.0 { y *= -1.; y_sign = -1.; }
-        let x_start = (x as u32 & !0xFFF) as f32;
-        let y_start = (y as u32 & !0xFFF) as f32;
-        let _scale = transform.scale.clone();
+    let rebuild = snap.is_changed();
+    let spacing = line_spacing(&snap);
+
+    if let Ok((transform, tracker)) = q.q0().get_single() {
+        if !rebuild && !tracker.is_changed() {
+            return;
+        }
+
+        // The grid only moves in large steps to keep the number
+        // of updates low.
+        let block = spacing * 32.;
+        let x_start = (transform.translation.x / block).round() * block;
+        let y_start = (transform.translation.y / block).round() * block;
+
+        if rebuild {
+            for (entity, _) in q.q1().iter() {
+                commands.entity(entity).despawn_recursive();
+            }
+        }
 
-        if let Ok((_entity, mut bgt)) = q.q1().get_single_mut() {
-            bgt.translation.x = x_start * x_sign;
-            bgt.translation.y = y_start * y_sign;
+        if let (false, Ok((_entity, mut bgt))) = (rebuild, q.q1().get_single_mut()) {
+            bgt.translation.x = x_start;
+            bgt.translation.y = y_start;
         } else {
             let color = Color::rgba(0., 0., 0., 0.25);
             let window_size = get_primary_window_size(&wnds);
-            let wx = window_size.x * 5.;
-            let wy = window_size.y * 5.;
+            // Half the extent of the grid, rounded to whole lines.
+            let wx = (window_size.x * 10. / spacing).ceil() * spacing;
+            let wy = (window_size.y * 10. / spacing).ceil() * spacing;
 
             let grid = commands
                 .spawn()
@@ -42,13 +67,15 @@ pub fn draw_background_grid_system(
 
             let mut evec = Vec::new();
 
-            for xc in (0..wx as u32 * 4).step_by(120) {
+            let lines = (wx * 2. / spacing) as u32;
+            for i in 0..=lines {
+                let xc = i as f32 * spacing;
                 evec.push(
                     commands
                         .spawn_bundle(GeometryBuilder::build_as(
                             &shapes::Line(
-                                Vec2::new(-wx * 2. + xc as f32, wy * 2.),
-                                Vec2::new(-wx * 2. + xc as f32, -wy * 2.),
+                                Vec2::new(-wx + xc, wy),
+                                Vec2::new(-wx + xc, -wy),
                             ),
                             DrawMode::Stroke(StrokeMode::new(color, 7.0)),
                             Transform::from_xyz(0., 0., 1.),
@@ -57,13 +84,15 @@ pub fn draw_background_grid_system(
                 );
             }
 
-            for yc in (0..wy as u32 * 4).step_by(120) {
+            let lines = (wy * 2. / spacing) as u32;
+            for i in 0..=lines {
+                let yc = i as f32 * spacing;
                 evec.push(
                     commands
                         .spawn_bundle(GeometryBuilder::build_as(
                             &shapes::Line(
-                                Vec2::new(wx * 2., -wy * 2. + yc as f32),
-                                Vec2::new(-wx * 2., -wy * 2. + yc as f32),
+                                Vec2::new(wx, -wy + yc),
+                                Vec2::new(-wx, -wy + yc),
                             ),
                             DrawMode::Stroke(StrokeMode::new(color, 7.0)),
                             Transform::from_xyz(0., 0., 1.),
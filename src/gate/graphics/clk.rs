@@ -5,20 +5,134 @@ use bevy::prelude::*;
 use bevy_prototype_lyon::prelude::*;
 use lyon_tessellation::path::path::Builder;
 use nodus::world2d::interaction2d::{Draggable, Interactable, Selectable};
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::sync::atomic::Ordering;
 
-/// Clock (clk) marker component.
+/// Configuration of a clock in addition to its frequency.
+///
+/// Save files written before the configuration existed don't
+/// contain it, i.e. the default must match the old behaviour.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+pub struct ClockConfig {
+    /// Fraction of a period the output is [`State::High`].
+    pub duty: f32,
+    /// Offset of the signal as fraction of a period.
+    pub phase: f32,
+    /// Number of cycles after which the clock stops or
+    /// `None` if the clock runs forever.
+    pub cycles: Option<u32>,
+}
+
+impl Default for ClockConfig {
+    fn default() -> Self {
+        Self {
+            duty: 0.5,
+            phase: 0.0,
+            cycles: None,
+        }
+    }
+}
+
+/// Clock (clk) component.
 #[derive(Debug, Clone, PartialEq, Component)]
-pub struct Clk(pub f32, pub f32);
+pub struct Clk {
+    /// Frequency in Hz.
+    pub frequency: f32,
+    pub config: ClockConfig,
+    /// Time in seconds since the clock has been started.
+    pub elapsed: f32,
+}
 
 impl Clk {
+    /// Minimum and maximum frequency in Hz.
+    ///
+    /// The signal is sampled once per frame, i.e. higher frequencies
+    /// would skip cycles. The minimum allows periods of several hours,
+    /// e.g. for clocks of older files that toggled rarely.
+    pub const FREQUENCY: (f32, f32) = (0.0001, 20.0);
+
+    /// Create a new free running clock with a duty cycle of 50%.
+    pub fn new(frequency: f32) -> Self {
+        Self {
+            frequency,
+            config: ClockConfig::default(),
+            elapsed: 0.0,
+        }
+    }
+
+    /// Create a clock from its persistent state, see [`Clk::state`].
+    pub fn from_state(half_period: f32, elapsed: f32, config: ClockConfig) -> Self {
+        Self {
+            frequency: 1.0 / (2.0 * half_period),
+            config,
+            elapsed,
+        }
+    }
+
+    /// Convert the state of a clock saved before clocks could be
+    /// configured.
+    ///
+    /// Those clocks toggled their output every `half_period` seconds
+    /// and stored the time `since_toggle` since the last toggle. The
+    /// position within the current period follows from the output,
+    /// i.e. the converted clock continues where the old one stopped.
+    pub fn from_legacy(half_period: f32, since_toggle: f32, output: State) -> NodeState {
+        let frequency = (1.0 / (2.0 * half_period)).clamp(Self::FREQUENCY.0, Self::FREQUENCY.1);
+        let half = if half_period > 0.0 {
+            (since_toggle / half_period).clamp(0.0, 1.0) / 2.0
+        } else {
+            0.0
+        };
+        let phase = if output == State::High { half } else { 0.5 + half };
+
+        let clk = Self {
+            frequency,
+            config: ClockConfig {
+                phase: phase.fract(),
+                ..ClockConfig::default()
+            },
+            elapsed: 0.0,
+        };
+        clk.state(clk.output())
+    }
+
+    /// Get the persistent state of the clock.
+    ///
+    /// For backwards compatibility the frequency is stored as
+    /// the duration of a half period in seconds.
+    pub fn state(&self, output: State) -> NodeState {
+        NodeState::Clock(1.0 / (2.0 * self.frequency), self.elapsed, output, self.config)
+    }
+
+    /// Check if the clock has run the configured number of cycles.
+    pub fn finished(&self) -> bool {
+        match self.config.cycles {
+            Some(n) => self.elapsed * self.frequency >= n as f32,
+            None => false,
+        }
+    }
+
+    /// Get the output of the clock at the current point in time.
+    pub fn output(&self) -> State {
+        if self.finished() {
+            return State::Low;
+        }
+
+        let t = (self.elapsed * self.frequency + self.config.phase).fract();
+        if t < self.config.duty {
+            State::High
+        } else {
+            State::Low
+        }
+    }
+
+    /// Create a new clock at the specified position.
     pub fn spawn(
         commands: &mut Commands,
         position: Vec2,
         rotation: Quat,
-        clk: f32,
-        start: f32,
+        clk: Clk,
         state: State,
     ) -> Entity {
         let z = Z_INDEX.fetch_add(1, Ordering::Relaxed) as f32;
@@ -38,7 +152,7 @@ impl Clk {
                 Transform::from_xyz(position.x, position.y, z)
                     .with_rotation(rotation),
             ))
-            .insert(Clk(clk, start))
+            .insert(clk)
             .insert(Name("Clock".to_string()))
             .insert(NodeType::Clock)
             .insert(Outputs(vec![state]))
@@ -99,7 +213,6 @@ impl Geometry for ClkShape {
 }
 
 pub fn clk_system(
-    _commands: Commands,
     mut q_clk: Query<(&Children, &mut Clk, &mut Outputs)>,
     mut draw: Query<&mut DrawMode, Without<Connector>>,
     time: Res<Time>,
@@ -107,14 +220,13 @@ pub fn clk_system(
     let delta = time.delta_seconds();
 
     for (children, mut clk, mut outs) in q_clk.iter_mut() {
-        clk.1 += delta;
-
-        if clk.1 >= clk.0 {
-            clk.1 = 0.0;
-            outs[0] = match outs[0] {
-                State::High => State::Low,
-                _ => State::High,
-            };
+        if !clk.finished() {
+            clk.elapsed += delta;
+        }
+
+        let next = clk.output();
+        if outs[0] != next {
+            outs[0] = next;
 
             for &child in children.iter() {
                 if let Ok(mut mode) = draw.get_mut(child) {
@@ -1,14 +1,22 @@
 use crate::gate::core::{State, *};
+use crate::gate::file_browser::CurrentlyOpen;
+use crate::gate::keys::{Keymap, Shortcut};
+use crate::gate::router::AutoRoute;
+use crate::gate::undo::{Action, UndoStack};
 use bevy::prelude::*;
 use bevy_prototype_lyon::{entity::ShapeBundle, prelude::*};
 use lyon_tessellation::path::path::Builder;
 use nodus::world2d::camera2d::MouseWorldPos;
 use nodus::world2d::interaction2d::Selected;
+use nodus::world2d::{GridSnap, InteractionMode, Lock};
+use std::cmp::Ordering;
+
+/// Radius of the handle of a waypoint.
+const HANDLE_RADIUS: f32 = 10.;
 
 /// Sameple the cubic bezier curve, defined by s` (start),
 /// `c1` (control point 1), `c2` (control point 2) and `e` (end),
 /// at `t` (t e [0, 1]);
-#[allow(dead_code)]
 fn qubic_bezier_point(t: f32, s: Vec2, c1: Vec2, c2: Vec2, e: Vec2) -> Vec2 {
     let u = 1. - t;
     let tt = t * t;
@@ -23,49 +31,181 @@ fn qubic_bezier_point(t: f32, s: Vec2, c1: Vec2, c2: Vec2, e: Vec2) -> Vec2 {
     p
 }
 
-/// Solve t for a point `xy` on a qubic bezier curve defined by `s` (start),
-/// `c1` (control point 1), `c2` (control point 2) and `e` (end).
+/// Get the distance between the point `p` and the line segment from `a` to `b`.
+fn segment_distance(p: Vec2, a: Vec2, b: Vec2) -> f32 {
+    let ab = b - a;
+    let len = ab.length_squared();
+    if len == 0. {
+        return p.distance(a);
+    }
+    let t = ((p - a).dot(ab) / len).clamp(0., 1.);
+    p.distance(a + ab * t)
+}
+
+/// Check if the point `xy` lies on the polyline `path`.
 ///
 /// This is just a approximation and can be used to check if a user clicked
-/// on a qubic bezier curve.
-fn t_for_point(xy: Vec2, s: Vec2, c1: Vec2, c2: Vec2, e: Vec2) -> Option<f32> {
-    use lyon_geom::*;
-
+/// on a connection line.
+fn on_path(xy: Vec2, path: &[Vec2]) -> bool {
     const EPSILON: f32 = 16.;
-    let c = CubicBezierSegment {
-        from: Point::new(s.x, s.y),
-        ctrl1: Point::new(c1.x, c1.y),
-        ctrl2: Point::new(c2.x, c2.y),
-        to: Point::new(e.x, e.y),
-    };
 
-    let possible_t_values_x = c.solve_t_for_x(xy.x);
-    let _possible_t_values_y = c.solve_t_for_y(xy.y);
+    path.windows(2)
+        .any(|w| segment_distance(xy, w[0], w[1]) <= EPSILON)
+}
 
-    for t in possible_t_values_x {
-        if t >= -0.001 && t <= 1.001 {
-            let p = c.sample(t);
+/// Sample the polyline `path` at `t` (t e [0, 1]).
+fn path_point(t: f32, path: &[Vec2]) -> Vec2 {
+    let length: f32 = path.windows(2).map(|w| w[0].distance(w[1])).sum();
+    let mut rest = length * t;
 
-            let offset = p - Point::new(xy.x, xy.y);
-            let dot = offset.x * offset.x + offset.y * offset.y;
-            if dot <= EPSILON * EPSILON {
-                return Some(t);
-            }
+    for w in path.windows(2) {
+        let l = w[0].distance(w[1]);
+        if rest <= l && l > 0. {
+            return w[0].lerp(w[1], rest / l);
         }
+        rest -= l;
     }
 
-    None
+    path.last().copied().unwrap_or_default()
 }
 
-struct ConnectionLineShape<'a> {
-    pub via: &'a [Vec2],
+/// Find the index at which the new waypoint `p` should be inserted into
+/// the waypoints `via` of a line from `from` to `to`, i.e. the index that
+/// extends the line the least.
+fn waypoint_index(from: Vec2, via: &[Vec2], to: Vec2, p: Vec2) -> usize {
+    let mut points = vec![from];
+    points.extend_from_slice(via);
+    points.push(to);
+
+    let detour = |k: usize| {
+        points[k].distance(p) + p.distance(points[k + 1]) - points[k].distance(points[k + 1])
+    };
+
+    (0..points.len() - 1)
+        .min_by(|&a, &b| detour(a).partial_cmp(&detour(b)).unwrap_or(Ordering::Equal))
+        .unwrap_or(0)
+}
+
+/// How connection lines are drawn between their end points and waypoints.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WireStyle {
+    /// Smooth cubic bezier curves.
+    Bezier,
+    /// Horizontal and vertical segments only (Manhattan routing).
+    Orthogonal,
+}
+
+/// The drawable route of a connection line.
+#[derive(Debug, Clone, PartialEq)]
+enum Route {
+    /// A start point followed by two control points and a end point
+    /// for each cubic bezier segment.
+    Bezier(Vec<Vec2>),
+    /// The corner points of a polyline.
+    Polyline(Vec<Vec2>),
+}
+
+impl Route {
+    /// Calculate the route from `from` to `to` through the waypoints `via`.
+    fn new(from: Vec2, via: &[Vec2], to: Vec2, style: WireStyle) -> Self {
+        let mut points = vec![from];
+        points.extend_from_slice(via);
+        points.push(to);
+        let n = points.len();
+
+        match style {
+            WireStyle::Bezier if via.is_empty() => {
+                Route::Bezier(ConnectionLine::calculate_nodes(from.x, from.y, to.x, to.y))
+            }
+            WireStyle::Bezier => {
+                // Catmull-Rom spline through all points. The line leaves the
+                // output and enters the input horizontally.
+                let tangent = |i: usize| -> Vec2 {
+                    if i == 0 {
+                        Vec2::new(points[0].distance(points[1]), 0.)
+                    } else if i == n - 1 {
+                        Vec2::new(points[n - 2].distance(points[n - 1]), 0.)
+                    } else {
+                        (points[i + 1] - points[i - 1]) / 2.
+                    }
+                };
+
+                let mut nodes = vec![points[0]];
+                for i in 0..n - 1 {
+                    nodes.push(points[i] + tangent(i) / 3.);
+                    nodes.push(points[i + 1] - tangent(i + 1) / 3.);
+                    nodes.push(points[i + 1]);
+                }
+                Route::Bezier(nodes)
+            }
+            WireStyle::Orthogonal if via.is_empty() => {
+                let mid = (from.x + to.x) / 2.;
+                Route::Polyline(vec![
+                    from,
+                    Vec2::new(mid, from.y),
+                    Vec2::new(mid, to.y),
+                    to,
+                ])
+            }
+            WireStyle::Orthogonal => {
+                let mut nodes = vec![points[0]];
+                for i in 0..n - 1 {
+                    let (a, b) = (points[i], points[i + 1]);
+                    // Leave each point horizontally, except for the last
+                    // segment which must enter the input horizontally.
+                    let corner = if i == n - 2 {
+                        Vec2::new(a.x, b.y)
+                    } else {
+                        Vec2::new(b.x, a.y)
+                    };
+                    if corner != a && corner != b {
+                        nodes.push(corner);
+                    }
+                    nodes.push(b);
+                }
+                Route::Polyline(nodes)
+            }
+        }
+    }
+
+    /// Approximate the route by a polyline.
+    fn flatten(&self) -> Vec<Vec2> {
+        const STEPS: usize = 16;
+
+        match self {
+            Route::Polyline(nodes) => nodes.clone(),
+            Route::Bezier(nodes) => {
+                let mut path = vec![nodes[0]];
+                for c in nodes[1..].chunks_exact(3) {
+                    let s = *path.last().unwrap();
+                    for i in 1..=STEPS {
+                        let t = i as f32 / STEPS as f32;
+                        path.push(qubic_bezier_point(t, s, c[0], c[1], c[2]));
+                    }
+                }
+                path
+            }
+        }
+    }
 }
 
-impl<'a> Geometry for ConnectionLineShape<'a> {
+impl Geometry for Route {
     fn add_geometry(&self, b: &mut Builder) {
         let mut path = PathBuilder::new();
-        path.move_to(self.via[0]);
-        path.cubic_bezier_to(self.via[1], self.via[2], self.via[3]);
+        match self {
+            Route::Bezier(nodes) => {
+                path.move_to(nodes[0]);
+                for c in nodes[1..].chunks_exact(3) {
+                    path.cubic_bezier_to(c[0], c[1], c[2]);
+                }
+            }
+            Route::Polyline(nodes) => {
+                path.move_to(nodes[0]);
+                for &p in nodes[1..].iter() {
+                    path.line_to(p);
+                }
+            }
+        }
 
         b.concatenate(&[path.build().0.as_slice()]);
     }
@@ -86,6 +226,11 @@ pub struct DataPoint {
 #[derive(Component)]
 pub struct LineHighLight;
 
+/// The waypoint of a connection line that is currently dragged,
+/// given by the line and the index of the waypoint.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct WaypointDrag(pub Option<(Entity, usize)>);
+
 pub fn draw_line_system(
     mut commands: Commands,
     mut q_line: Query<
@@ -94,6 +239,7 @@ pub fn draw_line_system(
             &mut ConnectionLine,
             Option<&Children>,
             Option<&Selected>,
+            Option<&AutoRoute>,
         ),
         (),
     >,
@@ -102,10 +248,11 @@ pub fn draw_line_system(
     q_highlight: Query<Entity, With<LineHighLight>>,
     mut lr: ResMut<LineResource>,
     time: Res<Time>,
+    style: Res<WireStyle>,
 ) {
     lr.count += time.delta_seconds();
 
-    for (entity, mut conn_line, _children, selected) in q_line.iter_mut() {
+    for (entity, mut conn_line, _children, selected, auto) in q_line.iter_mut() {
         if let Ok((t_parent, t_conn, t_from)) = q_transform.get(conn_line.output.entity) {
             // Set connection line color based on the value of the output.
             let color = if let Ok(outputs) = q_outputs.get(t_parent.0) {
@@ -119,26 +266,24 @@ pub fn draw_line_system(
             };
 
             if let Ok((_, _, t_to)) = q_transform.get(conn_line.input.entity) {
-                let via = ConnectionLine::calculate_nodes(
-                    t_from.translation.x,
-                    t_from.translation.y,
-                    t_to.translation.x,
-                    t_to.translation.y,
+                // Automatically routed lines consist of horizontal and
+                // vertical segments, independent of the chosen style.
+                let style = if auto.is_some() { WireStyle::Orthogonal } else { *style };
+                let route = Route::new(
+                    t_from.translation.truncate(),
+                    &conn_line.via,
+                    t_to.translation.truncate(),
+                    style,
                 );
-                let _l = ((via[3].x - via[0].x).powi(2) + (via[3].y - via[0].y).powi(2)).sqrt();
 
                 // Remove current line path.
                 commands.entity(entity).remove_bundle::<ShapeBundle>();
 
                 // Create new path.
-                let mut path = PathBuilder::new();
-                path.move_to(via[0]);
-                path.cubic_bezier_to(via[1], via[2], via[3]);
-
                 let new_ent = commands
                     .entity(entity)
                     .insert_bundle(GeometryBuilder::build_as(
-                        &ConnectionLineShape { via: &via },
+                        &route,
                         DrawMode::Stroke(StrokeMode::new(color, 8.0)),
                         Transform::from_xyz(0., 0., 1.),
                     ))
@@ -148,11 +293,11 @@ pub fn draw_line_system(
                     commands.entity(e).despawn_recursive();
                 }
 
-                // Highlight if selected.
+                // Highlight if selected and show the handles of the waypoints.
                 if let Some(_) = selected {
                     let child = commands
                         .spawn_bundle(GeometryBuilder::build_as(
-                            &ConnectionLineShape { via: &via },
+                            &route,
                             DrawMode::Stroke(StrokeMode::new(
                                 Color::rgba(0.62, 0.79, 0.94, 0.5),
                                 18.0,
@@ -163,9 +308,31 @@ pub fn draw_line_system(
                         .id();
 
                     commands.entity(new_ent).add_child(child);
+
+                    for &p in conn_line.via.iter() {
+                        let handle = commands
+                            .spawn_bundle(GeometryBuilder::build_as(
+                                &shapes::Circle {
+                                    radius: HANDLE_RADIUS,
+                                    center: p,
+                                },
+                                DrawMode::Outlined {
+                                    fill_mode: FillMode::color(Color::WHITE),
+                                    outline_mode: StrokeMode::new(
+                                        Color::rgb(0.62, 0.79, 0.94),
+                                        4.0,
+                                    ),
+                                },
+                                Transform::from_xyz(0., 0., 1.),
+                            ))
+                            .insert(LineHighLight)
+                            .id();
+
+                        commands.entity(new_ent).add_child(handle);
+                    }
                 }
 
-                conn_line.via = via;
+                conn_line.path = route.flatten();
 
                 /*
                  * TODO: nice visual effect but probably distracting as well.
@@ -211,13 +378,7 @@ pub fn line_selection_system(
 ) {
     if mb.just_pressed(MouseButton::Left) {
         for (entity, line) in q_line.iter() {
-            if let Some(_) = t_for_point(
-                Vec2::new(mw.x, mw.y),
-                line.via[0].clone(),
-                line.via[1].clone(),
-                line.via[2].clone(),
-                line.via[3].clone(),
-            ) {
+            if on_path(Vec2::new(mw.x, mw.y), &line.path) {
                 commands.entity(entity).insert(Selected);
                 break;
             }
@@ -225,12 +386,110 @@ pub fn line_selection_system(
     }
 }
 
+/// Add, drag and remove the waypoints of selected connection lines.
+///
+/// A click on a selected line while holding `ctrl` adds a new waypoint,
+/// a click on a waypoint while holding `ctrl` removes it. Waypoints are
+/// moved by dragging them with the left mouse button and snap to the
+/// grid if snapping is active. Editing the waypoints of a line stops
+/// the router from routing it again.
+///
+/// Each edit, i.e. each drag, can be undone as a single action.
+pub fn waypoint_system(
+    mut commands: Commands,
+    mw: Res<MouseWorldPos>,
+    mb: Res<Input<MouseButton>>,
+    input_keyboard: Res<Input<KeyCode>>,
+    mode: Res<InteractionMode>,
+    lock: Res<Lock>,
+    snap: Res<GridSnap>,
+    mut drag: ResMut<WaypointDrag>,
+    mut curr_open: ResMut<CurrentlyOpen>,
+    mut stack: ResMut<UndoStack>,
+    // The waypoints of the dragged line before the drag started.
+    mut before: Local<Option<(Vec<Vec2>, bool)>>,
+    mut q_line: Query<(Entity, &mut ConnectionLine, Option<&AutoRoute>), With<Selected>>,
+) {
+    if mb.just_released(MouseButton::Left) {
+        if let (Some((entity, _)), Some((via, auto))) = (drag.0, before.take()) {
+            if let Ok((_, line, _)) = q_line.get(entity) {
+                if line.via != via {
+                    stack.push(Action::Waypoints(vec![(entity, via, auto)]));
+                }
+            }
+        }
+        drag.0 = None;
+    }
+
+    if lock.0 || *mode != InteractionMode::Select {
+        return;
+    }
+
+    let mouse = Vec2::new(mw.x, mw.y);
+    let point = if snap.active(&input_keyboard) {
+        snap.snap(mouse)
+    } else {
+        mouse
+    };
+
+    if mb.just_pressed(MouseButton::Left) {
+        let ctrl = input_keyboard.pressed(KeyCode::LControl)
+            || input_keyboard.pressed(KeyCode::RControl);
+
+        for (entity, mut line, auto) in q_line.iter_mut() {
+            if let Some(i) = line
+                .via
+                .iter()
+                .position(|p| p.distance(mouse) <= HANDLE_RADIUS * 1.5)
+            {
+                if ctrl {
+                    stack.push(Action::Waypoints(vec![(entity, line.via.clone(), auto.is_some())]));
+                    line.via.remove(i);
+                    curr_open.dirty = true;
+                    commands.entity(entity).remove::<AutoRoute>();
+                } else {
+                    *before = Some((line.via.clone(), auto.is_some()));
+                    drag.0 = Some((entity, i));
+                }
+                return;
+            }
+        }
+
+        if ctrl {
+            for (entity, mut line, auto) in q_line.iter_mut() {
+                if line.path.len() >= 2 && on_path(mouse, &line.path) {
+                    stack.push(Action::Waypoints(vec![(entity, line.via.clone(), auto.is_some())]));
+                    let from = line.path[0];
+                    let to = line.path[line.path.len() - 1];
+                    let i = waypoint_index(from, &line.via, to, mouse);
+                    line.via.insert(i, point);
+                    curr_open.dirty = true;
+                    commands.entity(entity).remove::<AutoRoute>();
+                    return;
+                }
+            }
+        }
+    } else if mb.pressed(MouseButton::Left) {
+        if let Some((entity, i)) = drag.0 {
+            if let Ok((_, mut line, _)) = q_line.get_mut(entity) {
+                if i < line.via.len() && line.via[i] != point {
+                    line.via[i] = point;
+                    curr_open.dirty = true;
+                    commands.entity(entity).remove::<AutoRoute>();
+                }
+            }
+        }
+    }
+}
+
 pub fn delete_line_system(
     input_keyboard: Res<Input<KeyCode>>,
     mut ev_disconnect: EventWriter<DisconnectEvent>,
     q_line: Query<Entity, (With<Selected>, With<ConnectionLine>)>,
+    lock: Res<Lock>,
+    keymap: Res<Keymap>,
 ) {
-    if input_keyboard.just_pressed(KeyCode::Delete) {
+    if !lock.0 && keymap.just_pressed(Shortcut::Delete, &input_keyboard) {
         for entity in q_line.iter() {
             ev_disconnect.send(DisconnectEvent {
                 connection: entity,
@@ -249,21 +508,13 @@ pub fn draw_data_flow(
 ) {
     for (entity, parent, mut transform, mut data) in q_point.iter_mut() {
         if let Ok(line) = q_line.get(parent.0) {
-            let l = ((line.via[3].x - line.via[0].x).powi(2)
-                + (line.via[3].y - line.via[0].y).powi(2))
-            .sqrt();
+            let l: f32 = line.path.windows(2).map(|w| w[0].distance(w[1])).sum();
             data.steps += (1. / (l / 300.)) * time.delta_seconds();
 
             if data.steps >= 1.0 {
                 commands.entity(entity).despawn_recursive();
             } else {
-                let p = qubic_bezier_point(
-                    data.steps,
-                    line.via[0].clone(),
-                    line.via[1].clone(),
-                    line.via[2].clone(),
-                    line.via[3].clone(),
-                );
+                let p = path_point(data.steps, &line.path);
 
                 transform.translation.x = p.x;
                 transform.translation.y = p.y;
@@ -1,4 +1,5 @@
 use crate::gate::core::*;
+use crate::gate::notify::Log;
 use bevy::prelude::*;
 use bevy_prototype_lyon::{entity::ShapeBundle, prelude::*};
 use nodus::world2d::camera2d::MouseWorldPos;
@@ -116,9 +117,10 @@ pub fn drag_connector_system(
     q_dragged: Query<(Entity, &GlobalTransform, &Connector), (With<Drag>, With<Free>)>,
     // The visual connection line indicator to update.
     q_conn_line: Query<Entity, With<ConnectionLineIndicator>>,
-    // Posible free connector the mouse currently hovers over.
-    q_drop: Query<(Entity, &Connector), (With<Hover>, With<Free>)>,
+    // Posible connector the mouse currently hovers over.
+    q_drop: Query<(Entity, &Connector, Option<&Free>), With<Hover>>,
     mut ev_connect: EventWriter<ConnectEvent>,
+    mut log: ResMut<Log>,
 ) {
     if let Ok((entity, transform, connector)) = q_dragged.get_single() {
         // If the LMB is released we check if we can connect two connectors.
@@ -133,10 +135,19 @@ pub fn drag_connector_system(
             }
 
             // Try to connect input and output.
-            if let Ok((drop_target, drop_connector)) = q_drop.get_single() {
-                eprintln!("drop");
-                // One can only connect an input to an output.
-                if connector.ctype != drop_connector.ctype {
+            if let Some((drop_target, drop_connector, free)) =
+                q_drop.iter().find(|(drop_target, ..)| *drop_target != entity)
+            {
+                if connector.ctype == drop_connector.ctype {
+                    // One can only connect an input to an output.
+                    log.warn(match connector.ctype {
+                        ConnectorType::In => "an input can only be connected to an output",
+                        ConnectorType::Out => "an output can only be connected to an input",
+                    });
+                } else if free.is_none() {
+                    // Each input is driven by a single output.
+                    log.warn("the input is already connected");
+                } else {
                     // Send connection event.
                     match connector.ctype {
                         ConnectorType::In => {
@@ -145,6 +156,8 @@ pub fn drag_connector_system(
                                 output_index: drop_connector.index,
                                 input: entity,
                                 input_index: connector.index,
+                                via: Vec::new(),
+                                auto: false,
                                 signal_success: true,
                             });
                         }
@@ -154,6 +167,8 @@ pub fn drag_connector_system(
                                 output_index: connector.index,
                                 input: drop_target,
                                 input_index: drop_connector.index,
+                                via: Vec::new(),
+                                auto: false,
                                 signal_success: true,
                             });
                         }
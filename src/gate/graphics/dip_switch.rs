@@ -0,0 +1,173 @@
+use super::*;
+use crate::gate::core::{State, *};
+use crate::gate::serialize::*;
+use bevy::prelude::*;
+use bevy_prototype_lyon::prelude::*;
+use nodus::world2d::interaction2d::{Draggable, Hover, Interactable, Selectable};
+use std::collections::HashMap;
+use std::sync::atomic::Ordering;
+
+/// Vertical distance between two levers of a DIP switch bank.
+const LEVER_STEP: f32 = GATE_WIDTH * 0.75;
+/// Width of the slot a lever slides in.
+const SLOT_WIDTH: f32 = GATE_WIDTH;
+
+/// A bank of DIP switches.
+///
+/// Every switch of the bank can be either on or off and drives
+/// its own output, i.e. a bank with N switches has N outputs.
+#[derive(Debug, Clone, PartialEq, Hash, Component)]
+pub struct DipSwitch;
+
+/// The slot of a single switch the user can click on.
+#[derive(Debug, Clone, PartialEq, Hash, Component)]
+pub struct DipSwitchLever {
+    /// Index of the output this lever drives.
+    pub index: usize,
+}
+
+/// The visual knob that slides within a [`DipSwitchLever`].
+#[derive(Debug, Clone, PartialEq, Hash, Component)]
+pub struct DipSwitchKnob;
+
+impl DipSwitch {
+    /// Minimum and maximum number of switches.
+    pub const RANGE: NodeRange = NodeRange { min: 1, max: 16 };
+
+    fn knob_offset(state: State) -> f32 {
+        if state == State::High {
+            SLOT_WIDTH / 4.
+        } else {
+            -SLOT_WIDTH / 4.
+        }
+    }
+
+    /// Create a new DIP switch bank at the specified position.
+    ///
+    /// The bank gets one switch for every entry in `states`.
+    pub fn spawn(
+        commands: &mut Commands,
+        position: Vec2,
+        rotation: Quat,
+        states: Vec<State>,
+    ) -> Entity {
+        let z = Z_INDEX.fetch_add(1, Ordering::Relaxed) as f32;
+        let n = states.len();
+        let width = GATE_WIDTH * 1.5;
+        let height = n as f32 * LEVER_STEP + 16.;
+
+        let shape = shapes::Rectangle {
+            extents: Vec2::new(width, height),
+            ..shapes::Rectangle::default()
+        };
+
+        let mut functions: Vec<Box<dyn Fn(&[State]) -> State + Send + Sync>> = Vec::new();
+        for i in 0..n {
+            functions.push(Box::new(move |inputs| inputs[i]));
+        }
+
+        let parent = commands
+            .spawn_bundle(GeometryBuilder::build_as(
+                &shape,
+                DrawMode::Outlined {
+                    fill_mode: FillMode::color(Color::WHITE),
+                    outline_mode: StrokeMode::new(Color::BLACK, 6.0),
+                },
+                Transform::from_xyz(position.x, position.y, z).with_rotation(rotation),
+            ))
+            .insert(DipSwitch)
+            .insert(Name("DIP Switch".to_string()))
+            .insert(Inputs(states.clone()))
+            .insert(Outputs(states.clone()))
+            .insert(Transitions(functions))
+            .insert(Targets(vec![TargetMap::from(HashMap::new()); n]))
+            .insert(NodeType::DipSwitch)
+            .insert(Interactable::new(
+                Vec2::new(0., 0.),
+                Vec2::new(width, height),
+                1,
+            ))
+            .insert(Selectable)
+            .insert(Draggable { update: true })
+            .id();
+
+        let mut entvec: Vec<Entity> = Vec::new();
+        for (i, &state) in states.iter().enumerate() {
+            let y = height / 2. - 8. - LEVER_STEP / 2. - i as f32 * LEVER_STEP;
+
+            let knob = commands
+                .spawn_bundle(GeometryBuilder::build_as(
+                    &shapes::Rectangle {
+                        extents: Vec2::new(SLOT_WIDTH * 0.4, LEVER_STEP * 0.4),
+                        ..shapes::Rectangle::default()
+                    },
+                    DrawMode::Fill(FillMode::color(Color::BLACK)),
+                    Transform::from_xyz(DipSwitch::knob_offset(state), 0., 1.),
+                ))
+                .insert(DipSwitchKnob)
+                .id();
+
+            let lever = commands
+                .spawn_bundle(GeometryBuilder::build_as(
+                    &shapes::Rectangle {
+                        extents: Vec2::new(SLOT_WIDTH, LEVER_STEP * 0.6),
+                        ..shapes::Rectangle::default()
+                    },
+                    DrawMode::Outlined {
+                        fill_mode: FillMode::color(Color::WHITE),
+                        outline_mode: StrokeMode::new(Color::BLACK, 4.0),
+                    },
+                    Transform::from_xyz(0., y, 1.),
+                ))
+                .insert(DipSwitchLever { index: i })
+                .insert(Interactable::new(
+                    Vec2::new(0., 0.),
+                    Vec2::new(SLOT_WIDTH, LEVER_STEP * 0.6),
+                    1,
+                ))
+                .id();
+            commands.entity(lever).push_children(&[knob]);
+            entvec.push(lever);
+
+            entvec.push(Connector::with_line(
+                commands,
+                Vec3::new(width / 2. + GATE_SIZE * 0.2, y, 0.),
+                GATE_SIZE * 0.1,
+                ConnectorType::Out,
+                i,
+                format!("y{}", i + 1),
+            ));
+        }
+
+        commands.entity(parent).push_children(&entvec);
+
+        parent
+    }
+}
+
+/// Register clicks on the levers of a DIP switch bank and flip the
+/// associated switch.
+pub fn dip_switch_system(
+    mut q_inputs: Query<&mut Inputs, With<DipSwitch>>,
+    q_lever: Query<(&Parent, &DipSwitchLever, &Children), With<Hover>>,
+    mut q_knob: Query<&mut Transform, With<DipSwitchKnob>>,
+    mb: Res<Input<MouseButton>>,
+) {
+    if mb.just_pressed(MouseButton::Left) {
+        for (parent, lever, children) in q_lever.iter() {
+            if let Ok(mut inputs) = q_inputs.get_mut(parent.0) {
+                let next = match inputs[lever.index] {
+                    State::High => State::Low,
+                    _ => State::High,
+                };
+                inputs[lever.index] = next;
+
+                for &child in children.iter() {
+                    if let Ok(mut transform) = q_knob.get_mut(child) {
+                        transform.translation.x = DipSwitch::knob_offset(next);
+                    }
+                }
+            }
+        }
+    }
+}
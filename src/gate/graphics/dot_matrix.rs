@@ -0,0 +1,278 @@
+use super::segment_display::{COLOR_OFF, COLOR_ON};
+use super::*;
+use crate::gate::core::{State, *};
+use crate::gate::serialize::*;
+use bevy::prelude::*;
+use bevy_prototype_lyon::prelude::*;
+use nodus::world2d::interaction2d::{Draggable, Interactable, Selectable};
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::Ordering;
+
+/// Distance between the centers of two neighbouring dots.
+const DOT_STEP: f32 = GATE_WIDTH / 2.;
+
+/// The way the dots of a [`DotMatrix`] are addressed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum MatrixAddressing {
+    /// There is one input for each row, followed by one input for
+    /// each column. A dot is lit if both its row and its column
+    /// input are [`State::High`].
+    RowColumn,
+    /// The inputs are the data bits of a row (one per column),
+    /// followed by the row address (most significant bit first) and a
+    /// clock. On a rising edge of the clock the data bits are written
+    /// to the addressed row of an internal frame buffer.
+    FrameBuffer,
+}
+
+impl MatrixAddressing {
+    pub fn label(&self) -> &'static str {
+        match self {
+            MatrixAddressing::RowColumn => "Row/ Column",
+            MatrixAddressing::FrameBuffer => "Frame Buffer",
+        }
+    }
+}
+
+/// A matrix of dots that can be lit individually.
+#[derive(Debug, Clone, PartialEq, Component)]
+pub struct DotMatrix {
+    pub rows: usize,
+    pub cols: usize,
+    pub addressing: MatrixAddressing,
+    /// The frame buffer, one bit mask per row. Bit `c` of a mask
+    /// belongs to column `c`.
+    pub frame: Vec<u32>,
+    /// The dots in row-major order.
+    dots: Vec<Entity>,
+    /// The state each dot is currently drawn with.
+    lit: Vec<bool>,
+    /// Last known state of the clock input.
+    clk: State,
+}
+
+impl DotMatrix {
+    /// Minimum and maximum number of rows and columns.
+    pub const RANGE: NodeRange = NodeRange { min: 1, max: 16 };
+
+    /// Number of address bits required to address `rows` rows.
+    pub fn address_bits(rows: usize) -> usize {
+        let mut bits = 0;
+        while (1 << bits) < rows {
+            bits += 1;
+        }
+        bits
+    }
+
+    /// Number of inputs of a matrix with the given configuration.
+    pub fn input_count(rows: usize, cols: usize, addressing: MatrixAddressing) -> usize {
+        match addressing {
+            MatrixAddressing::RowColumn => rows + cols,
+            MatrixAddressing::FrameBuffer => cols + DotMatrix::address_bits(rows) + 1,
+        }
+    }
+
+    /// Get the persistent state of the matrix.
+    pub fn state(&self) -> NodeState {
+        NodeState::DotMatrix(self.rows, self.cols, self.addressing, self.frame.clone())
+    }
+
+    /// Create a new dot matrix at the specified position.
+    pub fn spawn(
+        commands: &mut Commands,
+        position: Vec2,
+        rotation: Quat,
+        rows: usize,
+        cols: usize,
+        addressing: MatrixAddressing,
+        mut frame: Vec<u32>,
+    ) -> Entity {
+        let z = Z_INDEX.fetch_add(1, Ordering::Relaxed) as f32;
+        let width = cols as f32 * DOT_STEP + DOT_STEP;
+        let height = rows as f32 * DOT_STEP + DOT_STEP;
+        let x = |c: usize| (c as f32 - (cols - 1) as f32 / 2.) * DOT_STEP;
+        let y = |r: usize| ((rows - 1) as f32 / 2. - r as f32) * DOT_STEP;
+
+        frame.resize(rows, 0);
+        let mask = if cols >= 32 { u32::MAX } else { (1 << cols) - 1 };
+        for row in frame.iter_mut() {
+            *row &= mask;
+        }
+
+        let mut dots = Vec::new();
+        for r in 0..rows {
+            for c in 0..cols {
+                dots.push(
+                    commands
+                        .spawn_bundle(GeometryBuilder::build_as(
+                            &shapes::Circle {
+                                radius: DOT_STEP * 0.375,
+                                center: Vec2::new(0., 0.),
+                            },
+                            DrawMode::Outlined {
+                                fill_mode: FillMode::color(COLOR_OFF),
+                                outline_mode: StrokeMode::new(Color::BLACK, 2.0),
+                            },
+                            Transform::from_xyz(x(c), y(r), 0.1),
+                        ))
+                        .id(),
+                );
+            }
+        }
+
+        let mut connectors = Vec::new();
+        let left = -(width / 2. + GATE_SIZE * 0.2);
+        let bottom = -(height / 2. + GATE_SIZE * 0.2);
+        match addressing {
+            MatrixAddressing::RowColumn => {
+                for r in 0..rows {
+                    connectors.push(Connector::with_line(
+                        commands,
+                        Vec3::new(left, y(r), 0.),
+                        GATE_SIZE * 0.1,
+                        ConnectorType::In,
+                        r,
+                        format!("r{}", r),
+                    ));
+                }
+                for c in 0..cols {
+                    connectors.push(Connector::with_line_vert(
+                        commands,
+                        Vec3::new(x(c), bottom, 0.),
+                        GATE_SIZE * 0.1,
+                        ConnectorType::In,
+                        rows + c,
+                        format!("c{}", c),
+                    ));
+                }
+            }
+            MatrixAddressing::FrameBuffer => {
+                for c in 0..cols {
+                    connectors.push(Connector::with_line_vert(
+                        commands,
+                        Vec3::new(x(c), bottom, 0.),
+                        GATE_SIZE * 0.1,
+                        ConnectorType::In,
+                        c,
+                        format!("d{}", c),
+                    ));
+                }
+
+                let bits = DotMatrix::address_bits(rows);
+                let step = -(height / (bits + 2) as f32);
+                for k in 0..=bits {
+                    let name = if k < bits {
+                        format!("a{}", bits - 1 - k)
+                    } else {
+                        "clk".to_string()
+                    };
+                    connectors.push(Connector::with_line(
+                        commands,
+                        Vec3::new(left, height / 2. + (k + 1) as f32 * step, 0.),
+                        GATE_SIZE * 0.1,
+                        ConnectorType::In,
+                        cols + k,
+                        name,
+                    ));
+                }
+            }
+        }
+
+        let parent = commands
+            .spawn_bundle(Gate::body(
+                Vec3::new(position.x, position.y, z),
+                rotation,
+                Vec2::new(width, height),
+            ))
+            .insert(DotMatrix {
+                rows,
+                cols,
+                addressing,
+                frame,
+                dots: dots.clone(),
+                lit: vec![false; rows * cols],
+                clk: State::None,
+            })
+            .insert(Name("Dot Matrix".to_string()))
+            .insert(Inputs(vec![
+                State::None;
+                DotMatrix::input_count(rows, cols, addressing)
+            ]))
+            .insert(NodeType::DotMatrix)
+            .insert(Interactable::new(
+                Vec2::new(0., 0.),
+                Vec2::new(width, height),
+                1,
+            ))
+            .insert(Selectable)
+            .insert(Draggable { update: true })
+            .id();
+
+        commands.entity(parent).push_children(&dots);
+        commands.entity(parent).push_children(&connectors);
+
+        parent
+    }
+}
+
+pub fn dot_matrix_system(
+    mut q_matrix: Query<(&Inputs, &mut DotMatrix)>,
+    mut draw: Query<&mut DrawMode>,
+) {
+    for (inputs, mut matrix) in q_matrix.iter_mut() {
+        let (rows, cols) = (matrix.rows, matrix.cols);
+
+        if matrix.addressing == MatrixAddressing::FrameBuffer {
+            let bits = DotMatrix::address_bits(rows);
+            let clk = inputs[cols + bits];
+
+            // Write the data bits on a rising edge of the clock.
+            if clk == State::High && matrix.clk != State::High {
+                let mut address = 0;
+                for k in 0..bits {
+                    address <<= 1;
+                    if inputs[cols + k] == State::High {
+                        address |= 1;
+                    }
+                }
+
+                if address < rows {
+                    let mut data = 0;
+                    for c in 0..cols {
+                        if inputs[c] == State::High {
+                            data |= 1 << c;
+                        }
+                    }
+                    matrix.frame[address] = data;
+                }
+            }
+            matrix.clk = clk;
+        }
+
+        for r in 0..rows {
+            for c in 0..cols {
+                let on = match matrix.addressing {
+                    MatrixAddressing::RowColumn => {
+                        inputs[r] == State::High && inputs[rows + c] == State::High
+                    }
+                    MatrixAddressing::FrameBuffer => matrix.frame[r] & (1 << c) != 0,
+                };
+
+                // Update the visuals of a dot only if its state has changed.
+                let i = r * cols + c;
+                if matrix.lit[i] != on {
+                    if let Ok(mut mode) = draw.get_mut(matrix.dots[i]) {
+                        if let DrawMode::Outlined {
+                            ref mut fill_mode,
+                            outline_mode: _,
+                        } = *mode
+                        {
+                            fill_mode.color = if on { COLOR_ON } else { COLOR_OFF };
+                        }
+                    }
+                    matrix.lit[i] = on;
+                }
+            }
+        }
+    }
+}
@@ -234,6 +234,14 @@ impl Gate {
 }
 
 impl Gate {
+    /// Minimum and maximum number of inputs of the gates with a
+    /// configurable number of inputs, e.g. AND gates.
+    pub const INPUT_RANGE: NodeRange = NodeRange { min: 2, max: 16 };
+    /// Number of inputs of a NOT gate.
+    pub const NOT_RANGE: NodeRange = NodeRange { min: 1, max: 1 };
+    /// Number of outputs of a gate.
+    pub const OUTPUT_RANGE: NodeRange = NodeRange { min: 1, max: 1 };
+
     pub fn not_gate_bs_(
         commands: &mut Commands,
         position: Vec2,
@@ -248,8 +256,8 @@ impl Gate {
             position,
             rotation,
             Vec2::new(GATE_WIDTH, GATE_HEIGHT),
-            NodeRange { min: 1, max: 1 },
-            NodeRange { min: 1, max: 1 },
+            Self::NOT_RANGE,
+            Self::OUTPUT_RANGE,
             ins,
             outs,
             trans![|inputs| {
@@ -288,8 +296,8 @@ impl Gate {
             position,
             rotation,
             Vec2::new(GATE_WIDTH, GATE_HEIGHT),
-            NodeRange { min: 2, max: 16 },
-            NodeRange { min: 1, max: 1 },
+            Self::INPUT_RANGE,
+            Self::OUTPUT_RANGE,
             ins,
             outs,
             trans![|inputs| {
@@ -337,8 +345,8 @@ impl Gate {
             position,
             rotation,
             Vec2::new(GATE_WIDTH, GATE_HEIGHT),
-            NodeRange { min: 2, max: 16 },
-            NodeRange { min: 1, max: 1 },
+            Self::INPUT_RANGE,
+            Self::OUTPUT_RANGE,
             ins,
             outs,
             trans![|inputs| {
@@ -386,8 +394,8 @@ impl Gate {
             position,
             rotation,
             Vec2::new(GATE_WIDTH, GATE_HEIGHT),
-            NodeRange { min: 2, max: 16 },
-            NodeRange { min: 1, max: 1 },
+            Self::INPUT_RANGE,
+            Self::OUTPUT_RANGE,
             ins,
             outs,
             trans![|inputs| {
@@ -435,8 +443,8 @@ impl Gate {
             position,
             rotation,
             Vec2::new(GATE_WIDTH, GATE_HEIGHT),
-            NodeRange { min: 2, max: 16 },
-            NodeRange { min: 1, max: 1 },
+            Self::INPUT_RANGE,
+            Self::OUTPUT_RANGE,
             ins,
             outs,
             trans![|inputs| {
@@ -484,8 +492,8 @@ impl Gate {
             position,
             rotation,
             Vec2::new(GATE_WIDTH, GATE_HEIGHT),
-            NodeRange { min: 2, max: 16 },
-            NodeRange { min: 1, max: 1 },
+            Self::INPUT_RANGE,
+            Self::OUTPUT_RANGE,
             ins,
             outs,
             trans![|inputs| {
@@ -0,0 +1,223 @@
+use super::*;
+use crate::gate::core::{State, *};
+use crate::gate::serialize::*;
+use bevy::prelude::*;
+use bevy_prototype_lyon::prelude::*;
+use nodus::world2d::interaction2d::{Draggable, Hover, Interactable, Selectable};
+use std::collections::HashMap;
+use std::sync::atomic::Ordering;
+
+/// Side length of a single key.
+const KEY_SIZE: f32 = GATE_WIDTH * 0.75;
+/// Distance between the centers of two neighbouring keys.
+const KEY_STEP: f32 = GATE_WIDTH * 0.875;
+
+/// Arrangement of the keys, row by row, as known from
+/// classic hex keypads (e.g. the COSMAC VIP).
+const KEY_LAYOUT: [[u8; 4]; 4] = [
+    [0x1, 0x2, 0x3, 0xC],
+    [0x4, 0x5, 0x6, 0xD],
+    [0x7, 0x8, 0x9, 0xE],
+    [0xA, 0x0, 0xB, 0xF],
+];
+
+/// A hex keypad with 16 keys.
+///
+/// The keypad has five outputs. The first four outputs present the
+/// 4-bit code of the last key pressed (most significant bit first, i.e.
+/// the outputs can be wired directly to a 7-segment display).
+/// The fifth output is a strobe that is [`State::High`] as long as
+/// a key is held down.
+#[derive(Debug, Clone, PartialEq, Hash, Component)]
+pub struct HexKeypad;
+
+/// A single key of a [`HexKeypad`].
+#[derive(Debug, Clone, PartialEq, Hash, Component)]
+pub struct HexKey {
+    pub value: u8,
+}
+
+impl HexKeypad {
+    /// Index of the strobe output.
+    pub const STROBE: usize = 4;
+
+    /// Decode the 4-bit code presented by the given states.
+    pub fn code(states: &[State]) -> u8 {
+        let mut code = 0;
+        for i in 0..4 {
+            if states[i] == State::High {
+                code |= 1 << (3 - i);
+            }
+        }
+        code
+    }
+
+    /// Write the given 4-bit `code` to the first four `states`.
+    fn encode(code: u8, states: &mut [State]) {
+        for i in 0..4 {
+            states[i] = if code & (1 << (3 - i)) != 0 {
+                State::High
+            } else {
+                State::Low
+            };
+        }
+    }
+
+    /// Create a new hex keypad at the specified position presenting `code`.
+    pub fn spawn(
+        commands: &mut Commands,
+        position: Vec2,
+        rotation: Quat,
+        code: u8,
+        font: Handle<Font>,
+    ) -> Entity {
+        let z = Z_INDEX.fetch_add(1, Ordering::Relaxed) as f32;
+        let size = KEY_STEP * 4. + 16.;
+
+        let mut states = vec![State::Low; 5];
+        HexKeypad::encode(code, &mut states);
+
+        let mut functions: Vec<Box<dyn Fn(&[State]) -> State + Send + Sync>> = Vec::new();
+        for i in 0..5 {
+            functions.push(Box::new(move |inputs| inputs[i]));
+        }
+
+        let shape = shapes::Rectangle {
+            extents: Vec2::new(size, size),
+            ..shapes::Rectangle::default()
+        };
+
+        let parent = commands
+            .spawn_bundle(GeometryBuilder::build_as(
+                &shape,
+                DrawMode::Outlined {
+                    fill_mode: FillMode::color(Color::WHITE),
+                    outline_mode: StrokeMode::new(Color::BLACK, 6.0),
+                },
+                Transform::from_xyz(position.x, position.y, z).with_rotation(rotation),
+            ))
+            .insert(HexKeypad)
+            .insert(Name("Hex Keypad".to_string()))
+            .insert(Inputs(states.clone()))
+            .insert(Outputs(states))
+            .insert(Transitions(functions))
+            .insert(Targets(vec![TargetMap::from(HashMap::new()); 5]))
+            .insert(NodeType::HexKeypad)
+            .insert(Interactable::new(
+                Vec2::new(0., 0.),
+                Vec2::new(size, size),
+                1,
+            ))
+            .insert(Selectable)
+            .insert(Draggable { update: true })
+            .id();
+
+        let mut entvec: Vec<Entity> = Vec::new();
+        for (row, keys) in KEY_LAYOUT.iter().enumerate() {
+            for (col, &value) in keys.iter().enumerate() {
+                let label = commands
+                    .spawn_bundle(Text2dBundle {
+                        text: Text::with_section(
+                            &format!("{:X}", value),
+                            TextStyle {
+                                font: font.clone(),
+                                font_size: 30.0,
+                                color: Color::BLACK,
+                            },
+                            TextAlignment {
+                                horizontal: HorizontalAlign::Center,
+                                vertical: VerticalAlign::Center,
+                            },
+                        ),
+                        transform: Transform::from_xyz(0., 0., 1.),
+                        ..Default::default()
+                    })
+                    .id();
+
+                let key = commands
+                    .spawn_bundle(GeometryBuilder::build_as(
+                        &shapes::Rectangle {
+                            extents: Vec2::new(KEY_SIZE, KEY_SIZE),
+                            ..shapes::Rectangle::default()
+                        },
+                        DrawMode::Outlined {
+                            fill_mode: FillMode::color(Color::WHITE),
+                            outline_mode: StrokeMode::new(Color::BLACK, 4.0),
+                        },
+                        Transform::from_xyz(
+                            (col as f32 - 1.5) * KEY_STEP,
+                            (1.5 - row as f32) * KEY_STEP,
+                            1.,
+                        ),
+                    ))
+                    .insert(HexKey { value })
+                    .insert(Interactable::new(
+                        Vec2::new(0., 0.),
+                        Vec2::new(KEY_SIZE, KEY_SIZE),
+                        1,
+                    ))
+                    .id();
+                commands.entity(key).push_children(&[label]);
+                entvec.push(key);
+            }
+        }
+
+        let step = -(size / 6.);
+        for i in 0..5 {
+            entvec.push(Connector::with_line(
+                commands,
+                Vec3::new(
+                    size / 2. + GATE_SIZE * 0.2,
+                    size / 2. + (i + 1) as f32 * step,
+                    0.,
+                ),
+                GATE_SIZE * 0.1,
+                ConnectorType::Out,
+                i,
+                format!("y{}", i + 1),
+            ));
+        }
+
+        commands.entity(parent).push_children(&entvec);
+
+        parent
+    }
+}
+
+/// Present the code of the key held down on the outputs of its keypad
+/// and raise the strobe as long as the key is pressed.
+pub fn hex_keypad_system(
+    mut q_inputs: Query<(Entity, &mut Inputs), With<HexKeypad>>,
+    mut q_key: Query<(&Parent, &HexKey, Option<&Hover>, &mut DrawMode)>,
+    mb: Res<Input<MouseButton>>,
+) {
+    let mut pressed: HashMap<Entity, u8> = HashMap::new();
+
+    for (parent, key, hover, mut mode) in q_key.iter_mut() {
+        let down = hover.is_some() && mb.pressed(MouseButton::Left);
+        if down {
+            pressed.insert(parent.0, key.value);
+        }
+
+        // Only touch the visuals if the state of the key has changed.
+        let color = if down { Color::BLUE } else { Color::WHITE };
+        if matches!(&*mode, DrawMode::Outlined { fill_mode, .. } if fill_mode.color != color) {
+            if let DrawMode::Outlined {
+                ref mut fill_mode,
+                outline_mode: _,
+            } = *mode
+            {
+                fill_mode.color = color;
+            }
+        }
+    }
+
+    for (entity, mut inputs) in q_inputs.iter_mut() {
+        if let Some(&code) = pressed.get(&entity) {
+            HexKeypad::encode(code, &mut inputs.0);
+            inputs[HexKeypad::STROBE] = State::High;
+        } else if inputs[HexKeypad::STROBE] != State::Low {
+            inputs[HexKeypad::STROBE] = State::Low;
+        }
+    }
+}
@@ -0,0 +1,107 @@
+use super::segment_display::{COLOR_OFF, COLOR_ON};
+use super::*;
+use crate::gate::core::{State, *};
+use crate::gate::serialize::*;
+use bevy::prelude::*;
+use bevy_prototype_lyon::prelude::*;
+use nodus::world2d::interaction2d::{Draggable, Interactable, Selectable};
+use std::sync::atomic::Ordering;
+
+/// Vertical distance between two LEDs.
+const LED_STEP: f32 = GATE_WIDTH / 2.;
+
+/// A bar graph of LEDs.
+///
+/// Every LED has its own input and is lit if the input
+/// is [`State::High`]. The first input drives the top LED.
+#[derive(Debug, Clone, PartialEq, Component)]
+pub struct LedBar {
+    leds: Vec<Entity>,
+}
+
+impl LedBar {
+    /// Minimum and maximum number of LEDs.
+    pub const RANGE: NodeRange = NodeRange { min: 1, max: 16 };
+
+    /// Create a new LED bar with `n` LEDs at the specified position.
+    pub fn spawn(commands: &mut Commands, position: Vec2, rotation: Quat, n: usize) -> Entity {
+        let z = Z_INDEX.fetch_add(1, Ordering::Relaxed) as f32;
+        let width = GATE_WIDTH;
+        let height = n as f32 * LED_STEP + 16.;
+
+        let mut leds = Vec::new();
+        let mut connectors = Vec::new();
+        for i in 0..n {
+            let y = height / 2. - 8. - LED_STEP / 2. - i as f32 * LED_STEP;
+
+            leds.push(
+                commands
+                    .spawn_bundle(GeometryBuilder::build_as(
+                        &shapes::Rectangle {
+                            extents: Vec2::new(width * 0.6, LED_STEP * 0.6),
+                            ..shapes::Rectangle::default()
+                        },
+                        DrawMode::Outlined {
+                            fill_mode: FillMode::color(COLOR_OFF),
+                            outline_mode: StrokeMode::new(Color::BLACK, 2.0),
+                        },
+                        Transform::from_xyz(0., y, 0.1),
+                    ))
+                    .id(),
+            );
+
+            connectors.push(Connector::with_line(
+                commands,
+                Vec3::new(-(width / 2. + GATE_SIZE * 0.2), y, 0.),
+                GATE_SIZE * 0.1,
+                ConnectorType::In,
+                i,
+                format!("x{}", i),
+            ));
+        }
+
+        let parent = commands
+            .spawn_bundle(Gate::body(
+                Vec3::new(position.x, position.y, z),
+                rotation,
+                Vec2::new(width, height),
+            ))
+            .insert(LedBar { leds: leds.clone() })
+            .insert(Name("LED Bar".to_string()))
+            .insert(Inputs(vec![State::None; n]))
+            .insert(NodeType::LedBar)
+            .insert(Interactable::new(
+                Vec2::new(0., 0.),
+                Vec2::new(width, height),
+                1,
+            ))
+            .insert(Selectable)
+            .insert(Draggable { update: true })
+            .id();
+
+        commands.entity(parent).push_children(&leds);
+        commands.entity(parent).push_children(&connectors);
+
+        parent
+    }
+}
+
+pub fn led_bar_system(q_bar: Query<(&Inputs, &LedBar)>, mut draw: Query<&mut DrawMode>) {
+    for (inputs, bar) in q_bar.iter() {
+        for (i, &led) in bar.leds.iter().enumerate() {
+            if let Ok(mut mode) = draw.get_mut(led) {
+                if let DrawMode::Outlined {
+                    ref mut fill_mode,
+                    outline_mode: _,
+                } = *mode
+                {
+                    fill_mode.color = if inputs[i] == State::High {
+                        COLOR_ON
+                    } else {
+                        COLOR_OFF
+                    };
+                }
+            }
+        }
+    }
+}
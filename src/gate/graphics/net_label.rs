@@ -0,0 +1,128 @@
+use super::*;
+use crate::gate::core::{State, *};
+use crate::gate::serialize::*;
+use bevy::prelude::*;
+use bevy_prototype_lyon::prelude::*;
+use lyon_tessellation::path::path::Builder;
+use nodus::world2d::interaction2d::{Draggable, Interactable, Selectable};
+use std::collections::HashMap;
+use std::sync::atomic::Ordering;
+
+/// Width of the flag shaped body of a label.
+const LABEL_WIDTH: f32 = GATE_SIZE * 1.5;
+/// Height of the flag shaped body of a label.
+const LABEL_HEIGHT: f32 = GATE_SIZE * 0.5;
+
+/// Maximum length of the name of a label.
+pub const MAX_NAME_LEN: usize = 12;
+
+/// Text showing the name of a net label.
+#[derive(Debug, Clone, PartialEq, Component)]
+pub struct NetLabelText;
+
+#[derive(Debug, Clone, PartialEq)]
+struct FlagShape {
+    size: Vec2,
+}
+
+impl Geometry for FlagShape {
+    fn add_geometry(&self, b: &mut Builder) {
+        let (w, h) = (self.size.x / 2., self.size.y / 2.);
+        let mut path = PathBuilder::new();
+        path.move_to(Vec2::new(-w, h));
+        path.line_to(Vec2::new(w - h, h));
+        path.line_to(Vec2::new(w, 0.));
+        path.line_to(Vec2::new(w - h, -h));
+        path.line_to(Vec2::new(-w, -h));
+        path.close();
+        b.concatenate(&[path.build().0.as_slice()]);
+    }
+}
+
+impl NetLabel {
+    /// Create a new net label with the given `name` at the specified position.
+    pub fn spawn(
+        commands: &mut Commands,
+        position: Vec2,
+        rotation: Quat,
+        name: String,
+        font: Handle<Font>,
+    ) -> Entity {
+        let z = Z_INDEX.fetch_add(1, Ordering::Relaxed) as f32;
+        let size = Vec2::new(LABEL_WIDTH, LABEL_HEIGHT);
+
+        let text = commands
+            .spawn_bundle(Text2dBundle {
+                text: Text::with_section(
+                    &name,
+                    TextStyle {
+                        font,
+                        font_size: 24.0,
+                        color: Color::BLACK,
+                    },
+                    TextAlignment {
+                        horizontal: HorizontalAlign::Center,
+                        vertical: VerticalAlign::Center,
+                    },
+                ),
+                transform: Transform::from_xyz(-LABEL_HEIGHT / 4., 0., 1.),
+                ..Default::default()
+            })
+            .insert(NetLabelText)
+            .id();
+
+        let parent = commands
+            .spawn_bundle(GeometryBuilder::build_as(
+                &FlagShape { size },
+                DrawMode::Outlined {
+                    fill_mode: FillMode::color(Color::WHITE),
+                    outline_mode: StrokeMode::new(Color::BLACK, 6.0),
+                },
+                Transform::from_xyz(position.x, position.y, z).with_rotation(rotation),
+            ))
+            .insert(NetLabel(name))
+            .insert(Name("Net Label".to_string()))
+            .insert(Inputs(vec![State::None]))
+            .insert(Outputs(vec![State::None]))
+            .insert(Targets(vec![TargetMap::from(HashMap::new())]))
+            .insert(NodeType::NetLabel)
+            .insert(Interactable::new(Vec2::new(0., 0.), size, 1))
+            .insert(Selectable)
+            .insert(Draggable { update: true })
+            .id();
+
+        let input = Connector::with_line(
+            commands,
+            Vec3::new(-(size.x / 2. + GATE_SIZE * 0.2), 0., 0.),
+            GATE_SIZE * 0.1,
+            ConnectorType::In,
+            0,
+            "x0".to_string(),
+        );
+
+        let output = Connector::with_line(
+            commands,
+            Vec3::new(size.x / 2. + GATE_SIZE * 0.2, 0., 0.),
+            GATE_SIZE * 0.1,
+            ConnectorType::Out,
+            0,
+            "y1".to_string(),
+        );
+
+        commands.entity(parent).push_children(&[text, input, output]);
+
+        parent
+    }
+}
+
+/// Show the current name of each net label.
+pub fn net_label_text_system(
+    q_label: Query<&NetLabel, Changed<NetLabel>>,
+    mut q_text: Query<(&Parent, &mut Text), With<NetLabelText>>,
+) {
+    for (parent, mut text) in q_text.iter_mut() {
+        if let Ok(label) = q_label.get(parent.0) {
+            text.sections[0].value = label.0.clone();
+        }
+    }
+}
@@ -0,0 +1,331 @@
+use super::*;
+use crate::gate::core::{State, *};
+use crate::gate::serialize::*;
+use bevy::prelude::*;
+use bevy_prototype_lyon::prelude::*;
+use nodus::world2d::interaction2d::{Draggable, Interactable, Selectable};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::atomic::Ordering;
+
+/// Distance between two neighbouring outputs.
+const OUT_STEP: f32 = GATE_WIDTH / 2.;
+
+/// The values a [`PatternGenerator`] plays back.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum PatternMode {
+    /// Play back the patterns of the table.
+    Table,
+    /// Count upwards from zero.
+    Counter,
+    /// Present a random value on every step.
+    Random,
+}
+
+impl PatternMode {
+    pub fn label(&self) -> &'static str {
+        match self {
+            PatternMode::Table => "Table",
+            PatternMode::Counter => "Counter",
+            PatternMode::Random => "Random",
+        }
+    }
+}
+
+/// Persistent configuration of a [`PatternGenerator`].
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct PatternConfig {
+    /// Number of outputs.
+    pub outputs: usize,
+    /// The table of bit patterns. Bit `outputs - 1` of a pattern
+    /// belongs to the first output.
+    pub patterns: Vec<u32>,
+    pub mode: PatternMode,
+    /// Steps per second or `None` if the generator advances on
+    /// the rising edge of its clock input.
+    pub frequency: Option<f32>,
+    /// Start over after the last step instead of stopping.
+    pub looping: bool,
+}
+
+impl Default for PatternConfig {
+    fn default() -> Self {
+        Self {
+            outputs: 4,
+            patterns: vec![0b1000, 0b0100, 0b0010, 0b0001],
+            mode: PatternMode::Table,
+            frequency: Some(1.0),
+            looping: true,
+        }
+    }
+}
+
+/// A pattern generator with `N` outputs.
+///
+/// The generator steps through a table of bit patterns, counts upwards
+/// or generates random values. It advances either on its own timer or
+/// on the rising edge of its clock input.
+#[derive(Debug, Clone, PartialEq, Component)]
+pub struct PatternGenerator {
+    pub config: PatternConfig,
+    /// The current step.
+    pub step: usize,
+    /// Set if a one-shot sequence has reached its end.
+    pub finished: bool,
+    /// Time in seconds since the last step.
+    elapsed: f32,
+    /// Last known state of the clock input.
+    clk: State,
+    /// State of the random number generator.
+    seed: u32,
+    /// Text entity showing the current value.
+    text: Entity,
+}
+
+impl PatternGenerator {
+    /// Minimum and maximum number of outputs.
+    pub const RANGE: NodeRange = NodeRange { min: 1, max: 16 };
+    /// Maximum number of patterns of the table.
+    pub const MAX_PATTERNS: usize = 256;
+
+    /// Get the persistent state of the generator.
+    pub fn state(&self) -> NodeState {
+        NodeState::PatternGenerator(self.config.clone())
+    }
+
+    /// Restart the sequence from the first step.
+    pub fn reset(&mut self) {
+        self.step = 0;
+        self.elapsed = 0.0;
+        self.finished = false;
+    }
+
+    /// Mask of the bits used by the outputs.
+    fn mask(&self) -> u32 {
+        if self.config.outputs >= 32 {
+            u32::MAX
+        } else {
+            (1 << self.config.outputs) - 1
+        }
+    }
+
+    /// Get the value presented at the current step.
+    pub fn value(&self) -> u32 {
+        let value = match self.config.mode {
+            PatternMode::Table => self.config.patterns.get(self.step).copied().unwrap_or(0),
+            PatternMode::Counter => self.step as u32,
+            PatternMode::Random => self.seed,
+        };
+        value & self.mask()
+    }
+
+    /// Advance to the next step.
+    fn advance(&mut self) {
+        if self.finished {
+            return;
+        }
+
+        let len = match self.config.mode {
+            PatternMode::Table => self.config.patterns.len(),
+            PatternMode::Counter => self.mask() as usize + 1,
+            PatternMode::Random => usize::MAX,
+        };
+
+        if self.step + 1 < len {
+            self.step += 1;
+        } else if self.config.looping {
+            self.step = 0;
+        } else {
+            self.finished = true;
+        }
+
+        // xorshift32
+        self.seed ^= self.seed << 13;
+        self.seed ^= self.seed >> 17;
+        self.seed ^= self.seed << 5;
+    }
+
+    /// Write the current value to the given states, most significant bit first.
+    fn present(&self, states: &mut [State]) {
+        let value = self.value();
+        let n = self.config.outputs;
+        for i in 0..n {
+            states[i] = if value & (1 << (n - 1 - i)) != 0 {
+                State::High
+            } else {
+                State::Low
+            };
+        }
+    }
+
+    /// Get the text shown on the body of the generator.
+    fn label(&self) -> String {
+        format!(
+            "{}\n{:0width$b}",
+            self.step,
+            self.value(),
+            width = self.config.outputs
+        )
+    }
+
+    /// Create a new pattern generator at the specified position.
+    pub fn spawn(
+        commands: &mut Commands,
+        position: Vec2,
+        rotation: Quat,
+        mut config: PatternConfig,
+        font: Handle<Font>,
+    ) -> Entity {
+        let z = Z_INDEX.fetch_add(1, Ordering::Relaxed) as f32;
+        let n = config
+            .outputs
+            .clamp(Self::RANGE.min as usize, Self::RANGE.max as usize);
+        config.outputs = n;
+        config.patterns.truncate(Self::MAX_PATTERNS);
+        if config.patterns.is_empty() {
+            config.patterns.push(0);
+        }
+        let external = config.frequency.is_none();
+
+        let width = GATE_SIZE * 1.5;
+        let height = (n as f32 * OUT_STEP + 16.).max(GATE_SIZE);
+
+        let label = commands
+            .spawn_bundle(Text2dBundle {
+                text: Text::with_section(
+                    "",
+                    TextStyle {
+                        font,
+                        font_size: 20.0,
+                        color: Color::BLACK,
+                    },
+                    TextAlignment {
+                        horizontal: HorizontalAlign::Center,
+                        vertical: VerticalAlign::Center,
+                    },
+                ),
+                transform: Transform::from_xyz(0., 0., 1.),
+                ..Default::default()
+            })
+            .id();
+
+        let generator = PatternGenerator {
+            config,
+            step: 0,
+            finished: false,
+            elapsed: 0.0,
+            clk: State::None,
+            seed: chrono::Local::now().timestamp_subsec_nanos() | 1,
+            text: label,
+        };
+
+        // The first `n` inputs hold the current value, the last one
+        // belongs to the clock connector.
+        let mut states = vec![State::Low; n + 1];
+        generator.present(&mut states);
+        states[n] = State::None;
+
+        let mut functions: Vec<Box<dyn Fn(&[State]) -> State + Send + Sync>> = Vec::new();
+        for i in 0..n {
+            functions.push(Box::new(move |inputs| inputs[i]));
+        }
+
+        let parent = commands
+            .spawn_bundle(Gate::body(
+                Vec3::new(position.x, position.y, z),
+                rotation,
+                Vec2::new(width, height),
+            ))
+            .insert(generator)
+            .insert(Name("Pattern Generator".to_string()))
+            .insert(Inputs(states.clone()))
+            .insert(Outputs(states[..n].to_vec()))
+            .insert(Transitions(functions))
+            .insert(Targets(vec![TargetMap::from(HashMap::new()); n]))
+            .insert(NodeType::PatternGenerator)
+            .insert(Interactable::new(
+                Vec2::new(0., 0.),
+                Vec2::new(width, height),
+                1,
+            ))
+            .insert(Selectable)
+            .insert(Draggable { update: true })
+            .id();
+
+        let mut entvec = vec![label];
+        for i in 0..n {
+            entvec.push(Connector::with_line(
+                commands,
+                Vec3::new(
+                    width / 2. + GATE_SIZE * 0.2,
+                    (n - 1) as f32 * OUT_STEP / 2. - i as f32 * OUT_STEP,
+                    0.,
+                ),
+                GATE_SIZE * 0.1,
+                ConnectorType::Out,
+                i,
+                format!("y{}", i + 1),
+            ));
+        }
+
+        if external {
+            entvec.push(Connector::with_line(
+                commands,
+                Vec3::new(-(width / 2. + GATE_SIZE * 0.2), 0., 0.),
+                GATE_SIZE * 0.1,
+                ConnectorType::In,
+                n,
+                "clk".to_string(),
+            ));
+        }
+
+        commands.entity(parent).push_children(&entvec);
+
+        parent
+    }
+}
+
+/// Advance the pattern generators and present their current value.
+pub fn pattern_generator_system(
+    mut q_generator: Query<(&mut PatternGenerator, &mut Inputs)>,
+    mut q_text: Query<&mut Text>,
+    time: Res<Time>,
+) {
+    let delta = time.delta_seconds();
+
+    for (mut generator, mut inputs) in q_generator.iter_mut() {
+        let n = generator.config.outputs;
+
+        match generator.config.frequency {
+            Some(frequency) => {
+                generator.elapsed += delta;
+                if generator.elapsed >= 1.0 / frequency {
+                    generator.elapsed = 0.0;
+                    generator.advance();
+                }
+            }
+            None => {
+                let clk = inputs[n];
+                if clk == State::High && generator.clk != State::High {
+                    generator.advance();
+                }
+                generator.clk = clk;
+            }
+        }
+
+        // The table might have been edited, i.e. always check
+        // the current value.
+        let mut states = vec![State::Low; n];
+        generator.present(&mut states);
+        if inputs[..n] != states[..] {
+            inputs[..n].copy_from_slice(&states);
+        }
+
+        if let Ok(mut text) = q_text.get_mut(generator.text) {
+            let label = generator.label();
+            if text.sections[0].value != label {
+                text.sections[0].value = label;
+            }
+        }
+    }
+}
@@ -0,0 +1,133 @@
+use super::*;
+use crate::gate::core::{State, *};
+use crate::gate::keys::KeyBinding;
+use crate::gate::serialize::*;
+use bevy::prelude::*;
+use bevy_prototype_lyon::prelude::*;
+use nodus::world2d::interaction2d::{Draggable, Hover, Interactable, Selectable};
+use nodus::world2d::Lock;
+use std::collections::HashMap;
+use std::sync::atomic::Ordering;
+
+/// A momentary push button.
+///
+/// The button propagates a State::High signal to all connected
+/// logical components as long as the left mouse button is held
+/// down on it, else State::Low.
+#[derive(Debug, Clone, PartialEq, Hash, Component)]
+pub struct PushButton;
+
+impl PushButton {
+    /// Create a new push button at the specified position.
+    pub fn spawn(commands: &mut Commands, position: Vec2, rotation: Quat) -> Entity {
+        let z = Z_INDEX.fetch_add(1, Ordering::Relaxed) as f32;
+
+        let shape = shapes::Rectangle {
+            extents: Vec2::new(GATE_SIZE * 0.75, GATE_SIZE * 0.75),
+            ..shapes::Rectangle::default()
+        };
+
+        let parent = commands
+            .spawn_bundle(GeometryBuilder::build_as(
+                &shape,
+                DrawMode::Outlined {
+                    fill_mode: FillMode::color(Color::WHITE),
+                    outline_mode: StrokeMode::new(Color::BLACK, 6.0),
+                },
+                Transform::from_xyz(position.x, position.y, z).with_rotation(rotation),
+            ))
+            .insert(PushButton)
+            .insert(Name("Push Button".to_string()))
+            .insert(Inputs(vec![State::Low]))
+            .insert(Outputs(vec![State::Low]))
+            .insert(Transitions(trans![|inputs| inputs[0]]))
+            .insert(Targets(vec![TargetMap::from(HashMap::new())]))
+            .insert(NodeType::PushButton)
+            .insert(Interactable::new(
+                Vec2::new(0., 0.),
+                Vec2::new(GATE_SIZE * 0.75, GATE_SIZE * 0.75),
+                1,
+            ))
+            .insert(Selectable)
+            .insert(Draggable { update: true })
+            .id();
+
+        let child = Connector::with_line(
+            commands,
+            Vec3::new(GATE_SIZE * 0.6, 0., 0.),
+            GATE_SIZE * 0.1,
+            ConnectorType::Out,
+            0,
+            "y1".to_string(),
+        );
+
+        let plunger = commands
+            .spawn_bundle(GeometryBuilder::build_as(
+                &shapes::Circle {
+                    radius: GATE_SIZE / 4.,
+                    center: Vec2::new(0., 0.),
+                },
+                DrawMode::Outlined {
+                    fill_mode: FillMode::color(Color::WHITE),
+                    outline_mode: StrokeMode::new(Color::BLACK, 8.0),
+                },
+                Transform::from_xyz(0., 0., 1.),
+            ))
+            .insert(Plunger)
+            .insert(Interactable::new(
+                Vec2::new(0., 0.),
+                Vec2::new(GATE_SIZE / 2., GATE_SIZE / 2.),
+                1,
+            ))
+            .id();
+
+        commands.entity(parent).push_children(&vec![child, plunger]);
+
+        parent
+    }
+}
+
+/// Plunger represents the part of the push button the user can press.
+#[derive(Debug, Clone, PartialEq, Hash, Component)]
+pub struct Plunger;
+
+/// Drive push buttons high while the mouse is held down on their plunger
+/// or the key bound to them is held down.
+pub fn push_button_system(
+    mut q_inputs: Query<(&mut Inputs, Option<&KeyBinding>), With<PushButton>>,
+    mut q_plunger: Query<(&Parent, Option<&Hover>, &mut DrawMode), With<Plunger>>,
+    mb: Res<Input<MouseButton>>,
+    keyboard: Res<Input<KeyCode>>,
+    lock: Res<Lock>,
+) {
+    for (parent, hover, mut mode) in q_plunger.iter_mut() {
+        if let Ok((mut inputs, binding)) = q_inputs.get_mut(parent.0) {
+            let held = match binding {
+                Some(binding) => !lock.0 && keyboard.pressed(binding.0),
+                None => false,
+            };
+
+            let next = if held || (hover.is_some() && mb.pressed(MouseButton::Left)) {
+                State::High
+            } else {
+                State::Low
+            };
+
+            // Only touch the visuals if the state has changed.
+            if inputs[0] != next {
+                if let DrawMode::Outlined {
+                    ref mut fill_mode,
+                    outline_mode: _,
+                } = *mode
+                {
+                    fill_mode.color = match next {
+                        State::High => Color::BLUE,
+                        _ => Color::WHITE,
+                    };
+                }
+
+                inputs[0] = next;
+            }
+        }
+    }
+}
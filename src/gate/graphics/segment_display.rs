@@ -60,36 +60,47 @@ impl SegmentShape {
     }
 }
 
+/// Spawn the seven segments of a digit centered at `offset`.
+///
+/// The segments are returned in the order a (top), b (top right),
+/// c (bottom right), d (bottom), e (bottom left), f (top left)
+/// and g (middle).
+fn spawn_digit(commands: &mut Commands, offset: Vec2) -> Vec<Entity> {
+    let segment_size = GATE_WIDTH;
+    let x = segment_size * 0.5 - offset.x;
+    let y = segment_size * 1.2 + offset.y;
+    let coords = vec![
+        (Vec3::new(-x, y, 0.1), Quat::IDENTITY),
+        (Vec3::new(segment_size + 2.0 - x, y - 2.0, 0.1), Quat::from_rotation_z(-std::f32::consts::PI/ 2.0)),
+        (Vec3::new(segment_size + 2.0 - x, y - segment_size - 6.0, 0.1), Quat::from_rotation_z(-std::f32::consts::PI/ 2.0)),
+        (Vec3::new(-x, y - segment_size * 2.0 - 8.0, 0.1), Quat::IDENTITY),
+        (Vec3::new(-2.0 - x, y - segment_size - 6.0, 0.1), Quat::from_rotation_z(-std::f32::consts::PI/ 2.0)),
+        (Vec3::new(-2.0 - x, y - 2.0, 0.1), Quat::from_rotation_z(-std::f32::consts::PI/ 2.0)),
+        (Vec3::new(-x, y - segment_size - 4.0, 0.1), Quat::IDENTITY),
+    ];
+
+    let mut segments: Vec<Entity> = Vec::new();
+
+    for (nr, (pos, rot)) in coords.iter().enumerate() {
+        segments.push(
+            SegmentShape::spawn(
+                commands,
+                *pos,
+                *rot,
+                segment_size,
+                nr as u8
+            )
+        );
+    }
+
+    segments
+}
+
 impl SevenSegmentDisplay {
     pub fn spawn(commands: &mut Commands, position: Vec2, rotation: Quat) -> Entity {
         let z = Z_INDEX.fetch_add(1, Ordering::Relaxed) as f32;
-        let segment_size = GATE_WIDTH;
-        let x = segment_size * 0.5;
-        let y = segment_size * 1.2;
-        let coords = vec![
-            (Vec3::new(-x, y, 0.1), Quat::IDENTITY),
-            (Vec3::new(segment_size + 2.0 - x, y - 2.0, 0.1), Quat::from_rotation_z(-std::f32::consts::PI/ 2.0)),
-            (Vec3::new(segment_size + 2.0 - x, y - segment_size - 6.0, 0.1), Quat::from_rotation_z(-std::f32::consts::PI/ 2.0)),
-            (Vec3::new(-x, y - segment_size * 2.0 - 8.0, 0.1), Quat::IDENTITY),
-            (Vec3::new(-2.0 - x, y - segment_size - 6.0, 0.1), Quat::from_rotation_z(-std::f32::consts::PI/ 2.0)),
-            (Vec3::new(-2.0 - x, y - 2.0, 0.1), Quat::from_rotation_z(-std::f32::consts::PI/ 2.0)),
-            (Vec3::new(-x, y - segment_size - 4.0, 0.1), Quat::IDENTITY),
-        ];
-        
-        let mut segments: Vec<Entity> = Vec::new();
-        
-        for (nr, (pos, rot)) in coords.iter().enumerate() {
-            segments.push(
-                SegmentShape::spawn(
-                    commands, 
-                    *pos, 
-                    *rot,
-                    segment_size,
-                    nr as u8
-                )
-            );
-        }
-        
+        let mut segments = spawn_digit(commands, Vec2::new(0., 0.));
+
         let parent = commands
             .spawn_bundle(
                 Gate::body(
@@ -144,8 +155,8 @@ impl SevenSegmentDisplay {
     }
 }
 
-const COLOR_OFF: Color = Color::WHITE;
-const COLOR_ON: Color = Color::RED;
+pub const COLOR_OFF: Color = Color::WHITE;
+pub const COLOR_ON: Color = Color::RED;
 
 const DISPLAY_COLORS: [[Color; 7]; 16] = [
     [COLOR_ON, COLOR_ON, COLOR_ON, COLOR_ON, COLOR_ON, COLOR_ON, COLOR_OFF],
@@ -166,28 +177,216 @@ const DISPLAY_COLORS: [[Color; 7]; 16] = [
     [COLOR_ON, COLOR_OFF, COLOR_OFF, COLOR_OFF, COLOR_ON, COLOR_ON, COLOR_ON],
 ];
 
+/// Decode the four given states into a hex digit.
+///
+/// Inputs are treated as big endian, i.e. the first state is 2^3.
+fn decode(inputs: &[State]) -> usize {
+    inputs
+        .iter()
+        .fold(0, |i, s| (i << 1) | if *s == State::High { 1 } else { 0 })
+}
+
+/// Set the fill color of the given segment.
+fn set_color(draw: &mut Query<&mut DrawMode>, e: Entity, color: Color) {
+    if let Ok(mut mode) = draw.get_mut(e) {
+        if let DrawMode::Outlined {
+            ref mut fill_mode,
+            outline_mode: _,
+        } = *mode
+        {
+            fill_mode.color = color;
+        }
+    }
+}
+
 pub fn segment_system(
     q_seg: Query<(&Inputs, &SevenSegmentDisplay)>,
     mut draw: Query<&mut DrawMode>,
 ) {
     for (inputs, display) in q_seg.iter() {
-        // Inputs are treated as little endian, i.e. 2^3 + 2^2 + 2^1 + 2^0.
-        let mut i = if inputs[3] == State::High { 1 } else { 0 };
-        i |= if inputs[2] == State::High { 1 } else { 0 } << 1;
-        i |= if inputs[1] == State::High { 1 } else { 0 } << 2;
-        i |= if inputs[0] == State::High { 1 } else { 0 } << 3;
+        let i = decode(&inputs[0..4]);
 
         for j in 0..7 {
-            let e = display.segments[j];
-            
-            if let Ok(mut mode) = draw.get_mut(e) {
-                if let DrawMode::Outlined {
-                    ref mut fill_mode,
-                    outline_mode: _,
-                } = *mode 
-                {
-                    fill_mode.color = DISPLAY_COLORS[i][j];
-                }
+            set_color(&mut draw, display.segments[j], DISPLAY_COLORS[i][j]);
+        }
+    }
+}
+
+/// A seven-segment display whose segments are driven directly.
+///
+/// There is one input for each segment a to g, followed by an
+/// input for the decimal point.
+#[derive(Debug, Clone, PartialEq, Hash, Component)]
+pub struct SevenSegmentDirect {
+    segments: Vec<Entity>,
+}
+
+impl SevenSegmentDirect {
+    pub fn spawn(commands: &mut Commands, position: Vec2, rotation: Quat) -> Entity {
+        let z = Z_INDEX.fetch_add(1, Ordering::Relaxed) as f32;
+        let mut segments = spawn_digit(commands, Vec2::new(0., 0.));
+
+        segments.push(
+            commands
+                .spawn_bundle(GeometryBuilder::build_as(
+                    &shapes::Circle {
+                        radius: 6.,
+                        center: Vec2::new(0., 0.),
+                    },
+                    DrawMode::Outlined {
+                        fill_mode: FillMode::color(COLOR_OFF),
+                        outline_mode: StrokeMode::new(Color::BLACK, 2.0),
+                    },
+                    Transform::from_xyz(GATE_WIDTH * 0.5 + 14.0, -GATE_WIDTH * 0.8 - 8.0, 0.1),
+                ))
+                .insert(Segment { nr: 7 })
+                .id(),
+        );
+
+        let parent = commands
+            .spawn_bundle(
+                Gate::body(
+                    Vec3::new(position.x, position.y, z),
+                    rotation,
+                    Vec2::new(GATE_WIDTH * 2.0, GATE_HEIGHT * 2.0),
+                )
+            )
+            .insert(SevenSegmentDirect { segments: segments.clone() })
+            .insert(Name("7-Segment Display (Direct)".to_string()))
+            .insert(Inputs(vec![State::None; 8]))
+            .insert(NodeType::SevenSegmentDirect)
+            .insert(Interactable::new(
+                Vec2::new(0., 0.),
+                Vec2::new(GATE_WIDTH * 2.0, GATE_HEIGHT * 2.0),
+                1,
+            ))
+            .insert(Selectable)
+            .insert(Draggable { update: true })
+            .id();
+
+        let names = ["a", "b", "c", "d", "e", "f", "g", "dp"];
+        for (i, name) in names.iter().enumerate() {
+            segments.push(
+                Connector::with_line(
+                    commands,
+                    Vec3::new(
+                        -GATE_WIDTH * 1.4,
+                        GATE_HEIGHT * 2.0 * 0.4375 - i as f32 * GATE_HEIGHT * 2.0 * 0.125,
+                        0.
+                    ),
+                    GATE_SIZE * 0.1,
+                    ConnectorType::In,
+                    i,
+                    name.to_string(),
+                )
+            );
+        }
+
+        commands
+            .entity(parent)
+            .push_children(&segments);
+
+        parent
+    }
+}
+
+pub fn segment_direct_system(
+    q_seg: Query<(&Inputs, &SevenSegmentDirect)>,
+    mut draw: Query<&mut DrawMode>,
+) {
+    for (inputs, display) in q_seg.iter() {
+        for j in 0..8 {
+            let color = if inputs[j] == State::High { COLOR_ON } else { COLOR_OFF };
+            set_color(&mut draw, display.segments[j], color);
+        }
+    }
+}
+
+/// A display showing multiple hex digits.
+///
+/// Every digit is driven by four inputs, most significant bit
+/// first. The first four inputs belong to the leftmost digit.
+#[derive(Debug, Clone, PartialEq, Hash, Component)]
+pub struct HexDisplay {
+    digits: Vec<Vec<Entity>>,
+}
+
+impl HexDisplay {
+    /// Minimum and maximum number of digits.
+    pub const RANGE: NodeRange = NodeRange { min: 1, max: 8 };
+
+    /// Create a new hex display with `n` digits at the specified position.
+    pub fn spawn(commands: &mut Commands, position: Vec2, rotation: Quat, n: usize) -> Entity {
+        let z = Z_INDEX.fetch_add(1, Ordering::Relaxed) as f32;
+        let digit_width = GATE_WIDTH * 2.0;
+        let width = digit_width * n as f32;
+
+        let mut digits = Vec::new();
+        let mut children = Vec::new();
+        for d in 0..n {
+            let offset = -width / 2. + digit_width / 2. + d as f32 * digit_width;
+            let segments = spawn_digit(commands, Vec2::new(offset, 0.));
+            children.extend(segments.iter());
+            digits.push(segments);
+
+            for j in 0..4 {
+                let i = d * 4 + j;
+                children.push(
+                    Connector::with_line_vert(
+                        commands,
+                        Vec3::new(
+                            offset - digit_width * 0.375 + j as f32 * digit_width * 0.25,
+                            -GATE_HEIGHT * 2.0 * 0.7,
+                            0.
+                        ),
+                        GATE_SIZE * 0.1,
+                        ConnectorType::In,
+                        i,
+                        format!("x{}", i),
+                    )
+                );
+            }
+        }
+
+        let parent = commands
+            .spawn_bundle(
+                Gate::body(
+                    Vec3::new(position.x, position.y, z),
+                    rotation,
+                    Vec2::new(width, GATE_HEIGHT * 2.0),
+                )
+            )
+            .insert(HexDisplay { digits })
+            .insert(Name("Hex Display".to_string()))
+            .insert(Inputs(vec![State::None; n * 4]))
+            .insert(NodeType::HexDisplay)
+            .insert(Interactable::new(
+                Vec2::new(0., 0.),
+                Vec2::new(width, GATE_HEIGHT * 2.0),
+                1,
+            ))
+            .insert(Selectable)
+            .insert(Draggable { update: true })
+            .id();
+
+        commands
+            .entity(parent)
+            .push_children(&children);
+
+        parent
+    }
+}
+
+pub fn hex_display_system(
+    q_seg: Query<(&Inputs, &HexDisplay)>,
+    mut draw: Query<&mut DrawMode>,
+) {
+    for (inputs, display) in q_seg.iter() {
+        for (d, segments) in display.digits.iter().enumerate() {
+            let i = decode(&inputs[d * 4..d * 4 + 4]);
+
+            for j in 0..7 {
+                set_color(&mut draw, segments[j], DISPLAY_COLORS[i][j]);
             }
         }
     }
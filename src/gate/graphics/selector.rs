@@ -1,7 +1,4 @@
-use crate::gate::{
-    core::Gate,
-    graphics::{clk::Clk, light_bulb::LightBulb, toggle_switch::ToggleSwitch, segment_display::*,},
-};
+use crate::gate::serialize::NodeType;
 use bevy::prelude::*;
 use bevy_prototype_lyon::prelude::*;
 use nodus::world2d::camera2d::MouseWorldPos;
@@ -19,10 +16,7 @@ pub fn selector_system(
     mw: Res<MouseWorldPos>,
     lock: Res<Lock>,
     mode: Res<InteractionMode>,
-    q_gate: Query<
-        (Entity, &Transform),
-        Or<(With<Gate>, With<LightBulb>, With<ToggleSwitch>, With<Clk>, With<SevenSegmentDisplay>)>,
-    >,
+    q_gate: Query<(Entity, &Transform), With<NodeType>>,
     q_hover: Query<Entity, With<Hover>>,
     mut q_select: Query<(Entity, &mut Path, &SelectBox), With<SelectBox>>,
 ) {
@@ -0,0 +1,198 @@
+use super::*;
+use crate::gate::core::{State, *};
+use crate::gate::serialize::*;
+use bevy::prelude::*;
+use bevy_prototype_lyon::prelude::*;
+use nodus::world2d::interaction2d::{Draggable, Interactable, Selectable};
+use std::sync::atomic::Ordering;
+
+/// Number of characters per line.
+const COLS: usize = 32;
+/// Number of lines visible at once.
+const ROWS: usize = 10;
+/// Font size of the text.
+const FONT_SIZE: f32 = 20.;
+/// Horizontal space a single character occupies.
+const CHAR_WIDTH: f32 = FONT_SIZE * 0.6;
+/// Vertical space a single line occupies.
+const LINE_HEIGHT: f32 = FONT_SIZE * 1.2;
+
+/// Index of the clock input.
+const CLK: usize = 7;
+
+/// A terminal (TTY) that prints ASCII characters.
+///
+/// The terminal has seven data inputs (d0 is the least significant bit)
+/// and a clock. On a rising edge of the clock the character presented
+/// on the data inputs is appended to the text. Line feed (0x0A) starts
+/// a new line, backspace (0x08) removes the last character and form
+/// feed (0x0C) clears the screen. All other control characters are
+/// ignored. Old lines scroll out at the top.
+#[derive(Debug, Clone, PartialEq, Component)]
+pub struct Terminal {
+    /// The visible lines of text.
+    lines: Vec<String>,
+    /// Text entity displaying the lines.
+    text: Entity,
+    /// Last known state of the clock input.
+    clk: State,
+}
+
+impl Terminal {
+    /// Get the persistent state of the terminal.
+    pub fn state(&self) -> NodeState {
+        NodeState::Terminal(self.lines.join("\n"))
+    }
+
+    /// Append the given ASCII character.
+    fn put(&mut self, c: u8) {
+        match c {
+            0x0A => self.lines.push(String::new()),
+            0x08 => {
+                self.lines.last_mut().unwrap().pop();
+            }
+            0x0C => {
+                self.lines.clear();
+                self.lines.push(String::new());
+            }
+            0x20..=0x7E => {
+                if self.lines.last().unwrap().len() >= COLS {
+                    self.lines.push(String::new());
+                }
+                self.lines.last_mut().unwrap().push(c as char);
+            }
+            _ => {}
+        }
+
+        if self.lines.len() > ROWS {
+            let n = self.lines.len() - ROWS;
+            self.lines.drain(..n);
+        }
+    }
+
+    /// Create a new terminal at the specified position showing `text`.
+    pub fn spawn(
+        commands: &mut Commands,
+        position: Vec2,
+        rotation: Quat,
+        text: String,
+        font: Handle<Font>,
+    ) -> Entity {
+        let z = Z_INDEX.fetch_add(1, Ordering::Relaxed) as f32;
+        let screen = Vec2::new(COLS as f32 * CHAR_WIDTH, ROWS as f32 * LINE_HEIGHT) + 16.;
+        let size = screen + 32.;
+
+        let mut lines: Vec<String> = text
+            .split('\n')
+            .map(|l| l.chars().filter(|c| c.is_ascii_graphic() || *c == ' ').take(COLS).collect())
+            .collect();
+        if lines.len() > ROWS {
+            let n = lines.len() - ROWS;
+            lines.drain(..n);
+        }
+
+        let label = commands
+            .spawn_bundle(Text2dBundle {
+                text: Text::with_section(
+                    &lines.join("\n"),
+                    TextStyle {
+                        font,
+                        font_size: FONT_SIZE,
+                        color: Color::GREEN,
+                    },
+                    TextAlignment {
+                        horizontal: HorizontalAlign::Left,
+                        vertical: VerticalAlign::Top,
+                    },
+                ),
+                transform: Transform::from_xyz(-screen.x / 2. + 8., screen.y / 2. - 8., 1.),
+                ..Default::default()
+            })
+            .id();
+
+        let display = commands
+            .spawn_bundle(GeometryBuilder::build_as(
+                &shapes::Rectangle {
+                    extents: screen,
+                    ..shapes::Rectangle::default()
+                },
+                DrawMode::Outlined {
+                    fill_mode: FillMode::color(Color::BLACK),
+                    outline_mode: StrokeMode::new(Color::BLACK, 4.0),
+                },
+                Transform::from_xyz(0., 0., 0.1),
+            ))
+            .id();
+
+        let parent = commands
+            .spawn_bundle(Gate::body(
+                Vec3::new(position.x, position.y, z),
+                rotation,
+                size,
+            ))
+            .insert(Terminal {
+                lines,
+                text: label,
+                clk: State::None,
+            })
+            .insert(Name("Terminal".to_string()))
+            .insert(Inputs(vec![State::None; 8]))
+            .insert(NodeType::Terminal)
+            .insert(Interactable::new(Vec2::new(0., 0.), size, 1))
+            .insert(Selectable)
+            .insert(Draggable { update: true })
+            .id();
+
+        let mut entvec = vec![display, label];
+        let step = -(size.y / 9.);
+        for i in 0..8 {
+            let name = if i == CLK {
+                "clk".to_string()
+            } else {
+                format!("d{}", i)
+            };
+
+            entvec.push(Connector::with_line(
+                commands,
+                Vec3::new(
+                    -(size.x / 2. + GATE_SIZE * 0.2),
+                    size.y / 2. + (i + 1) as f32 * step,
+                    0.,
+                ),
+                GATE_SIZE * 0.1,
+                ConnectorType::In,
+                i,
+                name,
+            ));
+        }
+
+        commands.entity(parent).push_children(&entvec);
+
+        parent
+    }
+}
+
+/// Append a character to each terminal whose clock input has a rising edge.
+pub fn terminal_system(
+    mut q_terminal: Query<(&Inputs, &mut Terminal)>,
+    mut q_text: Query<&mut Text>,
+) {
+    for (inputs, mut terminal) in q_terminal.iter_mut() {
+        let clk = inputs[CLK];
+
+        if clk == State::High && terminal.clk != State::High {
+            let mut c = 0;
+            for i in 0..7 {
+                if inputs[i] == State::High {
+                    c |= 1 << i;
+                }
+            }
+            terminal.put(c);
+
+            if let Ok(mut text) = q_text.get_mut(terminal.text) {
+                text.sections[0].value = terminal.lines.join("\n");
+            }
+        }
+        terminal.clk = clk;
+    }
+}
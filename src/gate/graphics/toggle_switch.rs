@@ -1,10 +1,12 @@
 use super::*;
 use crate::gate::core::{State, *};
+use crate::gate::keys::KeyBinding;
 use crate::gate::serialize::*;
 use bevy::prelude::*;
 use bevy_prototype_lyon::prelude::*;
 use lyon_tessellation::path::path::Builder;
 use nodus::world2d::interaction2d::{Draggable, Hover, Interactable, Selectable};
+use nodus::world2d::Lock;
 use std::collections::HashMap;
 use std::sync::atomic::Ordering;
 
@@ -124,16 +126,25 @@ impl Geometry for ToggleSwitchShape {
     }
 }
 
-/// Register clicks on a switch and change its state accordingly.
+/// Register clicks on a switch, or presses of the key bound to
+/// it, and change its state accordingly.
 pub fn toggle_switch_system(
     _commands: Commands,
-    mut q_outputs: Query<&mut Inputs>,
-    mut q_switch: Query<(&Parent, &mut Transform), (With<Hover>, With<Switch>)>,
+    mut q_outputs: Query<(&mut Inputs, Option<&KeyBinding>)>,
+    mut q_switch: Query<(&Parent, &mut Transform, Option<&Hover>), With<Switch>>,
     mb: Res<Input<MouseButton>>,
+    keyboard: Res<Input<KeyCode>>,
+    lock: Res<Lock>,
 ) {
-    if mb.just_pressed(MouseButton::Left) {
-        for (parent, mut transform) in q_switch.iter_mut() {
-            if let Ok(mut inputs) = q_outputs.get_mut(parent.0) {
+    for (parent, mut transform, hover) in q_switch.iter_mut() {
+        if let Ok((mut inputs, binding)) = q_outputs.get_mut(parent.0) {
+            let clicked = hover.is_some() && mb.just_pressed(MouseButton::Left);
+            let typed = match binding {
+                Some(binding) => !lock.0 && keyboard.just_pressed(binding.0),
+                None => false,
+            };
+
+            if clicked || typed {
                 let next = match inputs[0] {
                     State::High => {
                         transform.translation.x -= GATE_SIZE / 2.;
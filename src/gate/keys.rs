@@ -0,0 +1,440 @@
+use crate::gate::notify::Log;
+use crate::gate::preferences::{config_path, write_config};
+use bevy::prelude::*;
+use ron::ser::{to_string_pretty, PrettyConfig};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fs;
+use std::io;
+
+/// Keyboard key bound to an input control, e.g. a toggle switch.
+///
+/// Pressing the key has the same effect as clicking on the
+/// input control with the mouse.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Component)]
+pub struct KeyBinding(pub KeyCode);
+
+macro_rules! named_keys {
+    ( $( #[$meta:meta] )* $table:ident: $( $key:ident ),* $(,)? ) => {
+        $( #[$meta] )*
+        const $table: &[(KeyCode, &str)] = &[
+            $( (KeyCode::$key, stringify!($key)) ),*
+        ];
+    };
+}
+
+named_keys![
+    /// All keys that can be bound, together with their name
+    /// used within save files.
+    NAMED_KEYS:
+    Key1, Key2, Key3, Key4, Key5, Key6, Key7, Key8, Key9, Key0,
+    A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P, Q, R, S, T, U, V, W, X, Y, Z,
+    F1, F2, F3, F4, F5, F6, F7, F8, F9, F10, F11, F12,
+    Numpad0, Numpad1, Numpad2, Numpad3, Numpad4, Numpad5, Numpad6, Numpad7, Numpad8, Numpad9,
+    Up, Down, Left, Right, Space, Return, Tab,
+];
+
+named_keys![
+    /// Keys that can't be bound to input controls but can
+    /// be used by keyboard shortcuts.
+    SHORTCUT_KEYS:
+    Delete, Back, Escape, Insert, Home, End, PageUp, PageDown,
+    Comma, Period, Minus, Equals,
+];
+
+/// Get the name of the given `key` or `None` if the key can't be bound.
+pub fn key_name(key: KeyCode) -> Option<&'static str> {
+    NAMED_KEYS.iter().find(|(k, _)| *k == key).map(|(_, n)| *n)
+}
+
+/// Get the key with the given `name`.
+pub fn key_from_name(name: &str) -> Option<KeyCode> {
+    NAMED_KEYS.iter().find(|(_, n)| *n == name).map(|(k, _)| *k)
+}
+
+/// Get a short label of the given `key` that can be shown to the user.
+pub fn key_label(key: KeyCode) -> &'static str {
+    match key_name(key) {
+        Some(name) => name.strip_prefix("Key").unwrap_or(name),
+        None => "?",
+    }
+}
+
+/// The modifier keys, which can't be used on their own by a shortcut.
+const MODIFIERS: &[KeyCode] = &[
+    KeyCode::LControl,
+    KeyCode::RControl,
+    KeyCode::LShift,
+    KeyCode::RShift,
+    KeyCode::LAlt,
+    KeyCode::RAlt,
+    KeyCode::LWin,
+    KeyCode::RWin,
+];
+
+/// A key pressed together with a set of modifier keys, e.g. `Ctrl+Shift+Z`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Chord {
+    pub key: KeyCode,
+    pub ctrl: bool,
+    pub shift: bool,
+    pub alt: bool,
+}
+
+impl Chord {
+    pub const fn key(key: KeyCode) -> Self {
+        Self {
+            key,
+            ctrl: false,
+            shift: false,
+            alt: false,
+        }
+    }
+
+    pub const fn ctrl(key: KeyCode) -> Self {
+        Self {
+            key,
+            ctrl: true,
+            shift: false,
+            alt: false,
+        }
+    }
+
+    pub const fn ctrl_shift(key: KeyCode) -> Self {
+        Self {
+            key,
+            ctrl: true,
+            shift: true,
+            alt: false,
+        }
+    }
+
+    /// Get the chord of the given `key` and the modifier keys currently
+    /// held down, or `None` if the key can't be used by a shortcut.
+    pub fn from_keyboard(key: KeyCode, keyboard: &Input<KeyCode>) -> Option<Self> {
+        shortcut_key_name(key)?;
+        let (ctrl, shift, alt) = modifiers(keyboard);
+        Some(Self {
+            key,
+            ctrl,
+            shift,
+            alt,
+        })
+    }
+
+    /// Check if the key has just been pressed while exactly the
+    /// modifier keys of the chord are held down.
+    pub fn just_pressed(&self, keyboard: &Input<KeyCode>) -> bool {
+        keyboard.just_pressed(self.key) && modifiers(keyboard) == (self.ctrl, self.shift, self.alt)
+    }
+
+    /// Parse a chord from its textual form, e.g. `Ctrl+Shift+Z`.
+    pub fn parse(s: &str) -> Option<Self> {
+        let mut chord = Self::key(KeyCode::Escape);
+        let mut key = None;
+
+        for part in s.split('+').map(str::trim) {
+            match part {
+                "Ctrl" => chord.ctrl = true,
+                "Shift" => chord.shift = true,
+                "Alt" => chord.alt = true,
+                _ if key.is_none() => key = Some(shortcut_key_from_label(part)?),
+                _ => return None,
+            }
+        }
+
+        chord.key = key?;
+        Some(chord)
+    }
+}
+
+impl fmt::Display for Chord {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.ctrl {
+            write!(f, "Ctrl+")?;
+        }
+        if self.shift {
+            write!(f, "Shift+")?;
+        }
+        if self.alt {
+            write!(f, "Alt+")?;
+        }
+        write!(f, "{}", shortcut_key_label(self.key))
+    }
+}
+
+/// Get the state of the control, shift and alt key.
+fn modifiers(keyboard: &Input<KeyCode>) -> (bool, bool, bool) {
+    (
+        keyboard.pressed(KeyCode::LControl) || keyboard.pressed(KeyCode::RControl),
+        keyboard.pressed(KeyCode::LShift) || keyboard.pressed(KeyCode::RShift),
+        keyboard.pressed(KeyCode::LAlt) || keyboard.pressed(KeyCode::RAlt),
+    )
+}
+
+/// Get the name of the given `key` or `None` if the key can't
+/// be used by a shortcut.
+fn shortcut_key_name(key: KeyCode) -> Option<&'static str> {
+    if MODIFIERS.contains(&key) {
+        return None;
+    }
+
+    NAMED_KEYS
+        .iter()
+        .chain(SHORTCUT_KEYS.iter())
+        .find(|(k, _)| *k == key)
+        .map(|(_, n)| *n)
+}
+
+fn shortcut_key_label(key: KeyCode) -> &'static str {
+    match shortcut_key_name(key) {
+        Some(name) => name.strip_prefix("Key").unwrap_or(name),
+        None => "?",
+    }
+}
+
+fn shortcut_key_from_label(label: &str) -> Option<KeyCode> {
+    NAMED_KEYS
+        .iter()
+        .chain(SHORTCUT_KEYS.iter())
+        .find(|&&(k, n)| n == label || shortcut_key_label(k) == label)
+        .map(|(k, _)| *k)
+}
+
+/// An action that can be triggered by a keyboard shortcut.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum Shortcut {
+    New,
+    Open,
+    Save,
+    Undo,
+    Redo,
+    Cut,
+    Copy,
+    Paste,
+    Duplicate,
+    CopyText,
+    PasteText,
+    Delete,
+    RotateLeft,
+    RotateRight,
+    PanMode,
+    SelectMode,
+}
+
+impl Shortcut {
+    pub const ALL: [Shortcut; 16] = [
+        Shortcut::New,
+        Shortcut::Open,
+        Shortcut::Save,
+        Shortcut::Undo,
+        Shortcut::Redo,
+        Shortcut::Cut,
+        Shortcut::Copy,
+        Shortcut::Paste,
+        Shortcut::Duplicate,
+        Shortcut::CopyText,
+        Shortcut::PasteText,
+        Shortcut::Delete,
+        Shortcut::RotateLeft,
+        Shortcut::RotateRight,
+        Shortcut::PanMode,
+        Shortcut::SelectMode,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Shortcut::New => "New File",
+            Shortcut::Open => "Open File",
+            Shortcut::Save => "Save File",
+            Shortcut::Undo => "Undo",
+            Shortcut::Redo => "Redo",
+            Shortcut::Cut => "Cut",
+            Shortcut::Copy => "Copy",
+            Shortcut::Paste => "Paste",
+            Shortcut::Duplicate => "Duplicate",
+            Shortcut::CopyText => "Copy as Text",
+            Shortcut::PasteText => "Paste from Text",
+            Shortcut::Delete => "Delete",
+            Shortcut::RotateLeft => "Rotate Left",
+            Shortcut::RotateRight => "Rotate Right",
+            Shortcut::PanMode => "Pan Mode",
+            Shortcut::SelectMode => "Select Mode",
+        }
+    }
+
+    fn default_chord(&self) -> Chord {
+        match self {
+            Shortcut::New => Chord::ctrl(KeyCode::N),
+            Shortcut::Open => Chord::ctrl(KeyCode::O),
+            Shortcut::Save => Chord::ctrl(KeyCode::S),
+            Shortcut::Undo => Chord::ctrl(KeyCode::Z),
+            Shortcut::Redo => Chord::ctrl_shift(KeyCode::Z),
+            Shortcut::Cut => Chord::ctrl(KeyCode::X),
+            Shortcut::Copy => Chord::ctrl(KeyCode::C),
+            Shortcut::Paste => Chord::ctrl(KeyCode::V),
+            Shortcut::Duplicate => Chord::ctrl(KeyCode::D),
+            Shortcut::CopyText => Chord::ctrl_shift(KeyCode::C),
+            Shortcut::PasteText => Chord::ctrl_shift(KeyCode::V),
+            Shortcut::Delete => Chord::key(KeyCode::Delete),
+            Shortcut::RotateLeft => Chord::ctrl(KeyCode::R),
+            Shortcut::RotateRight => Chord::ctrl_shift(KeyCode::R),
+            Shortcut::PanMode => Chord::key(KeyCode::P),
+            Shortcut::SelectMode => Chord::key(KeyCode::S),
+        }
+    }
+}
+
+/// The key chords bound to each [`Shortcut`].
+///
+/// The keymap is stored in `keymap.ron` within the configuration
+/// directory of the user. Shortcuts missing from the file use
+/// their default chord.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Keymap(HashMap<Shortcut, Chord>);
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Self(Shortcut::ALL.iter().map(|&s| (s, s.default_chord())).collect())
+    }
+}
+
+impl Keymap {
+    pub fn get(&self, shortcut: Shortcut) -> Chord {
+        self.0
+            .get(&shortcut)
+            .copied()
+            .unwrap_or_else(|| shortcut.default_chord())
+    }
+
+    pub fn set(&mut self, shortcut: Shortcut, chord: Chord) {
+        self.0.insert(shortcut, chord);
+    }
+
+    /// Check if the chord of the given `shortcut` has just been pressed.
+    pub fn just_pressed(&self, shortcut: Shortcut, keyboard: &Input<KeyCode>) -> bool {
+        self.get(shortcut).just_pressed(keyboard)
+    }
+
+    /// Check if the chord of the given `shortcut` is also bound
+    /// to another shortcut.
+    pub fn conflicts(&self, shortcut: Shortcut) -> bool {
+        let chord = self.get(shortcut);
+        Shortcut::ALL
+            .iter()
+            .any(|&s| s != shortcut && self.get(s) == chord)
+    }
+
+    const FILE: &'static str = "keymap.ron";
+
+    /// Parse a keymap from RON text, i.e. a map from shortcuts to chords.
+    ///
+    /// Returns the keymap and the chords that couldn't be parsed.
+    pub fn from_text(text: &str) -> Result<(Self, Vec<String>), ron::Error> {
+        let map: BTreeMap<Shortcut, String> = ron::from_str(text)?;
+        let mut keymap = Self::default();
+        let mut invalid = Vec::new();
+
+        for (shortcut, chord) in map {
+            match Chord::parse(&chord) {
+                Some(chord) => keymap.set(shortcut, chord),
+                None => invalid.push(chord),
+            }
+        }
+
+        Ok((keymap, invalid))
+    }
+
+    pub fn to_text(&self) -> Result<String, ron::Error> {
+        let map: BTreeMap<Shortcut, String> = Shortcut::ALL
+            .iter()
+            .map(|&s| (s, self.get(s).to_string()))
+            .collect();
+        to_string_pretty(&map, PrettyConfig::new())
+    }
+
+    /// Load the keymap of the user or the default keymap
+    /// if there is none.
+    pub fn load(log: &mut Log) -> Self {
+        let text = match config_path(Self::FILE).and_then(|path| fs::read_to_string(path).ok()) {
+            Some(text) => text,
+            None => return Self::default(),
+        };
+
+        match Self::from_text(&text) {
+            Ok((keymap, invalid)) => {
+                for chord in invalid {
+                    log.warn(format!("invalid key chord in keymap: {}", chord));
+                }
+                keymap
+            }
+            Err(e) => {
+                log.error(format!("unable to read keymap: {}", e));
+                Self::default()
+            }
+        }
+    }
+
+    /// Write the keymap to the keymap file of the user.
+    pub fn save(&self) -> io::Result<()> {
+        let text = self
+            .to_text()
+            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+        write_config(Self::FILE, &text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_key_names() {
+        for &(key, name) in NAMED_KEYS {
+            assert_eq!(key_name(key), Some(name));
+            assert_eq!(key_from_name(name), Some(key));
+        }
+
+        assert_eq!(key_label(KeyCode::Key7), "7");
+        assert_eq!(key_label(KeyCode::F4), "F4");
+        assert_eq!(key_name(KeyCode::Escape), None);
+        assert_eq!(key_from_name("Escape"), None);
+    }
+
+    #[test]
+    fn test_chords() {
+        assert_eq!(Chord::parse("Ctrl+Shift+Z"), Some(Chord::ctrl_shift(KeyCode::Z)));
+        assert_eq!(Chord::parse("Delete"), Some(Chord::key(KeyCode::Delete)));
+        assert_eq!(Chord::parse("Ctrl+7"), Some(Chord::ctrl(KeyCode::Key7)));
+        assert_eq!(Chord::parse("Ctrl+Key7"), Some(Chord::ctrl(KeyCode::Key7)));
+        assert_eq!(Chord::parse("Ctrl+Shift"), None);
+        assert_eq!(Chord::parse("A+B"), None);
+        assert_eq!(Chord::parse("Ctrl+LShift"), None);
+
+        for &shortcut in Shortcut::ALL.iter() {
+            let chord = shortcut.default_chord();
+            assert_eq!(Chord::parse(&chord.to_string()), Some(chord));
+        }
+    }
+
+    #[test]
+    fn test_keymap_text() {
+        let mut keymap = Keymap::default();
+        keymap.set(Shortcut::Undo, Chord::ctrl(KeyCode::Y));
+        assert!(!keymap.conflicts(Shortcut::Undo));
+        keymap.set(Shortcut::Redo, Chord::ctrl(KeyCode::Y));
+        assert!(keymap.conflicts(Shortcut::Undo));
+
+        let text = keymap.to_text().unwrap();
+        let (parsed, invalid) = Keymap::from_text(&text).unwrap();
+        assert_eq!(parsed, keymap);
+        assert!(invalid.is_empty());
+
+        // Missing shortcuts use the default chord.
+        let (parsed, invalid) = Keymap::from_text("{Save: \"Ctrl+W\", Open: \"Nope\"}").unwrap();
+        assert_eq!(parsed.get(Shortcut::Save), Chord::ctrl(KeyCode::W));
+        assert_eq!(parsed.get(Shortcut::Open), Chord::ctrl(KeyCode::O));
+        assert_eq!(invalid, vec!["Nope".to_string()]);
+    }
+}
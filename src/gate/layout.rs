@@ -0,0 +1,283 @@
+use crate::gate::{core::*, serialize::NodeType, undo::*};
+use bevy::prelude::*;
+use nodus::world2d::interaction2d::Interactable;
+use std::collections::{HashMap, HashSet};
+
+/// Horizontal space between two layers.
+const LAYER_GAP: f32 = 192.;
+/// Vertical space between two components of the same layer.
+const NODE_GAP: f32 = 64.;
+/// Height reserved for a line passing through a layer.
+const DUMMY_HEIGHT: f32 = 32.;
+/// Number of sweeps of the crossing reduction.
+const SWEEPS: usize = 8;
+
+/// Request to arrange all components of the circuit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ArrangeEvent;
+
+/// A vertex of the layered graph, i.e. a component or a dummy
+/// vertex of a line spanning multiple layers.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Vertex {
+    size: Vec2,
+    layer: usize,
+}
+
+/// Mark all vertices reachable from `u` and collect the edges
+/// closing a cycle.
+fn find_cycles(
+    u: usize,
+    adj: &[Vec<usize>],
+    state: &mut [u8],
+    back: &mut HashSet<(usize, usize)>,
+) {
+    state[u] = 1;
+    for &v in adj[u].iter() {
+        match state[v] {
+            0 => find_cycles(v, adj, state, back),
+            1 => {
+                back.insert((u, v));
+            }
+            _ => {}
+        }
+    }
+    state[u] = 2;
+}
+
+/// Compute a layered (Sugiyama-style) layout.
+///
+/// Each node is given by its size and whether it's a sink, i.e. an
+/// output control, that should be placed in the last layer. The
+/// `edges` connect the outputs of one node with the inputs of another.
+///
+/// The returned positions are the centers of the nodes.
+fn layout(nodes: &[(Vec2, bool)], edges: &HashSet<(usize, usize)>) -> Vec<Vec2> {
+    let n = nodes.len();
+
+    // 1. Break cycles by reversing the edges closing them.
+    let mut adj = vec![Vec::new(); n];
+    for &(u, v) in edges.iter() {
+        adj[u].push(v);
+    }
+    for list in adj.iter_mut() {
+        list.sort_unstable();
+    }
+    let mut state = vec![0; n];
+    let mut back = HashSet::new();
+    for u in 0..n {
+        if state[u] == 0 {
+            find_cycles(u, &adj, &mut state, &mut back);
+        }
+    }
+    let mut dag: Vec<(usize, usize)> = edges
+        .iter()
+        .map(|&(u, v)| if back.contains(&(u, v)) { (v, u) } else { (u, v) })
+        .collect();
+    dag.sort_unstable();
+    dag.dedup();
+
+    // 2. Assign each node to a layer using the longest path from the sources.
+    let mut indegree = vec![0; n];
+    let mut succ = vec![Vec::new(); n];
+    for &(u, v) in dag.iter() {
+        indegree[v] += 1;
+        succ[u].push(v);
+    }
+    let mut layer = vec![0; n];
+    let mut queue: Vec<usize> = (0..n).filter(|&u| indegree[u] == 0).collect();
+    while let Some(u) = queue.pop() {
+        for &v in succ[u].iter() {
+            layer[v] = layer[v].max(layer[u] + 1);
+            indegree[v] -= 1;
+            if indegree[v] == 0 {
+                queue.push(v);
+            }
+        }
+    }
+    let last = layer.iter().copied().max().unwrap_or(0);
+    for (u, &(_, sink)) in nodes.iter().enumerate() {
+        if sink && succ[u].is_empty() {
+            layer[u] = last;
+        }
+    }
+
+    // 3. Split edges spanning multiple layers using dummy vertices.
+    let mut vertices: Vec<Vertex> = (0..n)
+        .map(|u| Vertex {
+            size: nodes[u].0,
+            layer: layer[u],
+        })
+        .collect();
+    let mut links: Vec<(usize, usize)> = Vec::new();
+    for &(u, v) in dag.iter() {
+        let mut prev = u;
+        for l in layer[u] + 1..layer[v] {
+            vertices.push(Vertex {
+                size: Vec2::new(0., DUMMY_HEIGHT),
+                layer: l,
+            });
+            links.push((prev, vertices.len() - 1));
+            prev = vertices.len() - 1;
+        }
+        links.push((prev, v));
+    }
+
+    let mut layers: Vec<Vec<usize>> = vec![Vec::new(); last + 1];
+    for (i, vertex) in vertices.iter().enumerate() {
+        layers[vertex.layer].push(i);
+    }
+
+    // 4. Reduce crossings by ordering each layer by the barycenter
+    //    of the neighbours in the previous (or next) layer.
+    let mut pos = vec![0.; vertices.len()];
+    for l in layers.iter() {
+        for (i, &v) in l.iter().enumerate() {
+            pos[v] = i as f32;
+        }
+    }
+    for sweep in 0..SWEEPS {
+        let down = sweep % 2 == 0;
+        let order: Vec<usize> = if down {
+            (1..layers.len()).collect()
+        } else {
+            (0..layers.len().saturating_sub(1)).rev().collect()
+        };
+
+        for l in order {
+            let mut center: HashMap<usize, f32> = HashMap::new();
+            for &v in layers[l].iter() {
+                let neighbours: Vec<f32> = links
+                    .iter()
+                    .filter_map(|&(a, b)| match down {
+                        true if b == v => Some(pos[a]),
+                        false if a == v => Some(pos[b]),
+                        _ => None,
+                    })
+                    .collect();
+                let c = if neighbours.is_empty() {
+                    pos[v]
+                } else {
+                    neighbours.iter().sum::<f32>() / neighbours.len() as f32
+                };
+                center.insert(v, c);
+            }
+
+            layers[l].sort_by(|a, b| {
+                center[a]
+                    .partial_cmp(&center[b])
+                    .unwrap_or(std::cmp::Ordering::Equal)
+            });
+            for (i, &v) in layers[l].iter().enumerate() {
+                pos[v] = i as f32;
+            }
+        }
+    }
+
+    // 5. Assign coordinates, layers from left to right and the
+    //    vertices of each layer from top to bottom.
+    let mut result = vec![Vec2::ZERO; n];
+    let mut x = 0.;
+    for l in layers.iter() {
+        let width = l.iter().map(|&v| vertices[v].size.x).fold(0., f32::max);
+        let height: f32 = l.iter().map(|&v| vertices[v].size.y + NODE_GAP).sum::<f32>() - NODE_GAP;
+
+        let mut y = height / 2.;
+        for &v in l.iter() {
+            let h = vertices[v].size.y;
+            if v < n {
+                result[v] = Vec2::new(x + width / 2., y - h / 2.);
+            }
+            y -= h + NODE_GAP;
+        }
+        x += width + LAYER_GAP;
+    }
+
+    result
+}
+
+/// Arrange all components in layers by their logic depth, i.e. inputs
+/// on the left and outputs on the right.
+///
+/// The arrangement can be undone as a single action.
+pub fn arrange_system(
+    mut commands: Commands,
+    mut ev_arrange: EventReader<ArrangeEvent>,
+    mut stack: ResMut<UndoStack>,
+    q_node: Query<
+        (
+            Entity,
+            &Transform,
+            &Interactable,
+            Option<&Targets>,
+            Option<&Outputs>,
+        ),
+        With<NodeType>,
+    >,
+) {
+    if ev_arrange.iter().count() == 0 {
+        return;
+    }
+
+    let mut entities = Vec::new();
+    let mut nodes = Vec::new();
+    let mut index = HashMap::new();
+    let mut centroid = Vec2::ZERO;
+
+    for (entity, transform, interactable, _, outputs) in q_node.iter() {
+        let (_, dimensions) = interactable.bounds();
+        let size = transform
+            .rotation
+            .mul_vec3(dimensions.extend(0.))
+            .truncate()
+            .abs();
+        let sink = outputs.map_or(true, |o| o.is_empty());
+
+        index.insert(entity, entities.len());
+        entities.push((entity, *transform));
+        nodes.push((size, sink));
+        centroid += transform.translation.truncate();
+    }
+
+    if entities.is_empty() {
+        return;
+    }
+    centroid /= entities.len() as f32;
+
+    let mut edges = HashSet::new();
+    for (entity, _, _, targets, _) in q_node.iter() {
+        if let Some(targets) = targets {
+            for map in targets.iter() {
+                for target in map.keys() {
+                    if let Some(&v) = index.get(target) {
+                        if v != index[&entity] {
+                            edges.insert((index[&entity], v));
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    let positions = layout(&nodes, &edges);
+
+    // Keep the circuit where it was.
+    let center = positions.iter().fold(Vec2::ZERO, |a, &b| a + b) / positions.len() as f32;
+    let offset = centroid - center;
+
+    let mut moved = Vec::new();
+    for ((entity, transform), position) in entities.into_iter().zip(positions) {
+        let position = position + offset;
+        if position != transform.translation.truncate() {
+            moved.push((entity, transform));
+
+            let mut transform = transform;
+            transform.translation = position.extend(transform.translation.z);
+            commands.entity(entity).insert(transform);
+        }
+    }
+
+    if !moved.is_empty() {
+        stack.push(Action::Transform(moved));
+    }
+}
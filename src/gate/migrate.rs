@@ -0,0 +1,460 @@
+//! Versions of the save format, migrations between them and the
+//! validation of save files.
+//!
+//! History of the format:
+//!
+//! - `0.1`: initial format, components of early files have no rotation.
+//!   Clocks store the time since their output last toggled.
+//! - `0.2`: all components have a rotation. Clocks store the time since
+//!   they started and the position within a period as their phase.
+
+use crate::gate::{
+    core::{Gate, NodeRange},
+    graphics::{
+        clk::Clk, dip_switch::DipSwitch, dot_matrix::DotMatrix, led_bar::LedBar,
+        pattern_generator::PatternGenerator, segment_display::HexDisplay,
+    },
+    serialize::*,
+};
+use bevy::prelude::*;
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::io;
+use std::ops::RangeInclusive;
+
+impl Version {
+    /// The version written by this build of nodus.
+    pub const CURRENT: Version = Version { major: 0, minor: 2 };
+}
+
+/// Upgrade a save from one version of the format to the next one.
+type Migration = fn(&mut NodusSave);
+
+/// Migrations from each version to its successor.
+const MIGRATIONS: &[(Version, Version, Migration)] = &[(
+    Version { major: 0, minor: 1 },
+    Version { major: 0, minor: 2 },
+    migrate_0_1,
+)];
+
+fn migrate_0_1(save: &mut NodusSave) {
+    for nc in save.entities.iter_mut() {
+        nc.rotation.get_or_insert(Quat::IDENTITY);
+
+        if let Some(NodeState::Clock(half_period, since_toggle, output, _)) = nc.state {
+            nc.state = Some(Clk::from_legacy(half_period, since_toggle, output));
+        }
+    }
+}
+
+/// The reason a save file couldn't be loaded.
+#[derive(Debug)]
+pub enum LoadError {
+    Io(io::Error),
+    Parse(ron::Error),
+    /// The file has been saved by a newer version of nodus.
+    Newer(Version),
+    /// There is no migration from the version of the file.
+    Unknown(Version),
+    /// Structural problems found by [`validate`].
+    Invalid(Vec<String>),
+}
+
+impl LoadError {
+    /// Describe the error, one problem per line.
+    pub fn problems(&self) -> Vec<String> {
+        match self {
+            LoadError::Invalid(problems) => problems.clone(),
+            e => vec![e.to_string()],
+        }
+    }
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LoadError::Io(e) => write!(f, "unable to read file: {}", e),
+            LoadError::Parse(e) => write!(f, "unable to parse file: {}", e),
+            LoadError::Newer(v) => write!(
+                f,
+                "saved with format {} by a newer version of nodus, format {} is supported",
+                v,
+                Version::CURRENT
+            ),
+            LoadError::Unknown(v) => write!(f, "unknown format {}", v),
+            LoadError::Invalid(problems) => {
+                write!(f, "invalid file: {}", problems.join("; "))
+            }
+        }
+    }
+}
+
+/// The file that couldn't be loaded and why, shown to the user.
+#[derive(Debug, Default)]
+pub struct LoadReport(pub Option<(String, LoadError)>);
+
+/// The part of a save file read before the rest of it, to
+/// reject files that can't be parsed by this version.
+#[derive(Deserialize)]
+struct Header {
+    version: Version,
+}
+
+/// Parse a save file, upgrade it to the current version and
+/// validate it.
+pub fn parse(text: &str) -> Result<NodusSave, LoadError> {
+    let header: Header = ron::from_str(text).map_err(LoadError::Parse)?;
+    if header.version > Version::CURRENT {
+        return Err(LoadError::Newer(header.version));
+    }
+
+    let mut save: NodusSave = ron::from_str(text).map_err(LoadError::Parse)?;
+    check(&mut save)?;
+    Ok(save)
+}
+
+/// Upgrade a save to the current version and validate it.
+pub fn check(save: &mut NodusSave) -> Result<(), LoadError> {
+    migrate(save)?;
+
+    let problems = validate(&save.entities);
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        Err(LoadError::Invalid(problems))
+    }
+}
+
+/// Apply all migrations from the version of the save to the
+/// current version.
+pub fn migrate(save: &mut NodusSave) -> Result<(), LoadError> {
+    while save.version != Version::CURRENT {
+        let (_, to, migration) = MIGRATIONS
+            .iter()
+            .find(|(from, _, _)| *from == save.version)
+            .ok_or(LoadError::Unknown(save.version))?;
+        migration(save);
+        save.version = *to;
+    }
+    Ok(())
+}
+
+/// The range of a component with `step` inputs, outputs or
+/// rows per unit, e.g. four inputs per digit.
+fn inputs(range: NodeRange, step: usize) -> RangeInclusive<usize> {
+    range.min as usize * step..=range.max as usize * step
+}
+
+/// The number of inputs allowed for the given type, if it
+/// is configurable.
+fn input_range(ntype: &NodeType) -> Option<RangeInclusive<usize>> {
+    match ntype {
+        NodeType::And | NodeType::Nand | NodeType::Or | NodeType::Nor | NodeType::Xor => {
+            Some(inputs(Gate::INPUT_RANGE, 1))
+        }
+        NodeType::Not => Some(inputs(Gate::NOT_RANGE, 1)),
+        NodeType::LedBar => Some(inputs(LedBar::RANGE, 1)),
+        NodeType::HexDisplay => Some(inputs(HexDisplay::RANGE, 4)),
+        _ => None,
+    }
+}
+
+/// Check whether the given state belongs to the type, i.e. whether
+/// a component of that type can be spawned with it.
+fn state_matches(ntype: &NodeType, state: &Option<NodeState>) -> bool {
+    matches!(
+        (ntype, state),
+        (NodeType::ToggleSwitch, Some(NodeState::ToggleSwitch(..)))
+            | (NodeType::Clock, Some(NodeState::Clock(..)))
+            | (NodeType::LightBulb, Some(NodeState::LightBulb(..)))
+            | (NodeType::DipSwitch, Some(NodeState::DipSwitch(..)))
+            | (NodeType::HexKeypad, Some(NodeState::HexKeypad(..)))
+            | (NodeType::DotMatrix, Some(NodeState::DotMatrix(..)))
+            | (NodeType::Terminal, Some(NodeState::Terminal(..)))
+            | (NodeType::AsciiKeyboard, Some(NodeState::AsciiKeyboard(..)))
+            | (NodeType::PatternGenerator, Some(NodeState::PatternGenerator(..)))
+            | (NodeType::NetLabel, Some(NodeState::NetLabel(..)))
+            | (
+                NodeType::And
+                    | NodeType::Nand
+                    | NodeType::Or
+                    | NodeType::Nor
+                    | NodeType::Xor
+                    | NodeType::Xnor
+                    | NodeType::Not
+                    | NodeType::HighConst
+                    | NodeType::LowConst
+                    | NodeType::SevenSegmentDisplay
+                    | NodeType::PushButton
+                    | NodeType::LedBar
+                    | NodeType::SevenSegmentDirect
+                    | NodeType::HexDisplay,
+                _
+            )
+    )
+}
+
+/// Find values of a state that a component can't be spawned with.
+fn state_problems(state: &NodeState) -> Vec<String> {
+    let mut problems = Vec::new();
+
+    match state {
+        NodeState::Clock(half_period, ..) if !(*half_period > 0.) => {
+            problems.push(format!("invalid clock half period {}", half_period));
+        }
+        NodeState::DipSwitch(states) if !inputs(DipSwitch::RANGE, 1).contains(&states.len()) => {
+            problems.push(format!("invalid number of switches {}", states.len()));
+        }
+        NodeState::DotMatrix(rows, cols, _, frame) => {
+            let range = inputs(DotMatrix::RANGE, 1);
+            if !range.contains(rows) {
+                problems.push(format!("invalid number of rows {}", rows));
+            }
+            if !range.contains(cols) {
+                problems.push(format!("invalid number of columns {}", cols));
+            }
+            if frame.len() > *rows {
+                problems.push(format!("frame of {} rows for {} rows", frame.len(), rows));
+            }
+        }
+        NodeState::PatternGenerator(config) => {
+            if !inputs(PatternGenerator::RANGE, 1).contains(&config.outputs) {
+                problems.push(format!("invalid number of outputs {}", config.outputs));
+            }
+            if let Some(frequency) = config.frequency {
+                if !(frequency > 0.) {
+                    problems.push(format!("invalid frequency {}", frequency));
+                }
+            }
+        }
+        _ => {}
+    }
+
+    problems
+}
+
+/// Find structural problems that would prevent the given components,
+/// e.g. the entities of a save, from being spawned and connected.
+///
+/// Returns a description of each problem.
+pub fn validate(entities: &[NodusComponent]) -> Vec<String> {
+    let mut problems = Vec::new();
+
+    let mut components: HashMap<ComponentId, &NodusComponent> = HashMap::new();
+    for nc in entities.iter() {
+        if components.insert(nc.id, nc).is_some() {
+            problems.push(format!("{} ({}): id used more than once", nc.name, nc.id));
+        }
+        if nc.id > ComponentId::MAX {
+            problems.push(format!("{} ({}): id too large", nc.name, nc.id));
+        }
+    }
+
+    for nc in entities.iter() {
+        let name = format!("{} ({})", nc.name, nc.id);
+
+        if let NodeType::Xnor = nc.ntype {
+            problems.push(format!("{}: XNOR gates are not supported", name));
+        }
+
+        if let Some(range) = input_range(&nc.ntype) {
+            match nc.inputs {
+                Some(n) if range.contains(&n) => {}
+                Some(n) => problems.push(format!("{}: invalid number of inputs {}", name, n)),
+                None => problems.push(format!("{}: number of inputs missing", name)),
+            }
+        }
+        if let (NodeType::HexDisplay, Some(n)) = (&nc.ntype, nc.inputs) {
+            if n % 4 != 0 {
+                problems.push(format!("{}: {} inputs aren't a multiple of 4", name, n));
+            }
+        }
+        let gate = matches!(
+            nc.ntype,
+            NodeType::And
+                | NodeType::Nand
+                | NodeType::Or
+                | NodeType::Nor
+                | NodeType::Xor
+                | NodeType::Not
+        );
+        if gate && nc.outputs.is_none() {
+            problems.push(format!("{}: number of outputs missing", name));
+        }
+
+        if !state_matches(&nc.ntype, &nc.state) {
+            problems.push(format!("{}: state missing or of the wrong type", name));
+        }
+        if let Some(state) = &nc.state {
+            for problem in state_problems(state) {
+                problems.push(format!("{}: {}", name, problem));
+            }
+        }
+
+        if let Some(targets) = &nc.targets {
+            if let Some(n) = nc.outputs {
+                if targets.len() > n {
+                    problems.push(format!(
+                        "{}: connections for {} outputs but only {} outputs",
+                        name,
+                        targets.len(),
+                        n
+                    ));
+                }
+            }
+
+            for (i, map) in targets.iter().enumerate() {
+                for (target, indices) in map.iter() {
+                    let target = match components.get(target) {
+                        Some(target) => target,
+                        None => {
+                            problems.push(format!(
+                                "{}: output {} connected to missing component {}",
+                                name,
+                                i,
+                                target
+                            ));
+                            continue;
+                        }
+                    };
+
+                    if let Some(n) = target.inputs {
+                        let mut indices: Vec<&usize> = indices.iter().filter(|&&j| j >= n).collect();
+                        indices.sort();
+                        for j in indices {
+                            problems.push(format!(
+                                "{}: output {} connected to input {} of {} ({}) with {} inputs",
+                                name,
+                                i,
+                                j,
+                                target.name,
+                                target.id,
+                                n
+                            ));
+                        }
+                    }
+                }
+            }
+        }
+
+        let connected: HashSet<ComponentId> = nc
+            .targets
+            .iter()
+            .flat_map(|targets| targets.iter())
+            .flat_map(|map| map.keys().copied())
+            .collect();
+        for wire in nc.wires.iter().filter(|w| !connected.contains(&w.target)) {
+            problems.push(format!(
+                "{}: waypoints of a missing connection to {}",
+                name,
+                wire.target
+            ));
+        }
+    }
+
+    problems
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FLIPFLOP: &str = include_str!("../../circuits/tacktflankengesteuertes-flipflop.ron");
+
+    fn save(version: &str, entities: &str) -> String {
+        format!(
+            "(time: \"2022-02-03T18:02:51.231419653+01:00\", application: \"Nodus\", \
+             version: {}, entities: [{}])",
+            version, entities
+        )
+    }
+
+    #[test]
+    fn test_migrate() {
+        let save = parse(FLIPFLOP).unwrap();
+        assert_eq!(save.version, Version::CURRENT);
+        assert_eq!(save.entities.len(), 10);
+        assert!(save.entities.iter().all(|nc| nc.rotation == Some(Quat::IDENTITY)));
+
+        // The clock toggled every second and has been high for 0.017 seconds.
+        let clock = save.entities.iter().find(|nc| nc.name == "Clock").unwrap();
+        match clock.state {
+            Some(NodeState::Clock(half_period, elapsed, output, config)) => {
+                assert_eq!(half_period, 1.0);
+                assert_eq!(elapsed, 0.0);
+                assert_eq!(output, crate::gate::core::State::High);
+                assert!((config.phase - 0.016932108 / 2.0).abs() < 1e-6);
+                assert_eq!(config.duty, 0.5);
+            }
+            ref state => panic!("expected clock state, got {:?}", state),
+        }
+    }
+
+    #[test]
+    fn test_versions() {
+        let newer = save("(major: 0, minor: 3)", "");
+        assert!(matches!(parse(&newer), Err(LoadError::Newer(_))));
+
+        let unknown = save("(major: 0, minor: 0)", "");
+        assert!(matches!(parse(&unknown), Err(LoadError::Unknown(_))));
+
+        assert!(matches!(parse("(version: 1)"), Err(LoadError::Parse(_))));
+    }
+
+    #[test]
+    fn test_validate() {
+        let text = save(
+            "(major: 0, minor: 2)",
+            "(id: 1, name: \"NOT Gate\", inputs: Some(1), outputs: Some(1), \
+              targets: Some(([({2: ([0]), 3: ([5])})])), position: (0.0, 0.0), \
+              rotation: None, ntype: Not, state: None), \
+             (id: 3, name: \"NOT Gate\", inputs: Some(3), outputs: Some(1), \
+              targets: None, position: (0.0, 0.0), rotation: None, ntype: Not, state: None), \
+             (id: 4, name: \"Toggle Switch\", inputs: None, outputs: Some(1), \
+              targets: None, position: (0.0, 0.0), rotation: None, ntype: ToggleSwitch, \
+              state: None), \
+             (id: 5, name: \"Dot Matrix\", inputs: Some(8), outputs: None, \
+              targets: None, position: (0.0, 0.0), rotation: None, ntype: DotMatrix, \
+              state: Some(DotMatrix(0, 8, RowColumn, [0]))), \
+             (id: 6, name: \"Dot Matrix\", inputs: Some(8), outputs: None, \
+              targets: None, position: (0.0, 0.0), rotation: None, ntype: DotMatrix, \
+              state: Some(DotMatrix(2, 17, RowColumn, []))), \
+             (id: 7, name: \"Clock\", inputs: None, outputs: Some(1), \
+              targets: None, position: (0.0, 0.0), rotation: None, ntype: Clock, \
+              state: Some(Clock(0.0, 0.0, Low))), \
+             (id: 8, name: \"DIP Switch\", inputs: None, outputs: Some(17), \
+              targets: None, position: (0.0, 0.0), rotation: None, ntype: DipSwitch, \
+              state: Some(DipSwitch([]))), \
+             (id: 9, name: \"Pattern Generator\", inputs: Some(1), outputs: Some(0), \
+              targets: None, position: (0.0, 0.0), rotation: None, ntype: PatternGenerator, \
+              state: Some(PatternGenerator((outputs: 0, patterns: [], mode: Table, \
+              frequency: Some(-1.0), looping: false)))), \
+             (id: 18446744073709551615, name: \"NOT Gate\", inputs: Some(1), \
+              outputs: Some(1), targets: None, position: (0.0, 0.0), rotation: None, \
+              ntype: Not, state: None)",
+        );
+
+        let problems = match parse(&text) {
+            Err(LoadError::Invalid(problems)) => problems,
+            res => panic!("expected problems, got {:?}", res.map(|save| save.entities.len())),
+        };
+        assert_eq!(problems.len(), 12);
+        for problem in [
+            "NOT Gate (1): output 0 connected to missing component 2",
+            "NOT Gate (1): output 0 connected to input 5 of NOT Gate (3) with 3 inputs",
+            "NOT Gate (3): invalid number of inputs 3",
+            "Toggle Switch (4): state missing or of the wrong type",
+            "Dot Matrix (5): invalid number of rows 0",
+            "Dot Matrix (5): frame of 1 rows for 0 rows",
+            "Dot Matrix (6): invalid number of columns 17",
+            "Clock (7): invalid clock half period 0",
+            "DIP Switch (8): invalid number of switches 0",
+            "Pattern Generator (9): invalid number of outputs 0",
+            "Pattern Generator (9): invalid frequency -1",
+            "NOT Gate (18446744073709551615): id too large",
+        ] {
+            assert!(problems.contains(&problem.to_string()), "{}", problem);
+        }
+    }
+}
@@ -0,0 +1,87 @@
+use bevy::prelude::*;
+use chrono::prelude::*;
+use std::collections::VecDeque;
+use std::fmt;
+
+/// Severity of a [`Message`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Level {
+    Info,
+    Warning,
+    Error,
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Level::Info => write!(f, "Info"),
+            Level::Warning => write!(f, "Warning"),
+            Level::Error => write!(f, "Error"),
+        }
+    }
+}
+
+/// A message for the user, e.g. why a file couldn't be saved.
+#[derive(Debug, Clone)]
+pub struct Message {
+    pub time: DateTime<Local>,
+    pub level: Level,
+    pub text: String,
+    /// The toast of the message has been closed by the user.
+    pub dismissed: bool,
+}
+
+impl Message {
+    /// Seconds the message is shown as a toast.
+    pub const TOAST_DURATION: i64 = 5;
+
+    /// Check whether the message should still be shown as a toast.
+    pub fn is_toast(&self, now: DateTime<Local>) -> bool {
+        !self.dismissed && (now - self.time).num_seconds() < Self::TOAST_DURATION
+    }
+}
+
+/// The messages of the current session, shown as toasts when they
+/// arrive and listed in the log window.
+///
+/// Messages are also written to the console.
+#[derive(Debug, Default)]
+pub struct Log {
+    pub messages: VecDeque<Message>,
+}
+
+impl Log {
+    /// Maximum number of messages kept, older ones are dropped.
+    pub const MAX: usize = 500;
+
+    pub fn push(&mut self, level: Level, text: impl Into<String>) {
+        let text = text.into();
+        match level {
+            Level::Info => info!("{}", text),
+            Level::Warning => warn!("{}", text),
+            Level::Error => error!("{}", text),
+        }
+
+        self.messages.push_back(Message {
+            time: Local::now(),
+            level,
+            text,
+            dismissed: false,
+        });
+        if self.messages.len() > Self::MAX {
+            self.messages.pop_front();
+        }
+    }
+
+    pub fn info(&mut self, text: impl Into<String>) {
+        self.push(Level::Info, text);
+    }
+
+    pub fn warn(&mut self, text: impl Into<String>) {
+        self.push(Level::Warning, text);
+    }
+
+    pub fn error(&mut self, text: impl Into<String>) {
+        self.push(Level::Error, text);
+    }
+}
@@ -0,0 +1,146 @@
+use crate::gate::notify::Log;
+use crate::gate::undo::UndoStack;
+use bevy::prelude::*;
+use bevy_egui::EguiSettings;
+use ron::ser::{to_string_pretty, PrettyConfig};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+/// Get the path of the given file within the configuration
+/// directory of nodus, e.g. `~/.config/nodus/<file>`.
+pub fn config_path(file: &str) -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("nodus").join(file))
+}
+
+/// Write `text` to the given file within the configuration directory.
+pub fn write_config(file: &str, text: &str) -> io::Result<()> {
+    let path = config_path(file)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no configuration directory"))?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, text)
+}
+
+/// Settings of the user, stored in `preferences.ron` within the
+/// configuration directory.
+///
+/// Settings missing from the file use their default value.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Preferences {
+    /// Scale factor of the user interface.
+    pub scale_factor: f64,
+    /// Background color as RGB.
+    pub background: [f32; 3],
+    pub window_width: f32,
+    pub window_height: f32,
+    pub vsync: bool,
+    /// Directory the file browser starts in, the home
+    /// directory if not set.
+    pub default_dir: Option<PathBuf>,
+    /// The maximum number of actions that can be undone.
+    pub undo_steps: usize,
+    /// Reopen the file of the last session at startup.
+    pub reopen_last: bool,
+    /// Seconds between two autosaves, zero disables autosaving.
+    pub autosave_interval: u64,
+}
+
+impl Default for Preferences {
+    fn default() -> Self {
+        Self {
+            scale_factor: 1.5,
+            background: [0.75, 0.75, 0.75],
+            window_width: 1920.,
+            window_height: 1080.,
+            vsync: true,
+            default_dir: None,
+            undo_steps: UndoStack::DEFAULT_MAX,
+            reopen_last: false,
+            autosave_interval: 60,
+        }
+    }
+}
+
+impl Preferences {
+    const FILE: &'static str = "preferences.ron";
+
+    /// Minimum and maximum scale factor.
+    pub const SCALE_RANGE: (f64, f64) = (0.5, 3.0);
+    /// Minimum and maximum width and height of the window.
+    pub const WINDOW_RANGE: (f32, f32) = (640., 7680.);
+    /// Minimum and maximum seconds between two autosaves.
+    pub const AUTOSAVE_RANGE: (u64, u64) = (0, 3600);
+
+    pub fn background_color(&self) -> Color {
+        let [r, g, b] = self.background;
+        Color::rgb(r, g, b)
+    }
+
+    /// The directory the file browser starts in.
+    pub fn browser_dir(&self) -> PathBuf {
+        self.default_dir
+            .clone()
+            .filter(|dir| dir.is_dir())
+            .or_else(dirs::home_dir)
+            .expect("home dir to exist")
+    }
+
+    /// Load the preferences of the user or the default
+    /// preferences if there are none.
+    pub fn load(log: &mut Log) -> Self {
+        let text = match config_path(Self::FILE).and_then(|path| fs::read_to_string(path).ok()) {
+            Some(text) => text,
+            None => return Self::default(),
+        };
+
+        match ron::from_str(&text) {
+            Ok(prefs) => prefs,
+            Err(e) => {
+                log.error(format!("unable to read preferences: {}", e));
+                Self::default()
+            }
+        }
+    }
+
+    /// Write the preferences to the preferences file of the user.
+    pub fn save(&self) -> io::Result<()> {
+        let text = to_string_pretty(self, PrettyConfig::new())
+            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+        write_config(Self::FILE, &text)
+    }
+}
+
+/// Apply changed preferences to the running application.
+pub fn apply_preferences_system(
+    prefs: Res<Preferences>,
+    mut egui_settings: ResMut<EguiSettings>,
+    mut clear_color: ResMut<ClearColor>,
+    mut windows: ResMut<Windows>,
+    mut stack: ResMut<UndoStack>,
+) {
+    if !prefs.is_changed() {
+        return;
+    }
+
+    egui_settings.scale_factor = prefs.scale_factor;
+    clear_color.0 = prefs.background_color();
+    // Changing the undo stack marks the circuit as modified.
+    if stack.max != prefs.undo_steps {
+        stack.max = prefs.undo_steps;
+    }
+
+    if let Some(window) = windows.get_primary_mut() {
+        if window.requested_width() != prefs.window_width
+            || window.requested_height() != prefs.window_height
+        {
+            window.set_resolution(prefs.window_width, prefs.window_height);
+        }
+        if window.vsync() != prefs.vsync {
+            window.set_vsync(prefs.vsync);
+        }
+    }
+}
@@ -0,0 +1,143 @@
+use crate::gate::{
+    file_browser::CurrentlyOpen,
+    notify::Log,
+    preferences::{config_path, write_config, Preferences},
+    serialize::LoadEvent,
+};
+use bevy::app::AppExit;
+use bevy::prelude::*;
+use nodus::world2d::camera2d::MainCamera;
+use ron::ser::{to_string_pretty, PrettyConfig};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// The file open when nodus has been closed and the view onto it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Session {
+    pub path: String,
+    /// Position of the camera.
+    pub camera: Vec2,
+    /// Scale of the camera.
+    pub zoom: f32,
+}
+
+/// The most recently used files, stored in `recent.ron` within
+/// the configuration directory.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RecentFiles {
+    /// Paths of the files, the most recent one first.
+    pub files: Vec<String>,
+    pub session: Option<Session>,
+}
+
+impl RecentFiles {
+    const FILE: &'static str = "recent.ron";
+
+    /// Maximum number of remembered files.
+    pub const MAX: usize = 10;
+
+    /// Move the given path to the front of the list.
+    pub fn add(&mut self, path: &str) {
+        self.files.retain(|p| p != path);
+        self.files.insert(0, path.to_string());
+        self.files.truncate(Self::MAX);
+    }
+
+    pub fn load(log: &mut Log) -> Self {
+        let text = match config_path(Self::FILE).and_then(|path| fs::read_to_string(path).ok()) {
+            Some(text) => text,
+            None => return Self::default(),
+        };
+
+        match ron::from_str(&text) {
+            Ok(recent) => recent,
+            Err(e) => {
+                log.error(format!("unable to read recent files: {}", e));
+                Self::default()
+            }
+        }
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        let text = to_string_pretty(self, PrettyConfig::new())
+            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+        write_config(Self::FILE, &text)
+    }
+}
+
+/// Add each file that is opened or saved to the recent files.
+pub fn recent_files_system(
+    curr_open: Res<CurrentlyOpen>,
+    mut recent: ResMut<RecentFiles>,
+    mut log: ResMut<Log>,
+) {
+    if !curr_open.is_changed() {
+        return;
+    }
+
+    if let Some(path) = &curr_open.path {
+        if recent.files.first() != Some(path) {
+            recent.add(path);
+            if let Err(e) = recent.save() {
+                log.error(format!("unable to save recent files: {}", e));
+            }
+        }
+    }
+}
+
+/// Remember the open file and the camera when nodus is closed.
+///
+/// Must run after all systems that might request the exit.
+pub fn save_session_system(
+    mut ev_exit: EventReader<AppExit>,
+    curr_open: Res<CurrentlyOpen>,
+    mut recent: ResMut<RecentFiles>,
+    q_camera: Query<&Transform, With<MainCamera>>,
+    mut log: ResMut<Log>,
+) {
+    if ev_exit.iter().count() == 0 {
+        return;
+    }
+
+    recent.session = match (&curr_open.path, q_camera.get_single()) {
+        (Some(path), Ok(transform)) => Some(Session {
+            path: path.clone(),
+            camera: transform.translation.truncate(),
+            zoom: transform.scale.x,
+        }),
+        _ => None,
+    };
+
+    if let Err(e) = recent.save() {
+        log.error(format!("unable to save session: {}", e));
+    }
+}
+
+/// Open the file of the last session, if enabled by the preferences,
+/// and restore the camera.
+pub fn restore_session_system(
+    prefs: Res<Preferences>,
+    recent: Res<RecentFiles>,
+    mut ev_load: EventWriter<LoadEvent>,
+    mut q_camera: Query<&mut Transform, With<MainCamera>>,
+) {
+    if !prefs.reopen_last {
+        return;
+    }
+
+    if let Some(session) = &recent.session {
+        if !Path::new(&session.path).is_file() {
+            return;
+        }
+
+        ev_load.send(LoadEvent(session.path.clone()));
+        if let Ok(mut transform) = q_camera.get_single_mut() {
+            transform.translation.x = session.camera.x;
+            transform.translation.y = session.camera.y;
+            transform.scale = Vec3::new(session.zoom, session.zoom, session.zoom);
+        }
+    }
+}
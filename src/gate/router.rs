@@ -0,0 +1,395 @@
+use crate::gate::{
+    core::*,
+    serialize::NodeType,
+    undo::{Action, UndoStack},
+};
+use bevy::prelude::*;
+use nodus::world2d::interaction2d::{Drag, Interactable, Selected};
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+
+/// Edge length of a cell of the routing grid.
+const GRID: f32 = 32.;
+/// Number of free cells around the routed area.
+const PADDING: i32 = 4;
+/// Maximum number of cells of the routing grid.
+const MAX_CELLS: i64 = 250_000;
+
+/// Cost of moving to a neighbouring cell.
+const STEP_COST: u32 = 10;
+/// Additional cost of changing the direction.
+const TURN_COST: u32 = 20;
+/// Additional cost of entering a cell used by another line.
+const CROSS_COST: u32 = 30;
+
+type Cell = (i32, i32);
+
+const DIRECTIONS: [Cell; 4] = [(1, 0), (-1, 0), (0, 1), (0, -1)];
+/// Pseudo direction of the start cell.
+const NO_DIRECTION: usize = 4;
+
+/// Marker component for connection lines whose waypoints are
+/// computed by the router. Such lines are always drawn with
+/// horizontal and vertical segments.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Component)]
+pub struct AutoRoute;
+
+/// Request to route the selected connection lines or all
+/// lines if no line is selected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AutoRouteEvent;
+
+fn cell(p: Vec2) -> Cell {
+    ((p.x / GRID).round() as i32, (p.y / GRID).round() as i32)
+}
+
+fn world(c: Cell) -> Vec2 {
+    Vec2::new(c.0 as f32 * GRID, c.1 as f32 * GRID)
+}
+
+/// The grid the router searches for paths.
+struct Grid {
+    min: Cell,
+    max: Cell,
+    /// Cells covered by components.
+    blocked: HashSet<Cell>,
+    /// Number of routed lines passing through a cell.
+    used: HashMap<Cell, u32>,
+}
+
+impl Grid {
+    /// Create a new grid around the given obstacles (minimum and maximum
+    /// corner of each bounding box) and end points.
+    ///
+    /// Returns `None` if the area is too large to be routed.
+    fn new(obstacles: &[(Vec2, Vec2)], points: &[Vec2]) -> Option<Self> {
+        let margin = GRID / 2.;
+        let mut min = (i32::MAX, i32::MAX);
+        let mut max = (i32::MIN, i32::MIN);
+        let mut blocked = HashSet::new();
+
+        for &(lo, hi) in obstacles {
+            let lo = (
+                ((lo.x - margin) / GRID).ceil() as i32,
+                ((lo.y - margin) / GRID).ceil() as i32,
+            );
+            let hi = (
+                ((hi.x + margin) / GRID).floor() as i32,
+                ((hi.y + margin) / GRID).floor() as i32,
+            );
+            for x in lo.0..=hi.0 {
+                for y in lo.1..=hi.1 {
+                    blocked.insert((x, y));
+                }
+            }
+            min = (min.0.min(lo.0), min.1.min(lo.1));
+            max = (max.0.max(hi.0), max.1.max(hi.1));
+        }
+
+        for &p in points {
+            let c = cell(p);
+            min = (min.0.min(c.0), min.1.min(c.1));
+            max = (max.0.max(c.0), max.1.max(c.1));
+        }
+
+        if min.0 > max.0 || min.1 > max.1 {
+            return None;
+        }
+
+        let min = (min.0 - PADDING, min.1 - PADDING);
+        let max = (max.0 + PADDING, max.1 + PADDING);
+        let cells = (max.0 - min.0 + 1) as i64 * (max.1 - min.1 + 1) as i64;
+        if cells > MAX_CELLS {
+            return None;
+        }
+
+        Some(Self {
+            min,
+            max,
+            blocked,
+            used: HashMap::new(),
+        })
+    }
+
+    fn contains(&self, c: Cell) -> bool {
+        c.0 >= self.min.0 && c.0 <= self.max.0 && c.1 >= self.min.1 && c.1 <= self.max.1
+    }
+
+    /// Find the cheapest orthogonal path from `from` to `to` using A*.
+    ///
+    /// Turns and cells already used by other lines are penalized,
+    /// which keeps the number of bends and crossings low.
+    fn route(&self, from: Cell, to: Cell) -> Option<Vec<Cell>> {
+        let h = |c: Cell| ((c.0 - to.0).abs() + (c.1 - to.1).abs()) as u32 * STEP_COST;
+
+        let mut best: HashMap<(Cell, usize), u32> = HashMap::new();
+        let mut came: HashMap<(Cell, usize), (Cell, usize)> = HashMap::new();
+        let mut open = BinaryHeap::new();
+
+        best.insert((from, NO_DIRECTION), 0);
+        open.push(Reverse((h(from), from, NO_DIRECTION)));
+
+        while let Some(Reverse((f, c, d))) = open.pop() {
+            let g = best[&(c, d)];
+            if f > g + h(c) {
+                // Outdated entry.
+                continue;
+            }
+
+            if c == to {
+                let mut path = vec![c];
+                let mut key = (c, d);
+                while let Some(&prev) = came.get(&key) {
+                    path.push(prev.0);
+                    key = prev;
+                }
+                path.reverse();
+                return Some(path);
+            }
+
+            for (nd, &(dx, dy)) in DIRECTIONS.iter().enumerate() {
+                if d != NO_DIRECTION && DIRECTIONS[d] == (-dx, -dy) {
+                    continue;
+                }
+
+                let n = (c.0 + dx, c.1 + dy);
+                if !self.contains(n) || (n != to && self.blocked.contains(&n)) {
+                    continue;
+                }
+
+                let mut cost = g + STEP_COST;
+                if d != NO_DIRECTION && d != nd {
+                    cost += TURN_COST;
+                }
+                cost += CROSS_COST * self.used.get(&n).copied().unwrap_or(0);
+
+                if cost < best.get(&(n, nd)).copied().unwrap_or(u32::MAX) {
+                    best.insert((n, nd), cost);
+                    came.insert((n, nd), (c, d));
+                    open.push(Reverse((cost + h(n), n, nd)));
+                }
+            }
+        }
+
+        None
+    }
+
+    /// Mark the cells of `path` as used.
+    fn occupy(&mut self, path: &[Cell]) {
+        for &c in path {
+            *self.used.entry(c).or_insert(0) += 1;
+        }
+    }
+}
+
+/// Turn a path of cells into the waypoints of a line from `from` to `to`.
+///
+/// Only the corners of the path are kept. The first and the last corner
+/// are aligned with the actual end points of the line.
+fn waypoints(path: &[Cell], from: Vec2, to: Vec2) -> Vec<Vec2> {
+    let mut corners = Vec::new();
+    for w in path.windows(3) {
+        let d1 = (w[1].0 - w[0].0, w[1].1 - w[0].1);
+        let d2 = (w[2].0 - w[1].0, w[2].1 - w[1].1);
+        if d1 != d2 {
+            corners.push(world(w[1]));
+        }
+    }
+
+    let n = path.len();
+    if let Some(first) = corners.first_mut() {
+        if path[0].1 == path[1].1 {
+            first.y = from.y;
+        } else {
+            first.x = from.x;
+        }
+    }
+    if let Some(last) = corners.last_mut() {
+        if path[n - 1].1 == path[n - 2].1 {
+            last.y = to.y;
+        } else {
+            last.x = to.x;
+        }
+    }
+
+    corners
+}
+
+/// Route connection lines around the components of the circuit.
+///
+/// Lines routed on request are marked with [`AutoRoute`] and are routed
+/// again whenever a component has been moved or rotated. While components
+/// are dragged, routing is postponed until they are dropped. Routing on
+/// request can be undone as a single action.
+pub fn auto_route_system(
+    mut commands: Commands,
+    mut ev_route: EventReader<AutoRouteEvent>,
+    mut stack: ResMut<UndoStack>,
+    mut q_line: Query<(
+        Entity,
+        &mut ConnectionLine,
+        Option<&Selected>,
+        Option<&AutoRoute>,
+    )>,
+    q_gate: Query<(&Transform, &Interactable), With<NodeType>>,
+    q_moved: Query<Entity, (With<NodeType>, Changed<Transform>)>,
+    q_dragged: Query<(), (With<NodeType>, With<Drag>)>,
+    q_connector: Query<(&Parent, &Transform), With<Connector>>,
+    mut pending: Local<bool>,
+) {
+    let requested = ev_route.iter().count() > 0;
+    *pending |= q_moved.iter().next().is_some();
+    let moved = *pending && q_dragged.iter().next().is_none();
+    if !requested && !moved {
+        return;
+    }
+    *pending = false;
+
+    let mut lines: Vec<Entity> = Vec::new();
+    if requested {
+        let selected = q_line.iter().any(|(_, _, s, _)| s.is_some());
+        let mut before = Vec::new();
+        for (entity, line, s, auto) in q_line.iter() {
+            if !selected || s.is_some() {
+                commands.entity(entity).insert(AutoRoute);
+                lines.push(entity);
+                before.push((entity, line.via.clone(), auto.is_some()));
+            }
+        }
+
+        if !before.is_empty() {
+            stack.push(Action::Waypoints(before));
+        }
+    }
+    for (entity, _, _, auto) in q_line.iter() {
+        if auto.is_some() && !lines.contains(&entity) {
+            lines.push(entity);
+        }
+    }
+    if lines.is_empty() {
+        return;
+    }
+
+    let position = |connector: Entity| -> Option<Vec2> {
+        let (parent, local) = q_connector.get(connector).ok()?;
+        let (transform, _) = q_gate.get(parent.0).ok()?;
+        Some(transform.mul_vec3(local.translation).truncate())
+    };
+
+    let mut obstacles = Vec::new();
+    for (transform, interactable) in q_gate.iter() {
+        let (center, dimensions) = interactable.bounds();
+        let half = dimensions / 2.;
+        let corners = [
+            center + Vec2::new(-half.x, -half.y),
+            center + Vec2::new(half.x, -half.y),
+            center + Vec2::new(-half.x, half.y),
+            center + Vec2::new(half.x, half.y),
+        ];
+        let corners: Vec<Vec2> = corners
+            .iter()
+            .map(|c| transform.mul_vec3(c.extend(0.)).truncate())
+            .collect();
+        let lo = corners.iter().fold(Vec2::splat(f32::MAX), |a, &b| a.min(b));
+        let hi = corners.iter().fold(Vec2::splat(f32::MIN), |a, &b| a.max(b));
+        obstacles.push((lo, hi));
+    }
+
+    let mut ends = Vec::new();
+    for &entity in lines.iter() {
+        if let Ok((_, line, _, _)) = q_line.get(entity) {
+            if let (Some(from), Some(to)) = (position(line.output.entity), position(line.input.entity)) {
+                ends.push((entity, from, to));
+            }
+        }
+    }
+
+    let points: Vec<Vec2> = ends.iter().flat_map(|&(_, a, b)| [a, b]).collect();
+    let mut grid = match Grid::new(&obstacles, &points) {
+        Some(grid) => grid,
+        None => return,
+    };
+
+    // Short lines first, they have the fewest alternatives.
+    ends.sort_by_key(|&(_, from, to)| {
+        let (a, b) = (cell(from), cell(to));
+        (a.0 - b.0).abs() + (a.1 - b.1).abs()
+    });
+
+    for (entity, from, to) in ends {
+        let via = match grid.route(cell(from), cell(to)) {
+            Some(path) => {
+                grid.occupy(&path);
+                waypoints(&path, from, to)
+            }
+            None => Vec::new(),
+        };
+
+        if let Ok((_, mut line, _, _)) = q_line.get_mut(entity) {
+            if line.via != via {
+                line.via = via;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Check that `path` connects `from` and `to` with steps between
+    /// neighbouring cells that aren't blocked.
+    fn check_path(grid: &Grid, path: &[Cell], from: Cell, to: Cell) {
+        assert_eq!(path.first(), Some(&from));
+        assert_eq!(path.last(), Some(&to));
+        for w in path.windows(2) {
+            assert_eq!((w[0].0 - w[1].0).abs() + (w[0].1 - w[1].1).abs(), 1);
+        }
+        for c in path[1..path.len() - 1].iter() {
+            assert!(!grid.blocked.contains(c), "path crosses blocked cell {:?}", c);
+        }
+    }
+
+    #[test]
+    fn test_route_around_obstacle() {
+        // A component between both end points that covers the
+        // cells -1..=1 horizontally and -2..=2 vertically.
+        let obstacles = [(Vec2::new(-40., -72.), Vec2::new(40., 72.))];
+        let (from, to) = (Vec2::new(-128., 0.), Vec2::new(128., 0.));
+        let grid = Grid::new(&obstacles, &[from, to]).unwrap();
+
+        let path = grid.route(cell(from), cell(to)).unwrap();
+        check_path(&grid, &path, cell(from), cell(to));
+        // Up (or down) by three cells, eight cells to the right and back.
+        assert_eq!(path.len(), 15);
+
+        let via = waypoints(&path, from, to);
+        assert_eq!(via.len(), 2);
+        assert_eq!(via[0].x, from.x);
+        assert_eq!(via[1].x, to.x);
+        assert_eq!(via[0].y, via[1].y);
+    }
+
+    #[test]
+    fn test_route_unreachable() {
+        // The target is enclosed by blocked cells.
+        let mut blocked = HashSet::new();
+        for x in 1..=3 {
+            for y in 1..=3 {
+                if (x, y) != (2, 2) {
+                    blocked.insert((x, y));
+                }
+            }
+        }
+        let grid = Grid {
+            min: (-5, -5),
+            max: (5, 5),
+            blocked,
+            used: HashMap::new(),
+        };
+
+        assert_eq!(grid.route((-3, -3), (2, 2)), None);
+        // Cells outside of the grid can't be reached either.
+        assert_eq!(grid.route((-3, -3), (6, 0)), None);
+    }
+}
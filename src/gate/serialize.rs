@@ -1,22 +1,36 @@
 use crate::{
     gate::{
-        core::{Name, State, *},
+        clipboard::{center, paste},
+        core::{State, *},
         file_browser::*,
-        graphics::{clk::*, light_bulb::*, toggle_switch::*, segment_display::*,},
+        migrate::{self, LoadError, LoadReport},
+        notify::Log,
+        undo::{insert, snapshot, Action, LineQuery, NodeQuery, ReconnectGates, UndoStack},
+        graphics::{clk::*, dot_matrix::*, pattern_generator::*},
     },
     FontAssets,
 };
 use bevy::prelude::*;
 use chrono::prelude::*;
+use nodus::world2d::interaction2d::Selected;
 use ron::ser::{to_string_pretty, PrettyConfig};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fs::{self};
+use std::ops::{Deref, DerefMut};
+use std::sync::atomic::{AtomicU64, Ordering};
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
+/// Version of the save format, see [`crate::gate::migrate`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize)]
 pub struct Version {
-    major: u8,
-    minor: u8,
+    pub major: u8,
+    pub minor: u8,
+}
+
+impl std::fmt::Display for Version {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}.{}", self.major, self.minor)
+    }
 }
 
 #[derive(Debug, Clone, Component, Deserialize, Serialize)]
@@ -34,122 +48,278 @@ pub enum NodeType {
     Clock,
     LightBulb,
     SevenSegmentDisplay,
+    PushButton,
+    DipSwitch,
+    HexKeypad,
+    LedBar,
+    DotMatrix,
+    SevenSegmentDirect,
+    HexDisplay,
+    Terminal,
+    AsciiKeyboard,
+    PatternGenerator,
+    NetLabel,
 }
 
 #[derive(Debug, Clone, Component, Deserialize, Serialize)]
 pub enum NodeState {
     ToggleSwitch(State),
-    Clock(f32, f32, State),
+    /// Half period in seconds, elapsed time, output and configuration.
+    /// The configuration is missing in older save files, where the
+    /// elapsed time is the time since the last toggle instead (see
+    /// [`Clk::from_legacy`](crate::gate::graphics::clk::Clk::from_legacy)).
+    Clock(f32, f32, State, #[serde(default)] ClockConfig),
     LightBulb(State),
+    DipSwitch(Vec<State>),
+    HexKeypad(u8),
+    DotMatrix(usize, usize, MatrixAddressing, Vec<u32>),
+    Terminal(String),
+    AsciiKeyboard(String),
+    PatternGenerator(PatternConfig),
+    NetLabel(String),
+}
+
+/// Persistent identifier of a logic component.
+///
+/// Components keep their id when they are saved and loaded again or
+/// respawned by undo, i.e. save files only change where the circuit
+/// changed. Components created by the user, pasted or imported get
+/// a new id.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Component, Deserialize, Serialize,
+)]
+#[serde(transparent)]
+pub struct ComponentId(pub u64);
+
+/// The next free component id.
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+
+impl ComponentId {
+    /// The largest id allowed in files, which leaves enough
+    /// ids for new components after it has been reserved.
+    pub const MAX: ComponentId = ComponentId(u64::MAX / 2);
+
+    /// Get a new, unused id.
+    pub fn next() -> Self {
+        Self(NEXT_ID.fetch_add(1, Ordering::Relaxed))
+    }
+
+    /// Mark the id as used, e.g. after it has been read from a file.
+    pub fn reserve(self) {
+        NEXT_ID.fetch_max(self.0.saturating_add(1), Ordering::Relaxed);
+    }
+}
+
+impl std::fmt::Display for ComponentId {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// The inputs each output of a captured component is connected to,
+/// i.e. the [`Targets`] of the component with the entities replaced
+/// by [`ComponentId`]s.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct TargetIds(pub Vec<TargetIdMap>);
+
+/// The inputs of other components an output is connected to.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct TargetIdMap(pub BTreeMap<ComponentId, TIndex>);
+
+impl Deref for TargetIds {
+    type Target = Vec<TargetIdMap>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl DerefMut for TargetIds {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl Deref for TargetIdMap {
+    type Target = BTreeMap<ComponentId, TIndex>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl DerefMut for TargetIdMap {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+/// Give the given components new ids, e.g. before they are inserted
+/// next to the components they have been copied from.
+///
+/// Connections and waypoints to components outside of the given
+/// ones are dropped.
+pub fn renumber(components: &mut [NodusComponent]) {
+    let ids: HashMap<ComponentId, ComponentId> = components
+        .iter()
+        .map(|nc| (nc.id, ComponentId::next()))
+        .collect();
+
+    for nc in components.iter_mut() {
+        nc.id = ids[&nc.id];
+        if let Some(targets) = nc.targets.as_mut() {
+            for map in targets.iter_mut() {
+                map.0 = std::mem::take(&mut map.0)
+                    .into_iter()
+                    .filter_map(|(target, indices)| Some((*ids.get(&target)?, indices)))
+                    .collect();
+            }
+        }
+        nc.wires.retain(|w| ids.contains_key(&w.target));
+        for wire in nc.wires.iter_mut() {
+            wire.target = ids[&wire.target];
+        }
+    }
+}
+
+/// Give each logic component spawned without an id a new one.
+pub fn assign_component_id_system(
+    mut commands: Commands,
+    q_node: Query<Entity, (With<NodeType>, Without<ComponentId>)>,
+) {
+    for entity in q_node.iter() {
+        commands.entity(entity).insert(ComponentId::next());
+    }
+}
+
+/// Placeholder for the entity of components that haven't been
+/// captured from the world, e.g. read from a file.
+fn no_entity() -> Entity {
+    Entity::from_raw(u32::MAX)
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct NodusComponent {
-    pub id: Entity,
+    pub id: ComponentId,
+    /// The entity the component has been captured from.
+    #[serde(skip, default = "no_entity")]
+    pub entity: Entity,
     pub name: String,
     pub inputs: Option<usize>,
     pub outputs: Option<usize>,
-    pub targets: Option<Targets>,
+    pub targets: Option<TargetIds>,
     pub position: Vec2,
     pub rotation: Option<Quat>,
     pub ntype: NodeType,
     pub state: Option<NodeState>,
+    #[serde(default)]
+    pub key: Option<String>,
+    /// Waypoints of the connections starting at this component.
+    #[serde(default)]
+    pub wires: Vec<Wire>,
+}
+
+/// The waypoints of a connection from the output `output` of a
+/// component to the input `input` of the component `target`.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Wire {
+    pub output: usize,
+    pub target: ComponentId,
+    pub input: usize,
+    pub via: Vec<Vec2>,
+    /// The connection is routed automatically. Missing in files
+    /// saved before automatic routing was added.
+    #[serde(default)]
+    pub auto: bool,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct NodusSave {
-    time: DateTime<chrono::Local>,
-    application: String,
-    version: Version,
-    entities: Vec<NodusComponent>,
+    pub time: DateTime<chrono::Local>,
+    pub application: String,
+    pub version: Version,
+    pub entities: Vec<NodusComponent>,
 }
 
 pub struct SaveEvent(pub String);
 pub struct LoadEvent(pub String);
+/// Insert the components of the file at the given path into the
+/// current circuit.
+pub struct ImportEvent(pub String);
+
+/// The position on the canvas imported components are inserted at.
+///
+/// The mouse is on the menu when File → Import is chosen, i.e. this
+/// is the last position of the mouse on the canvas before, see
+/// [`import_position_system`](crate::gate::ui::import_position_system).
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct ImportPosition(pub Vec2);
+
+/// Capture all components currently in the world.
+pub fn to_save(
+    q_node: &NodeQuery,
+    children: &Query<&Children>,
+    q_connectors: &Query<&Connections>,
+    q_line: &LineQuery,
+    q_parent: &Query<&Parent>,
+) -> NodusSave {
+    let mut save: Vec<NodusComponent> = q_node
+        .iter()
+        .filter_map(|(e, ..)| snapshot(e, q_node, children, q_connectors, q_line, q_parent))
+        .collect();
+
+    // Keep the order stable, so files only change where the circuit did.
+    save.sort_by_key(|nc| nc.id);
+
+    NodusSave {
+        time: chrono::Local::now(),
+        application: String::from("Nodus - A logic gate simulator"),
+        version: Version::CURRENT,
+        entities: save,
+    }
+}
+
+/// Serialize the given save as pretty RON text.
+pub fn save_to_string(save: &NodusSave) -> Result<String, ron::Error> {
+    let pretty = PrettyConfig::new()
+        .depth_limit(5)
+        .separate_tuple_members(true)
+        .enumerate_arrays(true);
+    to_string_pretty(save, pretty)
+}
 
 pub fn save_event_system(
-    q_node: Query<(
-        Entity,
-        &Name,
-        Option<&Inputs>,
-        Option<&Outputs>,
-        Option<&Targets>,
-        Option<&Clk>,
-        &Transform,
-        &NodeType,
-    )>,
+    q_node: NodeQuery,
+    children: Query<&Children>,
+    q_connectors: Query<&Connections>,
+    q_line: LineQuery,
+    q_parent: Query<&Parent>,
     mut ev_save: EventReader<SaveEvent>,
     mut curr_open: ResMut<CurrentlyOpen>,
+    mut stack: ResMut<UndoStack>,
+    mut log: ResMut<Log>,
 ) {
     for ev in ev_save.iter() {
-        let mut save = Vec::new();
-
-        for (e, n, ip, op, t, clk, tr, nt) in q_node.iter() {
-            let i = if let Some(i) = ip {
-                Some(i.len())
-            } else {
-                None
-            };
-            let o = if let Some(o) = op {
-                Some(o.len())
-            } else {
-                None
-            };
-            let t = if let Some(t) = t {
-                Some(t.clone())
-            } else {
-                None
-            };
-
-            let state = match &nt {
-                NodeType::ToggleSwitch => Some(NodeState::ToggleSwitch(op.unwrap()[0])),
-                NodeType::Clock => {
-                    let clk = clk.unwrap();
-                    Some(NodeState::Clock(clk.0, clk.1, op.unwrap()[0]))
-                }
-                NodeType::LightBulb => Some(NodeState::LightBulb(ip.unwrap()[0])),
-                _ => None,
-            };
-
-            let nc = NodusComponent {
-                id: e,
-                name: n.0.to_string(),
-                inputs: i,
-                outputs: o,
-                targets: t,
-                position: Vec2::new(tr.translation.x, tr.translation.y),
-                rotation: Some(tr.rotation),
-                ntype: nt.clone(),
-                state: state,
-            };
-            save.push(nc);
-        }
-
-        let nsave = NodusSave {
-            time: chrono::Local::now(),
-            application: String::from("Nodus - A logic gate simulator"),
-            version: Version { major: 0, minor: 1 },
-            entities: save,
-        };
+        let nsave = to_save(&q_node, &children, &q_connectors, &q_line, &q_parent);
 
-        let pretty = PrettyConfig::new()
-            .depth_limit(5)
-            .separate_tuple_members(true)
-            .enumerate_arrays(true);
-        //eprintln!("RON: {}", to_string_pretty(&nsave, pretty).unwrap());
-        eprintln!("{}", &ev.0);
-        if let Ok(_res) = fs::write(&ev.0, &to_string_pretty(&nsave, pretty).unwrap()) {
-            curr_open.path = Some(ev.0.clone());
-            eprintln!("success");
-        } else {
-            eprintln!("failure");
+        let res = save_to_string(&nsave)
+            .map_err(|e| e.to_string())
+            .and_then(|text| fs::write(&ev.0, text).map_err(|e| e.to_string()));
+        match res {
+            Ok(()) => {
+                curr_open.path = Some(ev.0.clone());
+                curr_open.dirty = false;
+                stack.mark_saved();
+                log.info(format!("saved {}", ev.0));
+            }
+            Err(e) => log.error(format!("unable to save {}: {}", ev.0, e)),
         }
     }
 }
 
 #[derive(Component)]
 pub struct LoadMapper {
-    map: HashMap<Entity, Entity>,
+    map: HashMap<ComponentId, Entity>,
     save: NodusSave,
 }
 
@@ -187,11 +357,24 @@ pub fn link_gates_system(
                                 if let Ok((id, conn)) = q_conn.get(child) {
                                     for &j in tidx.iter() {
                                         if conn.index == j && conn.ctype == ConnectorType::In {
+                                            let (via, auto) = e
+                                                .wires
+                                                .iter()
+                                                .find(|w| {
+                                                    w.output == i
+                                                        && w.target == *gate
+                                                        && w.input == j
+                                                })
+                                                .map(|w| (w.via.clone(), w.auto))
+                                                .unwrap_or_default();
+
                                             cev.send(ConnectEvent {
                                                 output: out_id.unwrap(),
                                                 output_index: i,
                                                 input: id,
                                                 input_index: j,
+                                                via,
+                                                auto,
                                                 signal_success: false,
                                             });
                                             break;
@@ -209,153 +392,120 @@ pub fn link_gates_system(
     }
 }
 
+/// Spawn the components of the given save.
+///
+/// The components are connected by the [`link_gates_system`]
+/// once they have been inserted into the world.
+pub fn spawn_save(commands: &mut Commands, font: Handle<Font>, save: NodusSave) {
+    let mut id_map: HashMap<ComponentId, Entity> = HashMap::new();
+
+    for e in &save.entities {
+        if let Some(entities) = insert(commands, font.clone(), vec![e.clone()]) {
+            e.id.reserve();
+            id_map.insert(e.id, entities[0]);
+        }
+    }
+
+    // The different logical components must be connected to each other. This
+    // is done in another system that always runs before the load_event_system
+    // to give the ecs enough time to insert the spawned entities above into
+    // the world.
+    commands.spawn().insert(LoadMapper {
+        map: id_map,
+        save: save,
+    });
+}
+
 pub fn load_event_system(
     mut commands: Commands,
     mut ev_load: EventReader<LoadEvent>,
     font: Res<FontAssets>,
     mut curr_open: ResMut<CurrentlyOpen>,
+    mut stack: ResMut<UndoStack>,
+    mut report: ResMut<LoadReport>,
+    mut log: ResMut<Log>,
     q_all: Query<Entity, Or<(With<NodeType>, With<ConnectionLine>)>>,
 ) {
     for ev in ev_load.iter() {
-        if let Ok(loaded_save) = fs::read_to_string(&ev.0) {
-            // Remove all entities currently in the world before inserting
-            // the entities from the file.
-            for e in q_all.iter() {
-                commands.entity(e).despawn_recursive();
-            }
-
-            let save: Result<NodusSave, _> = ron::from_str(&loaded_save);
-            let mut id_map: HashMap<Entity, Entity> = HashMap::new();
-
-            if let Ok(save) = save {
-                for e in &save.entities {
-                    let id = match e.ntype {
-                        NodeType::And => {
-                            Some(Gate::and_gate_bs_(
-                                &mut commands,
-                                e.position,
-                                e.rotation.unwrap_or(Quat::IDENTITY),
-                                e.inputs.unwrap(),
-                                e.outputs.unwrap(),
-                                font.main.clone(),
-                            ))
-                        }
-                        NodeType::Nand => {
-                            Some(Gate::nand_gate_bs_(
-                                &mut commands,
-                                e.position,
-                                e.rotation.unwrap_or(Quat::IDENTITY),
-                                e.inputs.unwrap(),
-                                e.outputs.unwrap(),
-                                font.main.clone(),
-                            ))
-                        }
-                        NodeType::Or => {
-                            Some(Gate::or_gate_bs_(
-                                &mut commands,
-                                e.position,
-                                e.rotation.unwrap_or(Quat::IDENTITY),
-                                e.inputs.unwrap(),
-                                e.outputs.unwrap(),
-                                font.main.clone(),
-                            ))
-                        }
-                        NodeType::Nor => {
-                            Some(Gate::nor_gate_bs_(
-                                &mut commands,
-                                e.position,
-                                e.rotation.unwrap_or(Quat::IDENTITY),
-                                e.inputs.unwrap(),
-                                e.outputs.unwrap(),
-                                font.main.clone(),
-                            ))
-                        }
-                        NodeType::Xor => {
-                            Some(Gate::xor_gate_bs_(
-                                &mut commands,
-                                e.position,
-                                e.rotation.unwrap_or(Quat::IDENTITY),
-                                e.inputs.unwrap(),
-                                e.outputs.unwrap(),
-                                font.main.clone(),
-                            ))
-                        }
-                        NodeType::Xnor => { None }
-                        NodeType::Not => {
-                            Some(Gate::not_gate_bs_(
-                                &mut commands,
-                                e.position,
-                                e.rotation.unwrap_or(Quat::IDENTITY),
-                                e.inputs.unwrap(),
-                                e.outputs.unwrap(),
-                                font.main.clone(),
-                            ))
-                        }
-                        NodeType::HighConst => {
-                            Some(Gate::high_const(
-                                    &mut commands, 
-                                    e.position, 
-                                    e.rotation.unwrap_or(Quat::IDENTITY),
-                                    font.main.clone()
-                            ))
-                        }
-                        NodeType::LowConst => {
-                            Some(Gate::low_const(
-                                    &mut commands, 
-                                    e.position, 
-                                    e.rotation.unwrap_or(Quat::IDENTITY),
-                                    font.main.clone()
-                            ))
-                        }
-                        NodeType::ToggleSwitch => {
-                            if let Some(NodeState::ToggleSwitch(state)) = e.state {
-                                Some(ToggleSwitch::new(&mut commands, e.position, e.rotation.unwrap_or(Quat::IDENTITY), state))
-                            } else { None }
-                        }
-                        NodeType::Clock => {
-                            if let Some(NodeState::Clock(x1, x2, x3)) = e.state {
-                                Some(Clk::spawn(&mut commands, e.position, e.rotation.unwrap_or(Quat::IDENTITY), x1, x2, x3))
-                            } else { None }
-                        }
-                        NodeType::LightBulb => {
-                            if let Some(NodeState::LightBulb(state)) = e.state {
-                                Some(LightBulb::spawn(&mut commands, e.position, e.rotation.unwrap_or(Quat::IDENTITY), state))
-                            } else { None }
-                        }
-                        NodeType::SevenSegmentDisplay => {
-                            Some(
-                                SevenSegmentDisplay::spawn(
-                                    &mut commands,
-                                    e.position,
-                                    e.rotation.unwrap_or(Quat::IDENTITY),
-                                )
-                            )
-                        }
-                    };
+        let save = fs::read_to_string(&ev.0)
+            .map_err(LoadError::Io)
+            .and_then(|text| migrate::parse(&text));
 
-                    if let Some(id) = id {
-                        id_map.insert(e.id, id);
-                    }
+        match save {
+            Ok(save) => {
+                // Remove all entities currently in the world before inserting
+                // the entities from the file.
+                for e in q_all.iter() {
+                    commands.entity(e).despawn_recursive();
                 }
 
-                // The different logical components must be connected to each other. This
-                // is done in another system that always runs before this one to give the
-                // ecs enough time to insert the spawned entities above into the world.
-                commands.spawn().insert(LoadMapper {
-                    map: id_map,
-                    save: save,
-                });
+                spawn_save(&mut commands, font.main.clone(), save);
 
                 // Remember the path of the file. This allows to save the
                 // file without specifying the path all the time.
                 curr_open.path = Some(ev.0.clone());
+                curr_open.dirty = false;
+                stack.reset();
 
-                eprintln!("file loaded and parsed");
-            } else {
-                eprintln!("unable to parse file");
+                log.info(format!("opened {}", ev.0));
+            }
+            Err(e) => {
+                log.error(format!("unable to open {}: {}", ev.0, e));
+                report.0 = Some((ev.0.clone(), e));
             }
-        } else {
-            eprintln!("unable to load file");
+        }
+    }
+}
+
+/// Insert the components of another file at the [`ImportPosition`].
+///
+/// The components get new ids, i.e. a file can be imported more than
+/// once. They are selected, so they can be moved, and the import can
+/// be undone as a single action.
+pub fn import_event_system(
+    mut commands: Commands,
+    mut ev_import: EventReader<ImportEvent>,
+    font: Res<FontAssets>,
+    position: Res<ImportPosition>,
+    mut stack: ResMut<UndoStack>,
+    mut report: ResMut<LoadReport>,
+    mut log: ResMut<Log>,
+    q_selected: Query<Entity, With<Selected>>,
+    mut ev_conn: EventWriter<ReconnectGates>,
+) {
+    for ev in ev_import.iter() {
+        let save = fs::read_to_string(&ev.0)
+            .map_err(LoadError::Io)
+            .and_then(|text| migrate::parse(&text));
+
+        let mut components = match save {
+            Ok(save) => save.entities,
+            Err(e) => {
+                log.error(format!("unable to import {}: {}", ev.0, e));
+                report.0 = Some((ev.0.clone(), e));
+                continue;
+            }
+        };
+
+        center(&mut components);
+        let imported = paste(
+            &mut commands,
+            font.main.clone(),
+            &components,
+            position.0,
+            &mut ev_conn,
+        );
+
+        if !imported.is_empty() {
+            log.info(format!("imported {} components from {}", imported.len(), ev.0));
+            for entity in q_selected.iter() {
+                commands.entity(entity).remove::<Selected>();
+            }
+            for &entity in imported.iter() {
+                commands.entity(entity).insert(Selected);
+            }
+
+            stack.push(Action::Remove(imported));
         }
     }
 }
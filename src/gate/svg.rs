@@ -0,0 +1,390 @@
+use crate::gate::{
+    core::{ConnectionLine, NodeType},
+    graphics::highlight::Highlighter,
+    notify::Log,
+};
+use bevy::prelude::*;
+use bevy_prototype_lyon::prelude::*;
+use lyon_tessellation::path::PathEvent;
+use nodus::world2d::interaction2d::Selected;
+use std::collections::HashSet;
+use std::fmt::Write;
+use std::fs;
+
+/// Empty space around the drawing.
+const MARGIN: f32 = 20.;
+
+/// Request to write the drawing of the circuit as SVG to the given path.
+pub struct ExportEvent(pub String);
+
+/// What the SVG export includes, chosen in the file menu.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct SvgOptions {
+    /// Only export the selected components and the connections
+    /// between them.
+    pub selection: bool,
+    /// Color connection lines by their current signal, otherwise
+    /// they are drawn black.
+    pub signals: bool,
+}
+
+/// Format a coordinate with at most two decimal places.
+fn num(v: f32) -> String {
+    let v = (v * 100.).round() / 100.;
+    // Avoid `-0`.
+    format!("{}", if v == 0. { 0. } else { v })
+}
+
+/// Get the given color as hex code and its opacity.
+fn color(c: Color) -> (String, f32) {
+    let [r, g, b, a] = c.as_rgba_f32();
+    let byte = |v: f32| (v.clamp(0., 1.) * 255.).round() as u8;
+    (format!("#{:02x}{:02x}{:02x}", byte(r), byte(g), byte(b)), a)
+}
+
+/// Get a paint attribute, e.g. `fill="#ff0000"`, followed by its
+/// opacity if the color isn't opaque.
+fn paint(attr: &str, c: Color) -> String {
+    let (hex, alpha) = color(c);
+    if alpha < 1. {
+        format!("{}=\"{}\" {}-opacity=\"{}\"", attr, hex, attr, num(alpha))
+    } else {
+        format!("{}=\"{}\"", attr, hex)
+    }
+}
+
+/// Escape the characters with a special meaning in XML.
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// The elements of a SVG drawing and their bounding box.
+///
+/// World coordinates are mirrored at the x axis, i.e. the y axis
+/// of the drawing points downwards.
+#[derive(Debug, Default)]
+struct Svg {
+    /// The elements with their depth, elements further back are
+    /// drawn first.
+    elements: Vec<(f32, String)>,
+    bounds: Option<(Vec2, Vec2)>,
+}
+
+impl Svg {
+    /// Extend the bounding box to include a square of the given
+    /// size around `p`.
+    fn include(&mut self, p: Vec2, size: f32) {
+        let (min, max) = (p - Vec2::splat(size), p + Vec2::splat(size));
+        self.bounds = Some(match self.bounds {
+            Some((lo, hi)) => (lo.min(min), hi.max(max)),
+            None => (min, max),
+        });
+    }
+
+    /// Transform a point of a shape into the drawing.
+    fn point(&mut self, transform: &GlobalTransform, p: Vec2, size: f32) -> Vec2 {
+        let p = transform.mul_vec3(p.extend(0.));
+        let p = Vec2::new(p.x, -p.y);
+        self.include(p, size);
+        p
+    }
+
+    /// Add a shape, overriding the color of its outline if
+    /// `stroke` is given.
+    fn shape(
+        &mut self,
+        path: &Path,
+        mode: &DrawMode,
+        transform: &GlobalTransform,
+        stroke: Option<Color>,
+    ) {
+        let (fill, outline) = match mode {
+            DrawMode::Fill(fill) => (Some(fill.color), None),
+            DrawMode::Stroke(s) => (None, Some((s.color, s.options.line_width))),
+            DrawMode::Outlined {
+                fill_mode,
+                outline_mode,
+            } => (
+                Some(fill_mode.color),
+                Some((outline_mode.color, outline_mode.options.line_width)),
+            ),
+        };
+        let outline = outline.map(|(c, w)| (stroke.unwrap_or(c), w * transform.scale.x));
+        let width = outline.map_or(0., |(_, w)| w / 2.);
+
+        let mut d = String::new();
+        for event in path.0.iter() {
+            let _ = match event {
+                PathEvent::Begin { at } => {
+                    let p = self.point(transform, Vec2::new(at.x, at.y), width);
+                    write!(d, "M{} {} ", num(p.x), num(p.y))
+                }
+                PathEvent::Line { to, .. } => {
+                    let p = self.point(transform, Vec2::new(to.x, to.y), width);
+                    write!(d, "L{} {} ", num(p.x), num(p.y))
+                }
+                PathEvent::Quadratic { ctrl, to, .. } => {
+                    let c = self.point(transform, Vec2::new(ctrl.x, ctrl.y), width);
+                    let p = self.point(transform, Vec2::new(to.x, to.y), width);
+                    write!(d, "Q{} {} {} {} ", num(c.x), num(c.y), num(p.x), num(p.y))
+                }
+                PathEvent::Cubic {
+                    ctrl1, ctrl2, to, ..
+                } => {
+                    let c1 = self.point(transform, Vec2::new(ctrl1.x, ctrl1.y), width);
+                    let c2 = self.point(transform, Vec2::new(ctrl2.x, ctrl2.y), width);
+                    let p = self.point(transform, Vec2::new(to.x, to.y), width);
+                    write!(
+                        d,
+                        "C{} {} {} {} {} {} ",
+                        num(c1.x),
+                        num(c1.y),
+                        num(c2.x),
+                        num(c2.y),
+                        num(p.x),
+                        num(p.y)
+                    )
+                }
+                PathEvent::End { close: true, .. } => write!(d, "Z "),
+                PathEvent::End { .. } => Ok(()),
+            };
+        }
+        if d.is_empty() {
+            return;
+        }
+
+        let fill = fill.map_or_else(|| "fill=\"none\"".to_string(), |c| paint("fill", c));
+        let outline = outline.map_or_else(String::new, |(c, w)| {
+            format!(
+                " {} stroke-width=\"{}\" stroke-linejoin=\"round\"",
+                paint("stroke", c),
+                num(w)
+            )
+        });
+        self.elements.push((
+            transform.translation.z,
+            format!(
+                "<path d=\"{}\" {} fill-rule=\"evenodd\"{}/>",
+                d.trim_end(),
+                fill,
+                outline
+            ),
+        ));
+    }
+
+    /// Add a label, each line of it as separate line of text.
+    fn text(&mut self, text: &Text, transform: &GlobalTransform) {
+        let anchor = match text.alignment.horizontal {
+            HorizontalAlign::Left => "start",
+            HorizontalAlign::Center => "middle",
+            HorizontalAlign::Right => "end",
+        };
+        let baseline = match text.alignment.vertical {
+            VerticalAlign::Top => "hanging",
+            VerticalAlign::Center => "central",
+            VerticalAlign::Bottom => "auto",
+        };
+        let p = transform.translation;
+        let p = Vec2::new(p.x, -p.y);
+        // The drawing is mirrored, i.e. rotations are reversed.
+        let dir = transform.rotation * Vec3::X;
+        let angle = -dir.y.atan2(dir.x).to_degrees();
+
+        for section in text.sections.iter() {
+            let size = section.style.font_size * transform.scale.x;
+            let lines: Vec<&str> = section.value.lines().collect();
+            let longest = lines.iter().map(|l| l.chars().count()).max().unwrap_or(0);
+            if longest == 0 {
+                continue;
+            }
+            // Approximate the extent of the monospaced text.
+            self.include(p, size * longest as f32 * 0.6 + size * lines.len() as f32);
+
+            let mut element = format!(
+                "<text x=\"{}\" y=\"{}\" {} font-family=\"Hack, monospace\" font-weight=\"bold\" \
+                 font-size=\"{}\" text-anchor=\"{}\" dominant-baseline=\"{}\" \
+                 xml:space=\"preserve\"",
+                num(p.x),
+                num(p.y),
+                paint("fill", section.style.color),
+                num(size),
+                anchor,
+                baseline
+            );
+            if angle.abs() > 0.01 {
+                let _ = write!(
+                    element,
+                    " transform=\"rotate({} {} {})\"",
+                    num(angle),
+                    num(p.x),
+                    num(p.y)
+                );
+            }
+            element.push('>');
+            for (i, line) in lines.iter().enumerate() {
+                let _ = write!(
+                    element,
+                    "<tspan x=\"{}\" dy=\"{}\">{}</tspan>",
+                    num(p.x),
+                    if i == 0 { "0" } else { "1.2em" },
+                    escape(line)
+                );
+            }
+            element.push_str("</text>");
+            self.elements.push((transform.translation.z, element));
+        }
+    }
+
+    /// Get the SVG document.
+    fn finish(mut self) -> String {
+        let (min, max) = self.bounds.unwrap_or_default();
+        let (min, max) = (min - Vec2::splat(MARGIN), max + Vec2::splat(MARGIN));
+        let size = max - min;
+
+        // Keep the order of elements at the same depth.
+        self.elements
+            .sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(std::cmp::Ordering::Equal));
+
+        let mut svg = format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+             <svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" \
+             viewBox=\"{} {} {} {}\">\n",
+            num(size.x),
+            num(size.y),
+            num(min.x),
+            num(min.y),
+            num(size.x),
+            num(size.y)
+        );
+        for (_, element) in self.elements.iter() {
+            svg.push_str("  ");
+            svg.push_str(element);
+            svg.push('\n');
+        }
+        svg.push_str("</svg>\n");
+        svg
+    }
+}
+
+/// Add the shapes and labels of the given entity and its descendants.
+fn add_entity(
+    svg: &mut Svg,
+    entity: Entity,
+    q_children: &Query<&Children>,
+    q_shape: &Query<(&Path, &DrawMode, &GlobalTransform, Option<&Highlighter>)>,
+    q_text: &Query<(&Text, &GlobalTransform)>,
+) {
+    match q_shape.get(entity) {
+        // Highlights of selected components aren't part of the drawing.
+        Ok((.., Some(_))) => return,
+        Ok((path, mode, transform, None)) => svg.shape(path, mode, transform, None),
+        Err(_) => {}
+    }
+    if let Ok((text, transform)) = q_text.get(entity) {
+        svg.text(text, transform);
+    }
+
+    if let Ok(children) = q_children.get(entity) {
+        for &child in children.iter() {
+            add_entity(svg, child, q_children, q_shape, q_text);
+        }
+    }
+}
+
+/// Write the drawing of the circuit, or of the selected components,
+/// to a SVG file.
+///
+/// The drawing is generated from the shapes and labels of the
+/// components and the bezier curves of the connection lines, i.e. it
+/// can be scaled without loss, e.g. for slides.
+pub fn export_svg_system(
+    mut ev_export: EventReader<ExportEvent>,
+    options: Res<SvgOptions>,
+    mut log: ResMut<Log>,
+    q_node: Query<(Entity, Option<&Selected>), With<NodeType>>,
+    q_line: Query<(&ConnectionLine, &Path, &DrawMode, &GlobalTransform)>,
+    q_parent: Query<&Parent>,
+    q_children: Query<&Children>,
+    q_shape: Query<(&Path, &DrawMode, &GlobalTransform, Option<&Highlighter>)>,
+    q_text: Query<(&Text, &GlobalTransform)>,
+) {
+    for ev in ev_export.iter() {
+        let components: HashSet<Entity> = q_node
+            .iter()
+            .filter(|(_, selected)| !options.selection || selected.is_some())
+            .map(|(e, _)| e)
+            .collect();
+        if components.is_empty() {
+            log.warn("there are no components to export");
+            continue;
+        }
+
+        let mut svg = Svg::default();
+        for &entity in components.iter() {
+            add_entity(&mut svg, entity, &q_children, &q_shape, &q_text);
+        }
+
+        // Connection lines are drawn if both connected components are.
+        let exported = |connector: Entity| {
+            q_parent
+                .get(connector)
+                .map_or(false, |parent| components.contains(&parent.0))
+        };
+        for (line, path, mode, transform) in q_line.iter() {
+            if exported(line.output.entity) && exported(line.input.entity) {
+                let stroke = if options.signals {
+                    None
+                } else {
+                    Some(Color::BLACK)
+                };
+                svg.shape(path, mode, transform, stroke);
+            }
+        }
+
+        match fs::write(&ev.0, svg.finish()) {
+            Ok(()) => log.info(format!("exported {}", ev.0)),
+            Err(e) => log.error(format!("unable to export {}: {}", ev.0, e)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format() {
+        assert_eq!(num(1.234), "1.23");
+        assert_eq!(num(-0.001), "0");
+        assert_eq!(num(12.), "12");
+        assert_eq!(paint("fill", Color::RED), "fill=\"#ff0000\"");
+        assert_eq!(
+            paint("stroke", Color::rgba(0., 0., 1., 0.5)),
+            "stroke=\"#0000ff\" stroke-opacity=\"0.5\""
+        );
+        assert_eq!(escape("A & <B>"), "A &amp; &lt;B&gt;");
+    }
+
+    #[test]
+    fn test_shape() {
+        let mut path = PathBuilder::new();
+        path.move_to(Vec2::new(0., 0.));
+        path.cubic_bezier_to(Vec2::new(10., 0.), Vec2::new(20., 10.), Vec2::new(30., 10.));
+        let mut svg = Svg::default();
+        svg.shape(
+            &path.build(),
+            &DrawMode::Stroke(StrokeMode::new(Color::BLUE, 8.0)),
+            &GlobalTransform::from_xyz(5., 0., 1.),
+            Some(Color::BLACK),
+        );
+
+        let text = svg.finish();
+        assert!(text.contains("d=\"M5 0 C15 0 25 -10 35 -10\""), "{}", text);
+        assert!(text.contains("fill=\"none\""));
+        assert!(text.contains("stroke=\"#000000\" stroke-width=\"8\""));
+        assert!(text.contains("viewBox=\"-19 -34 78 58\""), "{}", text);
+    }
+}
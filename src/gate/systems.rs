@@ -1,45 +1,149 @@
 use super::{
-    core::{Name, *},
-    graphics::{clk::*, light_bulb::*, toggle_switch::*, segment_display::*},
+    core::*,
+    file_browser::*,
+    graphics::{
+        ascii_keyboard::*, clk::*, dip_switch::*, dot_matrix::*, hex_keypad::*, led_bar::*,
+        light_bulb::*, pattern_generator::*, push_button::*, segment_display::*, terminal::*,
+        toggle_switch::*,
+    },
+    keys::{Keymap, Shortcut},
     serialize::*,
     undo::*,
+    unsaved::FileAction,
 };
 use bevy::prelude::*;
-use nodus::world2d::interaction2d::{Drag, Selected};
-use nodus::world2d::{InteractionMode, Lock};
+use nodus::world2d::interaction2d::{Drag, Hover, Selected, SnapAnchor};
+use nodus::world2d::{GridSnap, InteractionMode, Lock};
 use crate::FontAssets;
+use std::collections::HashMap;
 
+/// Trigger the actions bound to keyboard shortcuts by the [`Keymap`].
 pub fn shortcut_system(
     mut mode: ResMut<InteractionMode>,
     input_keyboard: Res<Input<KeyCode>>,
     lock: Res<Lock>,
+    keymap: Res<Keymap>,
+    curr_open: Res<CurrentlyOpen>,
+    mut ev_undo: EventWriter<UndoEvent>,
+    mut ev_save: EventWriter<SaveEvent>,
+    mut ev_action: EventWriter<FileAction>,
+    mut fbe: EventWriter<OpenBrowserEvent>,
 ) {
     if lock.0 {
         return;
     }
 
-    if input_keyboard.pressed(KeyCode::P) {
+    let pressed = |shortcut| keymap.just_pressed(shortcut, &input_keyboard);
+
+    if pressed(Shortcut::PanMode) {
         *mode = InteractionMode::Pan;
-    } else if input_keyboard.pressed(KeyCode::S) {
+    } else if pressed(Shortcut::SelectMode) {
         *mode = InteractionMode::Select;
     }
+
+    if pressed(Shortcut::Undo) {
+        ev_undo.send(UndoEvent::Undo);
+    } else if pressed(Shortcut::Redo) {
+        ev_undo.send(UndoEvent::Redo);
+    }
+
+    if pressed(Shortcut::New) {
+        ev_action.send(FileAction::New);
+    } else if pressed(Shortcut::Open) {
+        ev_action.send(FileAction::Open);
+    } else if pressed(Shortcut::Save) {
+        if let Some(path) = &curr_open.path {
+            ev_save.send(SaveEvent(path.clone()));
+        } else {
+            fbe.send(OpenBrowserEvent(BrowserAction::Save));
+        }
+    }
+}
+
+/// Rotate the selected components by 90 degrees.
+///
+/// The rotation can be undone as a single action.
+pub fn rotate_gate_system(
+    input_keyboard: Res<Input<KeyCode>>,
+    lock: Res<Lock>,
+    keymap: Res<Keymap>,
+    mut stack: ResMut<UndoStack>,
+    mut q_gate: Query<
+        (Entity, &mut Transform),
+        (
+            With<Selected>,
+            With<NodeType>,
+        ),
+    >,
+) {
+    if lock.0 {
+        return;
+    }
+
+    let angle = if keymap.just_pressed(Shortcut::RotateLeft, &input_keyboard) {
+        std::f32::consts::PI / 2.0
+    } else if keymap.just_pressed(Shortcut::RotateRight, &input_keyboard) {
+        -std::f32::consts::PI / 2.0
+    } else {
+        return;
+    };
+
+    let mut rotated = Vec::new();
+    for (entity, mut transform) in q_gate.iter_mut() {
+        rotated.push((entity, *transform));
+        transform.rotate(Quat::from_rotation_z(angle));
+    }
+
+    if !rotated.is_empty() {
+        stack.push(Action::Transform(rotated));
+    }
 }
 
 /// Removes the drag state from draggable components.
+///
+/// The transforms at the start of a drag are recorded, i.e. moving
+/// components can be undone as a single action.
 pub fn drag_gate_system(
     mut commands: Commands,
     mb: Res<Input<MouseButton>>,
+    mut stack: ResMut<UndoStack>,
+    mut start: Local<HashMap<Entity, Transform>>,
+    q_grabbed: Query<
+        (Entity, &Transform),
+        (
+            With<NodeType>,
+            Or<(With<Hover>, With<Selected>)>,
+        ),
+    >,
     q_dragged: Query<
-        Entity,
+        (Entity, &Transform),
         (
             With<Drag>,
-            Or<(With<Gate>, With<LightBulb>, With<ToggleSwitch>, With<Clk>, With<SevenSegmentDisplay>)>,
+            With<NodeType>,
         ),
     >,
 ) {
+    if mb.just_pressed(MouseButton::Left) {
+        // The drag starts with this click, i.e. nothing has moved yet.
+        start.clear();
+        start.extend(q_grabbed.iter().map(|(e, t)| (e, *t)));
+    }
+
     if mb.just_released(MouseButton::Left) {
-        for dragged_gate in q_dragged.iter() {
+        let mut moved = Vec::new();
+        for (dragged_gate, transform) in q_dragged.iter() {
             commands.entity(dragged_gate).remove::<Drag>();
+
+            if let Some(&before) = start.get(&dragged_gate) {
+                if before != *transform {
+                    moved.push((dragged_gate, before));
+                }
+            }
+        }
+        start.clear();
+
+        if !moved.is_empty() {
+            stack.push(Action::Transform(moved));
         }
     }
 }
@@ -53,26 +157,19 @@ pub fn delete_gate_system(
         Entity,
         (
             With<Selected>,
-            Or<(With<Gate>, With<LightBulb>, With<ToggleSwitch>, With<Clk>, With<SevenSegmentDisplay>)>,
+            With<NodeType>,
         ),
     >,
     children: Query<&Children>,
     q_connectors: Query<&Connections>,
     mut stack: ResMut<UndoStack>,
-    q_node: Query<(
-        Entity,
-        &Name,
-        Option<&Inputs>,
-        Option<&Outputs>,
-        Option<&Targets>,
-        Option<&Clk>,
-        &Transform,
-        &NodeType,
-    )>,
-    q_line: Query<(Entity, &ConnectionLine)>,
+    q_node: NodeQuery,
+    q_line: LineQuery,
     q_parent: Query<&Parent>,
+    lock: Res<Lock>,
+    keymap: Res<Keymap>,
 ) {
-    if input_keyboard.pressed(KeyCode::Delete) {
+    if !lock.0 && keymap.just_pressed(Shortcut::Delete, &input_keyboard) {
         if let Some(ncs) = crate::gate::undo::remove(
             &mut commands, 
             q_gate.iter().map(|e| e).collect(), 
@@ -83,8 +180,7 @@ pub fn delete_gate_system(
             &q_parent,
             &mut ev_disconnect
         ) {
-            stack.undo.push(Action::Insert(ncs));
-            stack.redo.clear();
+            stack.push(Action::Insert(ncs));
         }
     }
 }
@@ -179,6 +275,119 @@ impl InsertGateEvent {
             position,
         }
     }
+
+    pub fn push(position: Vec2) -> Self {
+        Self {
+            gate_type: NodeType::PushButton,
+            position,
+        }
+    }
+
+    pub fn dip(position: Vec2) -> Self {
+        Self {
+            gate_type: NodeType::DipSwitch,
+            position,
+        }
+    }
+
+    pub fn keypad(position: Vec2) -> Self {
+        Self {
+            gate_type: NodeType::HexKeypad,
+            position,
+        }
+    }
+
+    pub fn ledbar(position: Vec2) -> Self {
+        Self {
+            gate_type: NodeType::LedBar,
+            position,
+        }
+    }
+
+    pub fn matrix(position: Vec2) -> Self {
+        Self {
+            gate_type: NodeType::DotMatrix,
+            position,
+        }
+    }
+
+    pub fn seg_direct(position: Vec2) -> Self {
+        Self {
+            gate_type: NodeType::SevenSegmentDirect,
+            position,
+        }
+    }
+
+    pub fn hex(position: Vec2) -> Self {
+        Self {
+            gate_type: NodeType::HexDisplay,
+            position,
+        }
+    }
+
+    pub fn terminal(position: Vec2) -> Self {
+        Self {
+            gate_type: NodeType::Terminal,
+            position,
+        }
+    }
+
+    pub fn ascii_keyboard(position: Vec2) -> Self {
+        Self {
+            gate_type: NodeType::AsciiKeyboard,
+            position,
+        }
+    }
+
+    pub fn pattern(position: Vec2) -> Self {
+        Self {
+            gate_type: NodeType::PatternGenerator,
+            position,
+        }
+    }
+
+    pub fn label(position: Vec2) -> Self {
+        Self {
+            gate_type: NodeType::NetLabel,
+            position,
+        }
+    }
+}
+
+/// Marker component for inserted components that must be aligned
+/// with the grid as soon as their connectors have been spawned.
+#[derive(Component)]
+pub struct AlignToGrid;
+
+/// Use the first connector of each component as its [`SnapAnchor`],
+/// i.e. components are snapped to the grid such that the end points
+/// of their connection lines lie on grid points.
+///
+/// The first input is preferred, components without inputs use
+/// their first output.
+pub fn snap_anchor_system(
+    mut commands: Commands,
+    snap: Res<GridSnap>,
+    q_node: Query<(Entity, &Children), (With<NodeType>, Changed<Children>)>,
+    q_connector: Query<(&Transform, &Connector)>,
+    mut q_align: Query<&mut Transform, (With<AlignToGrid>, Without<Connector>)>,
+) {
+    for (entity, children) in q_node.iter() {
+        let anchor = children
+            .iter()
+            .filter_map(|&child| q_connector.get(child).ok())
+            .min_by_key(|(_, c)| (c.ctype != ConnectorType::In, c.index))
+            .map_or(Vec2::ZERO, |(t, _)| t.translation.truncate());
+        commands.entity(entity).insert(SnapAnchor(anchor));
+
+        if let Ok(mut transform) = q_align.get_mut(entity) {
+            let anchor = transform.rotation.mul_vec3(anchor.extend(0.)).truncate();
+            let position = snap.snap_anchor(transform.translation.truncate(), anchor);
+            transform.translation.x = position.x;
+            transform.translation.y = position.y;
+            commands.entity(entity).remove::<AlignToGrid>();
+        }
+    }
 }
 
 pub fn insert_gate_system(
@@ -186,55 +395,108 @@ pub fn insert_gate_system(
     mut ev_insert: EventReader<InsertGateEvent>,
     mut stack: ResMut<UndoStack>,
     font: Res<FontAssets>,
+    snap: Res<GridSnap>,
+    keyboard: Res<Input<KeyCode>>,
 ) {
     use crate::gate::core::State;
     
+    let align = snap.active(&keyboard);
     for ev in ev_insert.iter() {
+        let position = if align {
+            snap.snap(ev.position)
+        } else {
+            ev.position
+        };
+
         let entity = match ev.gate_type {
             NodeType::And => {
-                Some(Gate::and_gate_bs(&mut commands, ev.position, Quat::IDENTITY, font.main.clone()))
+                Some(Gate::and_gate_bs(&mut commands, position, Quat::IDENTITY, font.main.clone()))
             },
             NodeType::Nand => {
-                Some(Gate::nand_gate_bs(&mut commands, ev.position, Quat::IDENTITY, font.main.clone()))
+                Some(Gate::nand_gate_bs(&mut commands, position, Quat::IDENTITY, font.main.clone()))
             },
             NodeType::Or => {
-                Some(Gate::or_gate_bs(&mut commands, ev.position, Quat::IDENTITY, font.main.clone()))
+                Some(Gate::or_gate_bs(&mut commands, position, Quat::IDENTITY, font.main.clone()))
             },
             NodeType::Nor => {
-                Some(Gate::nor_gate_bs(&mut commands, ev.position, Quat::IDENTITY, font.main.clone()))
+                Some(Gate::nor_gate_bs(&mut commands, position, Quat::IDENTITY, font.main.clone()))
             },
             NodeType::Xor => {
-                Some(Gate::xor_gate_bs(&mut commands, ev.position, Quat::IDENTITY, font.main.clone()))
+                Some(Gate::xor_gate_bs(&mut commands, position, Quat::IDENTITY, font.main.clone()))
             },
             NodeType::Xnor => {
                 None
             },
             NodeType::Not => {
-                Some(Gate::not_gate_bs(&mut commands, ev.position, Quat::IDENTITY, font.main.clone()))
+                Some(Gate::not_gate_bs(&mut commands, position, Quat::IDENTITY, font.main.clone()))
             },
             NodeType::HighConst => {
-                Some(Gate::high_const(&mut commands, ev.position, Quat::IDENTITY, font.main.clone()))
+                Some(Gate::high_const(&mut commands, position, Quat::IDENTITY, font.main.clone()))
             },
             NodeType::LowConst => {
-                Some(Gate::low_const(&mut commands, ev.position, Quat::IDENTITY, font.main.clone()))
+                Some(Gate::low_const(&mut commands, position, Quat::IDENTITY, font.main.clone()))
             },
             NodeType::ToggleSwitch => {
-                Some(ToggleSwitch::new(&mut commands, ev.position, Quat::IDENTITY, State::Low))
+                Some(ToggleSwitch::new(&mut commands, position, Quat::IDENTITY, State::Low))
             },
             NodeType::Clock => {
-                Some(Clk::spawn(&mut commands, ev.position, Quat::IDENTITY, 1.0, 0.0, State::Low))
+                Some(Clk::spawn(&mut commands, position, Quat::IDENTITY, Clk::new(0.5), State::Low))
             },
             NodeType::LightBulb => {
-                Some(LightBulb::spawn(&mut commands, ev.position, Quat::IDENTITY, State::None))
+                Some(LightBulb::spawn(&mut commands, position, Quat::IDENTITY, State::None))
             },
             NodeType::SevenSegmentDisplay => {
-                Some(SevenSegmentDisplay::spawn(&mut commands, ev.position, Quat::IDENTITY))
+                Some(SevenSegmentDisplay::spawn(&mut commands, position, Quat::IDENTITY))
+            }
+            NodeType::PushButton => {
+                Some(PushButton::spawn(&mut commands, position, Quat::IDENTITY))
+            }
+            NodeType::DipSwitch => {
+                Some(DipSwitch::spawn(&mut commands, position, Quat::IDENTITY, vec![State::Low; 4]))
+            }
+            NodeType::HexKeypad => {
+                Some(HexKeypad::spawn(&mut commands, position, Quat::IDENTITY, 0, font.main.clone()))
+            }
+            NodeType::LedBar => {
+                Some(LedBar::spawn(&mut commands, position, Quat::IDENTITY, 8))
+            }
+            NodeType::DotMatrix => {
+                Some(DotMatrix::spawn(
+                    &mut commands,
+                    position,
+                    Quat::IDENTITY,
+                    8,
+                    8,
+                    MatrixAddressing::RowColumn,
+                    Vec::new(),
+                ))
+            }
+            NodeType::SevenSegmentDirect => {
+                Some(SevenSegmentDirect::spawn(&mut commands, position, Quat::IDENTITY))
+            }
+            NodeType::HexDisplay => {
+                Some(HexDisplay::spawn(&mut commands, position, Quat::IDENTITY, 2))
+            }
+            NodeType::Terminal => {
+                Some(Terminal::spawn(&mut commands, position, Quat::IDENTITY, String::new(), font.main.clone()))
+            }
+            NodeType::AsciiKeyboard => {
+                Some(AsciiKeyboard::spawn(&mut commands, position, Quat::IDENTITY, String::new(), font.main.clone()))
+            }
+            NodeType::PatternGenerator => {
+                Some(PatternGenerator::spawn(&mut commands, position, Quat::IDENTITY, PatternConfig::default(), font.main.clone()))
+            }
+            NodeType::NetLabel => {
+                Some(NetLabel::spawn(&mut commands, position, Quat::IDENTITY, "NET".to_string(), font.main.clone()))
             }
         };
 
         if let Some(entity) = entity {
-            stack.undo.push(Action::Remove(vec![entity]));
-            stack.redo.clear();
+            if align {
+                // The connectors don't exist yet, see `snap_anchor_system`.
+                commands.entity(entity).insert(AlignToGrid);
+            }
+            stack.push(Action::Remove(vec![entity]));
         }
     }
 }
@@ -1,18 +1,37 @@
 use crate::gate::{
-    core::{Name, *},
+    autosave::{Recovery, RecoveryFile},
+    clipboard::ClipboardEvent,
+    core::{Name, State, *},
     file_browser::*,
-    graphics::clk::Clk,
+    graphics::clk::{Clk, ClockConfig},
+    graphics::dip_switch::DipSwitch,
+    graphics::connection_line::WireStyle,
+    graphics::dot_matrix::*,
     graphics::gate::ChangeInput,
+    graphics::led_bar::LedBar,
+    graphics::net_label::MAX_NAME_LEN,
+    graphics::pattern_generator::*,
+    graphics::segment_display::HexDisplay,
+    keys::*,
+    layout::ArrangeEvent,
+    migrate::{self, LoadReport},
+    notify::{Level, Log},
+    preferences::Preferences,
+    recent::RecentFiles,
+    router::AutoRouteEvent,
     serialize::*,
+    svg::SvgOptions,
     undo::*,
+    unsaved::*,
 };
 use crate::radial_menu::Menu;
-use bevy::app::AppExit;
+use crate::FontAssets;
 use bevy::prelude::*;
-use bevy_egui::{egui, EguiContext, EguiSettings};
-use nodus::world2d::camera2d::MainCamera;
+use bevy_egui::{egui, EguiContext};
+use nodus::world2d::camera2d::{MainCamera, MouseWorldPos};
 use nodus::world2d::interaction2d::*;
 use nodus::world2d::*;
+use std::path::Path;
 
 const MIT: &str = "\
 License
@@ -39,10 +58,39 @@ SOFTWARE.";
 
 const NODUS_LOGO_ID: u64 = 0;
 
+/// Set while the node info window waits for a key to bind
+/// to the selected input control.
+pub struct KeyCapture(pub bool);
+
+/// The ASCII keyboard that currently receives typed characters, if any.
+pub struct TypingFocus(pub Option<Entity>);
+
+/// Tool windows that can be opened from the menu bar.
+#[derive(Debug, Clone, Default)]
+pub struct ToolWindows {
+    /// The undo history.
+    pub history: bool,
+    /// The keyboard shortcut editor.
+    pub keymap: bool,
+    /// The preferences of the user.
+    pub preferences: bool,
+    /// The messages of the current session.
+    pub log: bool,
+}
+
+/// The shortcut the keymap editor waits a key chord for, if any.
+pub struct KeymapCapture(pub Option<Shortcut>);
+
 pub fn update_lock(
     mut lock: ResMut<Lock>,
+    egui_context: ResMut<EguiContext>,
     about: Res<GuiMenu>,
     browser: Res<FileBrowser>,
+    unsaved: Res<UnsavedChanges>,
+    recovery: Res<Recovery>,
+    capture: Res<KeyCapture>,
+    shortcut_capture: Res<KeymapCapture>,
+    typing: Res<TypingFocus>,
     q_menu: Query<&Menu>,
 ) {
     let menu = if let Ok(_) = q_menu.get_single() {
@@ -51,13 +99,18 @@ pub fn update_lock(
         false
     };
 
-    lock.0 = about.open || browser.open || menu;
-}
+    // Keyboard shortcuts must not fire while the user types into a text field.
+    let text_input = egui_context.ctx().wants_keyboard_input();
 
-pub fn update_ui_scale_factor(mut egui_settings: ResMut<EguiSettings>, windows: Res<Windows>) {
-    if let Some(_window) = windows.get_primary() {
-        egui_settings.scale_factor = 1.5;
-    }
+    lock.0 = about.open
+        || browser.open
+        || unsaved.asking()
+        || recovery.0.is_some()
+        || capture.0
+        || shortcut_capture.0.is_some()
+        || typing.0.is_some()
+        || text_input
+        || menu;
 }
 
 pub fn load_gui_assets(mut egui_context: ResMut<EguiContext>, assets: Res<AssetServer>) {
@@ -76,6 +129,21 @@ pub fn ui_reset_input(
     }
 }
 
+/// Remember the position of the mouse on the canvas as [`ImportPosition`].
+///
+/// The position is kept while the mouse is on a menu or window and
+/// while the file browser is open, i.e. while a file to import is chosen.
+pub fn import_position_system(
+    egui_context: ResMut<EguiContext>,
+    browser: Res<FileBrowser>,
+    mw: Res<MouseWorldPos>,
+    mut position: ResMut<ImportPosition>,
+) {
+    if !browser.open && !egui_context.ctx().wants_pointer_input() && position.0 != **mw {
+        position.0 = **mw;
+    }
+}
+
 pub fn ui_scroll_system(
     egui_context: ResMut<EguiContext>,
     mut q_camera: Query<&mut Transform, With<MainCamera>>,
@@ -225,34 +293,73 @@ pub fn ui_gui_about(egui_context: ResMut<EguiContext>, mut r: ResMut<GuiMenu>) {
 
 pub fn ui_top_panel_system(
     egui_context: ResMut<EguiContext>,
-    mut exit: EventWriter<AppExit>,
+    mut ev_action: EventWriter<FileAction>,
     mut fbe: EventWriter<OpenBrowserEvent>,
     mut ev_save: EventWriter<SaveEvent>,
-    mut ev_new: EventWriter<NewFileEvent>,
     mut ev_undo: EventWriter<UndoEvent>,
     mut r: ResMut<GuiMenu>,
-    curr_open: Res<CurrentlyOpen>,
+    (curr_open, mut recent, mut log): (Res<CurrentlyOpen>, ResMut<RecentFiles>, ResMut<Log>),
     mut mode: ResMut<InteractionMode>,
     stack: Res<UndoStack>,
+    mut wire_style: ResMut<WireStyle>,
+    (mut ev_route, mut ev_arrange): (EventWriter<AutoRouteEvent>, EventWriter<ArrangeEvent>),
+    mut snap: ResMut<GridSnap>,
+    mut ev_clipboard: EventWriter<ClipboardEvent>,
+    (mut windows, mut svg): (ResMut<ToolWindows>, ResMut<SvgOptions>),
+    keymap: Res<Keymap>,
 ) {
+    let shortcut = |s: Shortcut| keymap.get(s).to_string();
+
     egui::TopBottomPanel::top("side").show(egui_context.ctx(), |ui| {
         ui.columns(2, |columns| {
             columns[0].horizontal(|ui| {
                 ui.menu_button("File", |ui| {
                     ui.add_enabled_ui(true, |ui| {
-                        if ui.button("\u{2B} New").clicked() {
-                            if let Some(path) = &curr_open.path {
-                                ev_save.send(SaveEvent(path.clone()));
-                            }
-                            ev_new.send(NewFileEvent);
+                        if ui.button("\u{2B} New").on_hover_text(shortcut(Shortcut::New)).clicked() {
+                            ev_action.send(FileAction::New);
                             ui.close_menu();
                         }
-                        if ui.button("\u{1F5C1} Open").clicked() {
-                            fbe.send(OpenBrowserEvent(BrowserAction::Open));
+                        if ui.button("\u{1F5C1} Open").on_hover_text(shortcut(Shortcut::Open)).clicked() {
+                            ev_action.send(FileAction::Open);
+                            ui.close_menu();
+                        }
+                        ui.menu_button("Open Recent", |ui| {
+                            let mut open = None;
+                            for path in recent.files.iter() {
+                                let name = Path::new(path)
+                                    .file_name()
+                                    .map(|name| name.to_string_lossy().to_string())
+                                    .unwrap_or_else(|| path.clone());
+                                if ui
+                                    .add_enabled(Path::new(path).is_file(), egui::Button::new(name))
+                                    .on_hover_text(path)
+                                    .clicked()
+                                {
+                                    open = Some(path.clone());
+                                }
+                            }
+                            if recent.files.is_empty() {
+                                ui.add_enabled(false, egui::Button::new("No Recent Files"));
+                            }
+                            ui.separator();
+                            if ui.add_enabled(!recent.files.is_empty(), egui::Button::new("Clear Recent")).clicked() {
+                                recent.files.clear();
+                                if let Err(e) = recent.save() {
+                                    log.error(format!("unable to save recent files: {}", e));
+                                }
+                                ui.close_menu();
+                            }
+                            if let Some(path) = open {
+                                ev_action.send(FileAction::Load(path));
+                                ui.close_menu();
+                            }
+                        });
+                        if ui.button("\u{1F4E5} Import...").on_hover_text("Insert a file at the mouse position").clicked() {
+                            fbe.send(OpenBrowserEvent(BrowserAction::Import));
                             ui.close_menu();
                         }
                         ui.separator();
-                        if ui.button("\u{1F4BE} Save").clicked() {
+                        if ui.button("\u{1F4BE} Save").on_hover_text(shortcut(Shortcut::Save)).clicked() {
                             if let Some(path) = &curr_open.path {
                                 ev_save.send(SaveEvent(path.clone()));
                             } else {
@@ -264,11 +371,67 @@ pub fn ui_top_panel_system(
                             fbe.send(OpenBrowserEvent(BrowserAction::Save));
                             ui.close_menu();
                         }
+                        ui.menu_button("Export SVG", |ui| {
+                            let mut options = *svg;
+                            ui.checkbox(&mut options.selection, "Selection Only");
+                            ui.checkbox(&mut options.signals, "Signal Colors")
+                                .on_hover_text("Color the wires by their current signal");
+                            if options != *svg {
+                                *svg = options;
+                            }
+                            ui.separator();
+                            if ui.button("Export...").clicked() {
+                                fbe.send(OpenBrowserEvent(BrowserAction::Export));
+                                ui.close_menu();
+                            }
+                        });
                     });
                     ui.separator();
+                    if ui.button("Preferences").clicked() {
+                        windows.preferences = true;
+                        ui.close_menu();
+                    }
+                    ui.separator();
                     if ui.button("Exit").clicked() {
                         ui.close_menu();
-                        exit.send(AppExit);
+                        ev_action.send(FileAction::Exit);
+                    }
+                });
+
+                ui.menu_button("Edit", |ui| {
+                    for (s, ev) in [(Shortcut::Undo, UndoEvent::Undo), (Shortcut::Redo, UndoEvent::Redo)] {
+                        let enabled = match ev {
+                            UndoEvent::Undo => !stack.undo.is_empty(),
+                            UndoEvent::Redo => !stack.redo.is_empty(),
+                        };
+                        if ui
+                            .add_enabled(enabled, egui::Button::new(s.label()))
+                            .on_hover_text(shortcut(s))
+                            .clicked()
+                        {
+                            ev_undo.send(ev);
+                            ui.close_menu();
+                        }
+                    }
+                    ui.separator();
+                    for ev in ClipboardEvent::ALL {
+                        if ev == ClipboardEvent::CopyText {
+                            ui.separator();
+                        }
+                        let s = ev.shortcut();
+                        if ui.button(s.label()).on_hover_text(shortcut(s)).clicked() {
+                            ev_clipboard.send(ev);
+                            ui.close_menu();
+                        }
+                    }
+                    ui.separator();
+                    if ui.button("History").clicked() {
+                        windows.history = true;
+                        ui.close_menu();
+                    }
+                    if ui.button("Keyboard Shortcuts").clicked() {
+                        windows.keymap = true;
+                        ui.close_menu();
                     }
                 });
 
@@ -276,9 +439,55 @@ pub fn ui_top_panel_system(
                     if ui.button("Back to Origin").clicked() {
                         ui.close_menu();
                     }
+                    ui.separator();
+                    let mut style = *wire_style;
+                    ui.radio_value(&mut style, WireStyle::Bezier, "Curved Wires");
+                    ui.radio_value(&mut style, WireStyle::Orthogonal, "Orthogonal Wires");
+                    if style != *wire_style {
+                        *wire_style = style;
+                    }
+                    ui.separator();
+                    let mut enabled = snap.enabled;
+                    ui.checkbox(&mut enabled, "Snap to Grid")
+                        .on_hover_text("Hold alt while dragging to invert");
+                    let mut size = snap.size;
+                    ui.horizontal(|ui| {
+                        ui.label("Grid Size");
+                        ui.add(
+                            egui::DragValue::new(&mut size)
+                                .clamp_range(GridSnap::RANGE.0..=GridSnap::RANGE.1),
+                        );
+                    });
+                    if enabled != snap.enabled || size != snap.size {
+                        snap.enabled = enabled;
+                        snap.size = size;
+                    }
+                });
+
+                ui.menu_button("Tools", |ui| {
+                    if ui
+                        .button("Arrange")
+                        .on_hover_text("Place the components in layers from inputs to outputs")
+                        .clicked()
+                    {
+                        ev_arrange.send(ArrangeEvent);
+                        ui.close_menu();
+                    }
+                    if ui
+                        .button("Auto-Route Wires")
+                        .on_hover_text("Route the selected wires (or all wires) around components")
+                        .clicked()
+                    {
+                        ev_route.send(AutoRouteEvent);
+                        ui.close_menu();
+                    }
                 });
 
                 ui.menu_button("Help", |ui| {
+                    if ui.button("Message Log").clicked() {
+                        windows.log = true;
+                        ui.close_menu();
+                    }
                     ui.separator();
                     if ui.button("\u{FF1F} About Nodus").clicked() {
                         r.option = GuiMenuOptions::About;
@@ -349,12 +558,527 @@ pub fn ui_top_panel_system(
     });
 }
 
+/// List the actions of the undo and redo stack.
+///
+/// Clicking an entry undoes or redoes all actions up to it.
+pub fn ui_history_system(
+    egui_context: ResMut<EguiContext>,
+    mut windows: ResMut<ToolWindows>,
+    stack: Res<UndoStack>,
+    mut jump: ResMut<UndoJump>,
+    q_name: Query<&Name>,
+    q_line: Query<&ConnectionLine>,
+    q_parent: Query<&Parent>,
+    q_transform: Query<&Transform>,
+) {
+    let current = stack.undo.len();
+    let mut target = None;
+
+    egui::Window::new("History")
+        .open(&mut windows.history)
+        .resizable(false)
+        .collapsible(false)
+        .show(egui_context.ctx(), |ui| {
+            egui::ScrollArea::vertical().max_height(320.).show(ui, |ui| {
+                if ui.selectable_label(current == 0, "Start").clicked() {
+                    target = Some(0);
+                }
+                for (i, action) in stack.undo.iter().enumerate() {
+                    let text = describe(action, true, &q_name, &q_line, &q_parent, &q_transform);
+                    if ui.selectable_label(i + 1 == current, text).clicked() {
+                        target = Some(i + 1);
+                    }
+                }
+                // The next action to redo is the last one of the stack.
+                for (i, action) in stack.redo.iter().rev().enumerate() {
+                    let text = describe(action, false, &q_name, &q_line, &q_parent, &q_transform);
+                    if ui
+                        .selectable_label(false, egui::RichText::new(text).weak())
+                        .clicked()
+                    {
+                        target = Some(current + i + 1);
+                    }
+                }
+            });
+        });
+
+    if target.is_some() && target != Some(current) {
+        jump.0 = target;
+    }
+}
+
+/// Edit the keyboard shortcuts of the [`Keymap`].
+///
+/// Clicking a shortcut waits for the next key chord, escape cancels.
+/// Changes are written to the keymap file right away.
+pub fn ui_keymap_system(
+    egui_context: ResMut<EguiContext>,
+    mut windows: ResMut<ToolWindows>,
+    mut keymap: ResMut<Keymap>,
+    mut capture: ResMut<KeymapCapture>,
+    keyboard: Res<Input<KeyCode>>,
+    mut log: ResMut<Log>,
+) {
+    if !windows.keymap {
+        if capture.0.is_some() {
+            capture.0 = None;
+        }
+        return;
+    }
+
+    let mut changed = false;
+    if let Some(shortcut) = capture.0 {
+        for &key in keyboard.get_just_pressed() {
+            if key != KeyCode::Escape {
+                match Chord::from_keyboard(key, &keyboard) {
+                    Some(chord) => {
+                        keymap.set(shortcut, chord);
+                        changed = true;
+                    }
+                    // Modifier keys or keys that can't be used.
+                    None => continue,
+                }
+            }
+            capture.0 = None;
+            break;
+        }
+    }
+
+    egui::Window::new("Keyboard Shortcuts")
+        .open(&mut windows.keymap)
+        .resizable(false)
+        .collapsible(false)
+        .show(egui_context.ctx(), |ui| {
+            egui::Grid::new("keymap").striped(true).show(ui, |ui| {
+                for shortcut in Shortcut::ALL {
+                    ui.label(shortcut.label());
+                    let text = if capture.0 == Some(shortcut) {
+                        "Press keys...".to_string()
+                    } else {
+                        keymap.get(shortcut).to_string()
+                    };
+                    if ui.button(text).clicked() {
+                        capture.0 = Some(shortcut);
+                    }
+                    if keymap.conflicts(shortcut) {
+                        ui.label("\u{26A0}").on_hover_text("Also used by another shortcut");
+                    } else {
+                        ui.label("");
+                    }
+                    ui.end_row();
+                }
+            });
+            ui.separator();
+            if ui.button("Reset to Defaults").clicked() {
+                *keymap = Keymap::default();
+                capture.0 = None;
+                changed = true;
+            }
+        });
+
+    if changed {
+        if let Err(e) = keymap.save() {
+            log.error(format!("unable to save keymap: {}", e));
+        }
+    }
+}
+
+/// Edit the [`Preferences`] of the user.
+///
+/// Changes are applied right away and written to the preferences
+/// file once the mouse button is released.
+pub fn ui_preferences_system(
+    egui_context: ResMut<EguiContext>,
+    mut windows: ResMut<ToolWindows>,
+    mut prefs: ResMut<Preferences>,
+    mut unsaved: Local<bool>,
+    mut log: ResMut<Log>,
+) {
+    if !windows.preferences && !*unsaved {
+        return;
+    }
+
+    let mut edit = prefs.clone();
+    let mut dir = edit
+        .default_dir
+        .as_ref()
+        .map(|dir| dir.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    egui::Window::new("Preferences")
+        .open(&mut windows.preferences)
+        .resizable(false)
+        .collapsible(false)
+        .show(egui_context.ctx(), |ui| {
+            egui::Grid::new("preferences").num_columns(2).show(ui, |ui| {
+                ui.label("UI Scale: ");
+                ui.add(
+                    egui::DragValue::new(&mut edit.scale_factor)
+                        .speed(0.05)
+                        .clamp_range(Preferences::SCALE_RANGE.0..=Preferences::SCALE_RANGE.1),
+                );
+                ui.end_row();
+
+                ui.label("Background: ");
+                ui.color_edit_button_rgb(&mut edit.background);
+                ui.end_row();
+
+                let range = Preferences::WINDOW_RANGE.0..=Preferences::WINDOW_RANGE.1;
+                ui.label("Window Size: ");
+                ui.horizontal(|ui| {
+                    ui.add(egui::DragValue::new(&mut edit.window_width).clamp_range(range.clone()));
+                    ui.label("x");
+                    ui.add(egui::DragValue::new(&mut edit.window_height).clamp_range(range));
+                });
+                ui.end_row();
+
+                ui.label("VSync: ");
+                ui.checkbox(&mut edit.vsync, "");
+                ui.end_row();
+
+                ui.label("Default Directory: ");
+                ui.add(egui::TextEdit::singleline(&mut dir).hint_text("Home Directory"));
+                ui.end_row();
+
+                ui.label("Undo Steps: ");
+                ui.add(
+                    egui::DragValue::new(&mut edit.undo_steps)
+                        .clamp_range(UndoStack::RANGE.0..=UndoStack::RANGE.1),
+                );
+                ui.end_row();
+
+                ui.label("Reopen Last File: ");
+                ui.checkbox(&mut edit.reopen_last, "");
+                ui.end_row();
+
+                ui.label("Autosave Interval: ");
+                ui.add(
+                    egui::DragValue::new(&mut edit.autosave_interval)
+                        .suffix(" s")
+                        .clamp_range(Preferences::AUTOSAVE_RANGE.0..=Preferences::AUTOSAVE_RANGE.1),
+                )
+                .on_hover_text("0 disables autosaving");
+                ui.end_row();
+            });
+            ui.separator();
+            if ui.button("Reset to Defaults").clicked() {
+                edit = Preferences::default();
+                dir.clear();
+            }
+        });
+
+    edit.default_dir = if dir.is_empty() {
+        None
+    } else {
+        Some(dir.into())
+    };
+
+    if edit != *prefs {
+        *prefs = edit;
+        *unsaved = true;
+    }
+
+    if *unsaved && !egui_context.ctx().input().pointer.any_down() {
+        if let Err(e) = prefs.save() {
+            log.error(format!("unable to save preferences: {}", e));
+        }
+        *unsaved = false;
+    }
+}
+
+/// Offer to restore the circuit autosaved by a session that
+/// hasn't been closed properly.
+pub fn ui_recovery_system(
+    mut commands: Commands,
+    egui_context: ResMut<EguiContext>,
+    mut recovery: ResMut<Recovery>,
+    font: Res<FontAssets>,
+    mut curr_open: ResMut<CurrentlyOpen>,
+    mut stack: ResMut<UndoStack>,
+    mut report: ResMut<LoadReport>,
+    mut log: ResMut<Log>,
+    q_all: Query<Entity, Or<(With<NodeType>, With<ConnectionLine>)>>,
+) {
+    let file = match &recovery.0 {
+        Some(file) => file,
+        None => return,
+    };
+
+    let mut restore = None;
+    egui::Window::new("Restore Unsaved Work")
+        .resizable(false)
+        .collapsible(false)
+        .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+        .show(egui_context.ctx(), |ui| {
+            ui.label("Nodus has not been closed properly.");
+            ui.label(format!(
+                "Restore {} as autosaved at {}?",
+                file.path.as_deref().unwrap_or("the untitled circuit"),
+                file.time.format("%Y-%m-%d %H:%M:%S"),
+            ));
+            ui.separator();
+            ui.horizontal(|ui| {
+                if ui.button("Restore").clicked() {
+                    restore = Some(true);
+                }
+                if ui.button("Discard").clicked() {
+                    restore = Some(false);
+                }
+            });
+        });
+
+    if let Some(restore) = restore {
+        let mut file = recovery.0.take().unwrap();
+        if restore {
+            // The recovery file might have been written by an older version.
+            if let Err(e) = migrate::check(&mut file.save) {
+                log.error(format!("unable to restore the autosaved circuit: {}", e));
+                let path = RecoveryFile::path().unwrap_or_default();
+                report.0 = Some((path.to_string_lossy().to_string(), e));
+                return;
+            }
+
+            for e in q_all.iter() {
+                commands.entity(e).despawn_recursive();
+            }
+            spawn_save(&mut commands, font.main.clone(), file.save);
+            curr_open.path = file.path;
+            // The restored changes haven't been saved yet.
+            curr_open.dirty = true;
+            stack.reset();
+            stack.saved = None;
+            log.info("restored the autosaved circuit");
+        }
+
+        if let Err(e) = RecoveryFile::remove() {
+            log.error(format!("unable to remove recovery file: {}", e));
+        }
+    }
+}
+
+/// Ask whether to save the changes before carrying out a
+/// [`FileAction`] that would discard them.
+pub fn ui_unsaved_system(
+    egui_context: ResMut<EguiContext>,
+    mut unsaved: ResMut<UnsavedChanges>,
+    curr_open: Res<CurrentlyOpen>,
+    mut ev_save: EventWriter<SaveEvent>,
+    mut fbe: EventWriter<OpenBrowserEvent>,
+) {
+    if !unsaved.asking() {
+        return;
+    }
+
+    let name = curr_open
+        .path
+        .as_ref()
+        .and_then(|path| Path::new(path).file_name())
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| "the untitled circuit".to_string());
+
+    egui::Window::new("Unsaved Changes")
+        .resizable(false)
+        .collapsible(false)
+        .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+        .show(egui_context.ctx(), |ui| {
+            ui.label(format!("Save the changes to {}?", name));
+            ui.separator();
+            ui.horizontal(|ui| {
+                if ui.button("Save").clicked() {
+                    if let Some(path) = &curr_open.path {
+                        ev_save.send(SaveEvent(path.clone()));
+                    } else {
+                        fbe.send(OpenBrowserEvent(BrowserAction::Save));
+                    }
+                    unsaved.state = PromptState::Saving;
+                }
+                if ui.button("Discard").clicked() {
+                    unsaved.state = PromptState::Discard;
+                }
+                if ui.button("Cancel").clicked() {
+                    unsaved.pending = None;
+                }
+            });
+        });
+}
+
+/// Explain why a file couldn't be opened.
+pub fn ui_load_report_system(egui_context: ResMut<EguiContext>, mut report: ResMut<LoadReport>) {
+    let (path, error) = match &report.0 {
+        Some(report) => report,
+        None => return,
+    };
+
+    let mut close = false;
+    egui::Window::new("Unable to Open File")
+        .resizable(false)
+        .collapsible(false)
+        .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+        .show(egui_context.ctx(), |ui| {
+            ui.label(path);
+            ui.separator();
+            egui::ScrollArea::vertical().max_height(300.).show(ui, |ui| {
+                for problem in error.problems() {
+                    ui.label(problem);
+                }
+            });
+            ui.separator();
+            if ui.button("Ok").clicked() {
+                close = true;
+            }
+        });
+
+    if close {
+        report.0 = None;
+    }
+}
+
+/// The color messages of the given level are highlighted with.
+fn level_color(level: Level) -> egui::Color32 {
+    match level {
+        Level::Info => egui::Color32::LIGHT_BLUE,
+        Level::Warning => egui::Color32::YELLOW,
+        Level::Error => egui::Color32::from_rgb(255, 80, 80),
+    }
+}
+
+/// Show new messages in the bottom right corner for a few
+/// seconds. Clicking a message closes it.
+pub fn ui_toast_system(egui_context: ResMut<EguiContext>, mut log: ResMut<Log>) {
+    let now = chrono::Local::now();
+    if !log.messages.iter().any(|m| m.is_toast(now)) {
+        return;
+    }
+
+    let mut dismiss = None;
+    egui::Area::new("toasts")
+        .anchor(egui::Align2::RIGHT_BOTTOM, egui::Vec2::new(-10., -10.))
+        .show(egui_context.ctx(), |ui| {
+            for (i, message) in log.messages.iter().enumerate() {
+                if !message.is_toast(now) {
+                    continue;
+                }
+
+                egui::Frame::popup(ui.style()).show(ui, |ui| {
+                    ui.set_max_width(320.);
+                    let response = ui
+                        .horizontal_wrapped(|ui| {
+                            ui.colored_label(level_color(message.level), message.level.to_string());
+                            ui.label(&message.text);
+                        })
+                        .response
+                        .interact(egui::Sense::click());
+                    if response.on_hover_text("Click to close").clicked() {
+                        dismiss = Some(i);
+                    }
+                });
+            }
+        });
+
+    if let Some(i) = dismiss {
+        log.messages[i].dismissed = true;
+    }
+}
+
+/// List the messages of the current session.
+pub fn ui_log_system(
+    egui_context: ResMut<EguiContext>,
+    mut windows: ResMut<ToolWindows>,
+    mut log: ResMut<Log>,
+) {
+    let mut clear = false;
+    egui::Window::new("Message Log")
+        .open(&mut windows.log)
+        .resizable(true)
+        .collapsible(false)
+        .default_size(egui::Vec2::new(480., 240.))
+        .show(egui_context.ctx(), |ui| {
+            egui::ScrollArea::vertical().max_height(320.).show(ui, |ui| {
+                if log.messages.is_empty() {
+                    ui.label(egui::RichText::new("No messages").weak());
+                }
+                // The most recent message first.
+                for message in log.messages.iter().rev() {
+                    ui.horizontal_wrapped(|ui| {
+                        ui.label(
+                            egui::RichText::new(message.time.format("%H:%M:%S").to_string())
+                                .weak(),
+                        );
+                        ui.colored_label(level_color(message.level), message.level.to_string());
+                        ui.label(&message.text);
+                    });
+                }
+            });
+            ui.separator();
+            if ui.add_enabled(!log.messages.is_empty(), egui::Button::new("Clear")).clicked() {
+                clear = true;
+            }
+        });
+
+    if clear {
+        log.messages.clear();
+    }
+}
+
 pub fn ui_node_info_system(
+    mut commands: Commands,
     egui_context: ResMut<EguiContext>,
-    mut q_gate: Query<(Entity, &Name, &mut Transform, Option<&Gate>, Option<&mut Clk>), With<Selected>>,
+    mut q_gate: Query<
+        (
+            Entity,
+            &Name,
+            &mut Transform,
+            Option<&Gate>,
+            Option<&mut Clk>,
+            Option<&NodeType>,
+            Option<&KeyBinding>,
+            Option<&Inputs>,
+            Option<&DotMatrix>,
+            Option<&mut PatternGenerator>,
+            Option<&mut NetLabel>,
+        ),
+        With<Selected>,
+    >,
     mut ev_change: EventWriter<ChangeInput>,
+    mut ev_reconf: EventWriter<ReconfigureEvent>,
+    mut ev_edit: EventWriter<EditEvent>,
+    mut stack: ResMut<UndoStack>,
+    mut capture: ResMut<KeyCapture>,
+    mut typing: ResMut<TypingFocus>,
+    keyboard: Res<Input<KeyCode>>,
 ) {
-    if let Ok((entity, name, mut trans, gate, mut clk)) = q_gate.get_single_mut() {
+    if let Ok((entity, name, mut trans, gate, mut clk, ntype, binding, inputs, matrix, mut generator, mut label)) =
+        q_gate.get_single_mut()
+     {
+        let bindable = matches!(ntype, Some(NodeType::ToggleSwitch) | Some(NodeType::PushButton));
+        // Set by every change of a property, so it can be undone.
+        let mut edited = false;
+
+        // Bind the next key pressed to the selected input control.
+        // Backspace removes the current binding, escape cancels.
+        if bindable && capture.0 {
+            for &key in keyboard.get_just_pressed() {
+                match key {
+                    KeyCode::Escape => {}
+                    KeyCode::Back => {
+                        commands.entity(entity).remove::<KeyBinding>();
+                        edited = true;
+                    }
+                    _ => {
+                        if key_name(key).is_none() {
+                            continue;
+                        }
+                        commands.entity(entity).insert(KeyBinding(key));
+                        edited = true;
+                    }
+                }
+                capture.0 = false;
+                break;
+            }
+        } else {
+            capture.0 = false;
+        }
+
         egui::Window::new(&name.0)
             .title_bar(false)
             .anchor(egui::Align2::RIGHT_BOTTOM, egui::Vec2::new(-5., -5.))
@@ -372,6 +1096,7 @@ pub fn ui_node_info_system(
                                         gate: entity,
                                         to: gate.inputs - 1,
                                     });
+                                    edited = true;
                                 }
                             }
                             ui.label(format!("{}", gate.inputs));
@@ -381,36 +1106,355 @@ pub fn ui_node_info_system(
                                         gate: entity,
                                         to: gate.inputs + 1,
                                     });
+                                    edited = true;
+                                }
+                            }
+                        });
+                    }
+                }
+
+                // Displays that are configured by their input count.
+                let per_input = match ntype {
+                    Some(NodeType::LedBar) => Some(("LED Count: ", 1, LedBar::RANGE)),
+                    Some(NodeType::HexDisplay) => Some(("Digit Count: ", 4, HexDisplay::RANGE)),
+                    _ => None,
+                };
+
+                if let (Some((label, step, range)), Some(inputs)) = (per_input, inputs) {
+                    let count = inputs.len() / step;
+                    ui.horizontal(|ui| {
+                        ui.label(label);
+                        if ui.button("➖").clicked() && count > range.min as usize {
+                            ev_reconf.send(ReconfigureEvent {
+                                entity,
+                                inputs: Some((count - 1) * step),
+                                state: None,
+                            });
+                            edited = true;
+                        }
+                        ui.label(format!("{}", count));
+                        if ui.button("➕").clicked() && count < range.max as usize {
+                            ev_reconf.send(ReconfigureEvent {
+                                entity,
+                                inputs: Some((count + 1) * step),
+                                state: None,
+                            });
+                            edited = true;
+                        }
+                    });
+                }
+
+                if let (Some(NodeType::DipSwitch), Some(inputs)) = (ntype, inputs) {
+                    let range = DipSwitch::RANGE;
+                    let count = inputs.len();
+                    // The switches that remain keep their position.
+                    let mut resize = |n: usize| {
+                        let mut states = inputs.0.clone();
+                        states.resize(n, State::Low);
+                        ev_reconf.send(ReconfigureEvent {
+                            entity,
+                            inputs: None,
+                            state: Some(NodeState::DipSwitch(states)),
+                        });
+                    };
+
+                    ui.horizontal(|ui| {
+                        ui.label("Switch Count: ");
+                        if ui.button("➖").clicked() && count > range.min as usize {
+                            resize(count - 1);
+                            edited = true;
+                        }
+                        ui.label(format!("{}", count));
+                        if ui.button("➕").clicked() && count < range.max as usize {
+                            resize(count + 1);
+                            edited = true;
+                        }
+                    });
+                }
+
+                if let Some(matrix) = matrix {
+                    let (mut rows, mut cols, mut addressing) =
+                        (matrix.rows, matrix.cols, matrix.addressing);
+                    let range = DotMatrix::RANGE;
+
+                    ui.horizontal(|ui| {
+                        ui.label("Rows: ");
+                        if ui.button("➖").clicked() && rows > range.min as usize {
+                            rows -= 1;
+                        }
+                        ui.label(format!("{}", rows));
+                        if ui.button("➕").clicked() && rows < range.max as usize {
+                            rows += 1;
+                        }
+                    });
+                    ui.horizontal(|ui| {
+                        ui.label("Columns: ");
+                        if ui.button("➖").clicked() && cols > range.min as usize {
+                            cols -= 1;
+                        }
+                        ui.label(format!("{}", cols));
+                        if ui.button("➕").clicked() && cols < range.max as usize {
+                            cols += 1;
+                        }
+                    });
+                    ui.horizontal(|ui| {
+                        ui.label("Addressing: ");
+                        egui::ComboBox::from_id_source("addressing")
+                            .selected_text(addressing.label())
+                            .show_ui(ui, |ui| {
+                                for a in [MatrixAddressing::RowColumn, MatrixAddressing::FrameBuffer] {
+                                    ui.selectable_value(&mut addressing, a, a.label());
+                                }
+                            });
+                    });
+
+                    if rows != matrix.rows || cols != matrix.cols || addressing != matrix.addressing {
+                        ev_reconf.send(ReconfigureEvent {
+                            entity,
+                            inputs: Some(DotMatrix::input_count(rows, cols, addressing)),
+                            state: Some(NodeState::DotMatrix(rows, cols, addressing, matrix.frame.clone())),
+                        });
+                        edited = true;
+                    }
+                }
+
+                if let Some(ref mut label) = label {
+                    let mut net = label.0.clone();
+                    ui.horizontal(|ui| {
+                        ui.label("Net: ");
+                        ui.add(egui::TextEdit::singleline(&mut net).desired_width(120.));
+                    });
+                    if net.chars().count() > MAX_NAME_LEN {
+                        net = net.chars().take(MAX_NAME_LEN).collect();
+                    }
+
+                    if net != label.0 {
+                        label.0 = net;
+                        edited = true;
+                    }
+                }
+
+                if let Some(ref mut generator) = generator {
+                    let mut config = generator.config.clone();
+                    let mut external = config.frequency.is_none();
+                    let mut frequency = config.frequency.unwrap_or(1.0);
+                    let range = PatternGenerator::RANGE;
+                    let n = config.outputs;
+
+                    ui.horizontal(|ui| {
+                        ui.label("Output Count: ");
+                        if ui.button("➖").clicked() && n > range.min as usize {
+                            config.outputs -= 1;
+                        }
+                        ui.label(format!("{}", n));
+                        if ui.button("➕").clicked() && n < range.max as usize {
+                            config.outputs += 1;
+                        }
+                    });
+                    ui.horizontal(|ui| {
+                        ui.label("Mode: ");
+                        egui::ComboBox::from_id_source("pattern_mode")
+                            .selected_text(config.mode.label())
+                            .show_ui(ui, |ui| {
+                                for m in [PatternMode::Table, PatternMode::Counter, PatternMode::Random] {
+                                    ui.selectable_value(&mut config.mode, m, m.label());
+                                }
+                            });
+                        ui.checkbox(&mut config.looping, "Loop");
+                    });
+                    ui.horizontal(|ui| {
+                        ui.checkbox(&mut external, "External Clock");
+                        ui.add_enabled(
+                            !external,
+                            egui::DragValue::new(&mut frequency)
+                                .speed(0.01)
+                                .suffix(" Hz")
+                                .max_decimals(4)
+                                .clamp_range(Clk::FREQUENCY.0..=Clk::FREQUENCY.1),
+                        );
+                    });
+                    config.frequency = if external { None } else { Some(frequency) };
+
+                    if config.mode == PatternMode::Table {
+                        let mut remove = None;
+                        egui::ScrollArea::vertical().max_height(240.).show(ui, |ui| {
+                            egui::Grid::new("patterns").striped(true).show(ui, |ui| {
+                                for (step, pattern) in config.patterns.iter_mut().enumerate() {
+                                    if step == generator.step {
+                                        ui.label(format!("▶ {}", step));
+                                    } else {
+                                        ui.label(format!("{}", step));
+                                    }
+                                    ui.horizontal(|ui| {
+                                        for i in 0..n {
+                                            let bit = 1 << (n - 1 - i);
+                                            let set = *pattern & bit != 0;
+                                            if ui.selectable_label(set, if set { "1" } else { "0" }).clicked() {
+                                                *pattern ^= bit;
+                                            }
+                                        }
+                                    });
+                                    if ui.button("🗑").clicked() {
+                                        remove = Some(step);
+                                    }
+                                    ui.end_row();
                                 }
+                            });
+                        });
+
+                        if let Some(step) = remove {
+                            if config.patterns.len() > 1 {
+                                config.patterns.remove(step);
                             }
+                        }
+                        if ui.button("➕ Add Step").clicked()
+                            && config.patterns.len() < PatternGenerator::MAX_PATTERNS
+                        {
+                            let last = config.patterns.last().copied().unwrap_or(0);
+                            config.patterns.push(last);
+                        }
+                    }
+
+                    let restart = ui.button("Restart").clicked();
+
+                    if config.outputs != generator.config.outputs
+                        || config.frequency.is_none() != generator.config.frequency.is_none()
+                    {
+                        // The connectors change, i.e. the generator must be respawned.
+                        ev_reconf.send(ReconfigureEvent {
+                            entity,
+                            inputs: None,
+                            state: Some(NodeState::PatternGenerator(config)),
                         });
+                        edited = true;
+                    } else if config != generator.config {
+                        let mode_changed = config.mode != generator.config.mode;
+                        generator.config = config;
+                        if mode_changed {
+                            generator.reset();
+                        }
+                        edited = true;
+                    }
+
+                    if restart {
+                        generator.reset();
                     }
                 }
 
                 if let Some(ref mut clk) = clk {
-                    let mut clk_f32 = clk.0 * 1000.;
+                    let mut frequency = clk.frequency;
+                    let mut duty = clk.config.duty * 100.;
+                    let mut phase = clk.config.phase * 360.;
+                    let mut limited = clk.config.cycles.is_some();
+                    let mut cycles = clk.config.cycles.unwrap_or(1);
+
                     ui.horizontal(|ui| {
-                        ui.label("Signal Duration: ");
+                        ui.label("Frequency: ");
                         ui.add(
-                            egui::DragValue::new(&mut clk_f32)
+                            egui::DragValue::new(&mut frequency)
+                                .speed(0.01)
+                                .suffix(" Hz")
+                                .max_decimals(4)
+                                .clamp_range(Clk::FREQUENCY.0..=Clk::FREQUENCY.1),
+                        );
+                    });
+                    ui.horizontal(|ui| {
+                        ui.label("Duty Cycle: ");
+                        ui.add(
+                            egui::DragValue::new(&mut duty)
                                 .speed(1.0)
-                                .clamp_range(std::ops::RangeInclusive::new(250.0, 600000.0)),
+                                .suffix(" %")
+                                .clamp_range(1.0..=99.0),
                         );
                     });
+                    ui.horizontal(|ui| {
+                        ui.label("Phase: ");
+                        ui.add(
+                            egui::DragValue::new(&mut phase)
+                                .speed(1.0)
+                                .suffix("°")
+                                .clamp_range(0.0..=359.0),
+                        );
+                    });
+                    ui.horizontal(|ui| {
+                        ui.checkbox(&mut limited, "Stop after: ");
+                        ui.add_enabled(
+                            limited,
+                            egui::DragValue::new(&mut cycles)
+                                .speed(1.0)
+                                .suffix(" cycles")
+                                .clamp_range(1..=u32::MAX),
+                        );
+                    });
+
+                    let config = ClockConfig {
+                        duty: duty / 100.,
+                        phase: phase / 360.,
+                        cycles: if limited { Some(cycles) } else { None },
+                    };
+                    if frequency != clk.frequency || config != clk.config {
+                        clk.frequency = frequency;
+                        clk.config = config;
+                        edited = true;
+                    }
+
+                    if ui.button("Restart").clicked() {
+                        clk.elapsed = 0.0;
+                    }
+                }
+
+                if bindable {
+                    ui.horizontal(|ui| {
+                        ui.label("Key: ");
+                        let text = if capture.0 {
+                            "Press a key...".to_string()
+                        } else if let Some(binding) = binding {
+                            key_label(binding.0).to_string()
+                        } else {
+                            "None".to_string()
+                        };
 
-                    clk.0 = clk_f32 / 1000.;
+                        if ui
+                            .button(text)
+                            .on_hover_text("Click and press a key to bind it, backspace to unbind")
+                            .clicked()
+                        {
+                            capture.0 = true;
+                        }
+                    });
+                }
+
+                if let Some(NodeType::AsciiKeyboard) = ntype {
+                    let focused = typing.0 == Some(entity);
+                    let text = if focused { "Stop Typing" } else { "Type" };
+                    if ui
+                        .button(text)
+                        .on_hover_text("Typed characters are sent to the keyboard, escape to stop")
+                        .clicked()
+                    {
+                        typing.0 = if focused { None } else { Some(entity) };
+                    }
                 }
 
                 ui.horizontal(|ui| {
                     ui.label("Rotate: ");
+                    let before = *trans;
                     if ui.button("\u{27f2}").clicked() {
                         trans.rotate(Quat::from_rotation_z(std::f32::consts::PI / 2.0));
-
                     }
                     if ui.button("\u{27f3}").clicked() {
                         trans.rotate(Quat::from_rotation_z(-std::f32::consts::PI / 2.0));
                     }
+                    if *trans != before {
+                        stack.push(Action::Transform(vec![(entity, before)]));
+                    }
                 });
             });
+
+        if edited {
+            ev_edit.send(EditEvent(entity));
+        }
+    } else {
+        capture.0 = false;
     }
 }
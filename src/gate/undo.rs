@@ -1,11 +1,19 @@
-use std::collections::hash_set::HashSet;
+use std::collections::HashMap;
 use crate::gate::{
     core::{Name, *},
-    graphics::{clk::*, light_bulb::*, toggle_switch::*, segment_display::*},
+    keys::*,
+    graphics::{
+        ascii_keyboard::*, clk::*, dip_switch::*, dot_matrix::*, hex_keypad::*, led_bar::*,
+        light_bulb::*, pattern_generator::*, push_button::*, segment_display::*, terminal::*,
+        toggle_switch::*,
+    },
+    router::AutoRoute,
     serialize::*,
 };
 use bevy::prelude::*;
-use crate::GameState;
+use bevy_egui::EguiContext;
+use nodus::world2d::interaction2d::Selected;
+use crate::{FontAssets, GameState};
 
 pub struct UndoPlugin;
 
@@ -14,18 +22,29 @@ impl Plugin for UndoPlugin {
         app.add_event::<UndoEvent>()
             .add_event::<ReconnectGates>()
             .add_event::<DisconnectEventUndo>()
+            .add_event::<ReconfigureEvent>()
+            .add_event::<EditEvent>()
             .insert_resource(UndoStack {
                 undo: Vec::new(),
                 redo: Vec::new(),
+                max: UndoStack::DEFAULT_MAX,
+                saved: Some(0),
             })
+            .insert_resource(UndoJump(None))
             .add_system_set(
                 SystemSet::on_update(GameState::InGame)
                     .label("undo")
-                    .with_system(reconnect_gates_event_system.before("handle_undo"))
+                    .with_system(reconnect_gates_event_system.label("reconnect").before("handle_undo"))
                     // Not pretty but this system must run after the disconnect
                     // system to prevent program crashes due to data races.
                     .with_system(handle_undo_event_system.label("handle_undo").after("disconnect"))
+                    .with_system(undo_jump_system.before("handle_undo"))
+                    .with_system(limit_undo_stack_system.after("handle_undo"))
                     .with_system(listen_for_new_connections_system)
+                    .with_system(reconfigure_event_system.label("reconfigure").after("disconnect"))
+                    // The state before an edit must be on the stack before a
+                    // reconfiguration replaces the entity id of the component.
+                    .with_system(record_edit_system.after("ui_info").before("reconfigure"))
                     // Alot of systems run after disconnect to prevent Segfaults,
                     // i.e. we must run this system also before the others.
                     .with_system(disconnect_event_system_undo.before("disconnect").after("draw_line"))
@@ -39,18 +58,259 @@ pub enum UndoEvent {
     Redo,
 }
 
+/// A connection between two logic components, captured so it can
+/// be restored after the components have been respawned.
+///
+/// In contrast to a [`ConnectionLine`], the [`ConnInfo`]s refer to
+/// the components and not to their connectors.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Link {
+    pub output: ConnInfo,
+    pub input: ConnInfo,
+    /// The connection line the link has been captured from, if
+    /// the connection existed before, e.g. not for pasted ones.
+    pub line: Option<Entity>,
+    /// Waypoints of the connection line.
+    pub via: Vec<Vec2>,
+    /// The connection line is routed automatically, see [`AutoRoute`].
+    pub auto: bool,
+}
+
+impl Link {
+    /// Replace the component `old` with `new` at both ends of the link.
+    fn replace_entity(&mut self, old: Entity, new: Entity) {
+        if self.output.entity == old {
+            self.output.entity = new;
+        }
+        if self.input.entity == old {
+            self.input.entity = new;
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum Action {
-    Insert((Vec<NodusComponent>, HashSet<(ConnInfo, ConnInfo, Entity)>)),
+    Insert((Vec<NodusComponent>, Vec<Link>)),
     Remove(Vec<Entity>),
-    InsertConnection((ConnInfo, ConnInfo, Entity)),
+    InsertConnection(Link),
     RemoveConnection(Entity),
+    /// Move or rotate the given entities back to the given transforms.
+    Transform(Vec<(Entity, Transform)>),
+    /// Respawn the given components with the captured state, e.g. to
+    /// revert a property edit, and restore the captured connections.
+    Replace((Vec<NodusComponent>, Vec<Link>)),
+    /// Restore the waypoints of the given connection lines and whether
+    /// they are routed automatically.
+    Waypoints(Vec<(Entity, Vec<Vec2>, bool)>),
 }
 
 #[derive(Debug, Clone)]
 pub struct UndoStack {
     pub undo: Vec<Action>,
     pub redo: Vec<Action>,
+    /// The maximum number of actions kept on each stack.
+    pub max: usize,
+    /// The number of actions on the undo stack when the circuit has
+    /// been saved, `None` if the saved state can't be reached by
+    /// undoing or redoing actions.
+    pub saved: Option<usize>,
+}
+
+impl UndoStack {
+    pub const DEFAULT_MAX: usize = 100;
+    /// Minimum and maximum value of `max`.
+    pub const RANGE: (usize, usize) = (1, 1000);
+
+    /// Record a new action. The actions that could be redone are
+    /// discarded.
+    pub fn push(&mut self, action: Action) {
+        if self.saved.map_or(false, |saved| saved > self.undo.len()) {
+            // The saved state has been undone and is replaced.
+            self.saved = None;
+        }
+        self.undo.push(action);
+        self.redo.clear();
+    }
+
+    /// Forget all actions, e.g. when another circuit is opened,
+    /// whose state is the saved one.
+    pub fn reset(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+        self.saved = Some(0);
+    }
+
+    /// Remember the current point of the history as the saved state.
+    pub fn mark_saved(&mut self) {
+        self.saved = Some(self.undo.len());
+    }
+
+    /// Check if the current point of the history differs from the
+    /// saved state.
+    pub fn is_dirty(&self) -> bool {
+        self.saved != Some(self.undo.len())
+    }
+}
+
+/// The number of actions the undo stack should hold, i.e. the
+/// point of the history to jump to.
+///
+/// Actions are undone or redone one at a time until the undo
+/// stack has the requested size.
+pub struct UndoJump(pub Option<usize>);
+
+impl UndoJump {
+    /// Maximum number of frames to wait for an action to arrive
+    /// on the other stack.
+    const MAX_WAIT: u32 = 3;
+}
+
+/// Step through the history until the requested point is reached.
+///
+/// Undoing or redoing a connection moves its action to the other
+/// stack a frame later. Until then the size of the undo stack
+/// doesn't tell the current point of the history, i.e. the next
+/// step waits for the action to arrive.
+fn undo_jump_system(
+    mut jump: ResMut<UndoJump>,
+    stack: Res<UndoStack>,
+    mut ev_undo: EventWriter<UndoEvent>,
+    mut expected: Local<Option<usize>>,
+    mut waited: Local<u32>,
+) {
+    let target = match jump.0 {
+        Some(target) => target,
+        None => {
+            *expected = None;
+            return;
+        }
+    };
+
+    // Jumping neither adds nor removes actions.
+    let size = stack.undo.len() + stack.redo.len();
+    if size < *expected.get_or_insert(size) && *waited < UndoJump::MAX_WAIT {
+        *waited += 1;
+        return;
+    }
+    // Continue with the actions left if one got lost, e.g. because
+    // a connection couldn't be restored.
+    *expected = Some(size);
+    *waited = 0;
+
+    if stack.undo.len() > target {
+        ev_undo.send(UndoEvent::Undo);
+    } else if stack.undo.len() < target && !stack.redo.is_empty() {
+        ev_undo.send(UndoEvent::Redo);
+    } else {
+        jump.0 = None;
+    }
+}
+
+/// Drop the oldest actions exceeding the maximum size of the stack.
+fn limit_undo_stack_system(mut stack: ResMut<UndoStack>) {
+    if stack.undo.len() <= stack.max && stack.redo.len() <= stack.max {
+        return;
+    }
+
+    let stack = &mut *stack;
+    let max = stack.max;
+    if stack.undo.len() > max {
+        let excess = stack.undo.len() - max;
+        stack.undo.drain(..excess);
+        stack.saved = stack.saved.and_then(|saved| saved.checked_sub(excess));
+    }
+    if stack.redo.len() > max {
+        let excess = stack.redo.len() - max;
+        stack.redo.drain(..excess);
+    }
+    if stack.saved.map_or(false, |saved| saved > stack.undo.len() + stack.redo.len()) {
+        stack.saved = None;
+    }
+}
+
+/// Name the component(s) affected by an action.
+fn component_names<'a>(mut names: impl Iterator<Item = &'a str>) -> String {
+    match (names.next(), names.count()) {
+        (None, _) => "Nothing".to_string(),
+        (Some(name), 0) => name.to_string(),
+        (Some(_), n) => format!("{} Components", n + 1),
+    }
+}
+
+/// Describe the given action in human readable form, e.g.
+/// `Inserted AND Gate` or `Connected Clock.y0 → D Latch.x0`.
+///
+/// Actions of the undo stack revert what the user did, i.e. they
+/// must be described `inverse`.
+pub fn describe(
+    action: &Action,
+    inverse: bool,
+    q_name: &Query<&Name>,
+    q_line: &Query<&ConnectionLine>,
+    q_parent: &Query<&Parent>,
+    q_transform: &Query<&Transform>,
+) -> String {
+    let name = |e: Entity| q_name.get(e).map(|n| n.0.as_str()).unwrap_or("?");
+    let (insert, remove) = if inverse {
+        ("Removed", "Inserted")
+    } else {
+        ("Inserted", "Removed")
+    };
+    let (connect, disconnect) = if inverse {
+        ("Disconnected", "Connected")
+    } else {
+        ("Connected", "Disconnected")
+    };
+
+    match action {
+        Action::Insert((ncs, _)) => {
+            format!("{} {}", insert, component_names(ncs.iter().map(|nc| nc.name.as_str())))
+        }
+        Action::Remove(entities) => {
+            format!("{} {}", remove, component_names(entities.iter().map(|&e| name(e))))
+        }
+        Action::InsertConnection(Link { output, input, .. }) => format!(
+            "{} {}.y{} \u{2192} {}.x{}",
+            connect,
+            name(output.entity),
+            output.index,
+            name(input.entity),
+            input.index,
+        ),
+        Action::RemoveConnection(line) => {
+            let gate = |connector: Entity| {
+                q_parent.get(connector).map(|p| name(p.0)).unwrap_or("?")
+            };
+            match q_line.get(*line) {
+                Ok(line) => format!(
+                    "{} {}.y{} \u{2192} {}.x{}",
+                    disconnect,
+                    gate(line.output.entity),
+                    line.output.index,
+                    gate(line.input.entity),
+                    line.input.index,
+                ),
+                Err(_) => disconnect.to_string(),
+            }
+        }
+        Action::Transform(transforms) => {
+            let rotated = transforms.iter().any(|(e, t)| {
+                q_transform.get(*e).map_or(false, |current| current.rotation != t.rotation)
+            });
+            format!(
+                "{} {}",
+                if rotated { "Rotated" } else { "Moved" },
+                component_names(transforms.iter().map(|&(e, _)| name(e))),
+            )
+        }
+        Action::Replace((ncs, _)) => {
+            format!("Edited {}", component_names(ncs.iter().map(|nc| nc.name.as_str())))
+        }
+        Action::Waypoints(lines) => match lines.len() {
+            1 => "Edited Wire".to_string(),
+            n => format!("Edited {} Wires", n),
+        },
+    }
 }
 
 pub fn handle_undo_event_system(
@@ -58,20 +318,12 @@ pub fn handle_undo_event_system(
     mut stack: ResMut<UndoStack>,
     mut ev_undo: EventReader<UndoEvent>,
     server: Res<AssetServer>,
-    q_node: Query<(
-        Entity,
-        &Name,
-        Option<&Inputs>,
-        Option<&Outputs>,
-        Option<&Targets>,
-        Option<&Clk>,
-        &Transform,
-        &NodeType,
-    )>,
+    q_node: NodeQuery,
     children: Query<&Children>,
     q_connectors: Query<&Connections>,
-    q_line: Query<(Entity, &ConnectionLine)>,
+    q_line: LineQuery,
     q_parent: Query<&Parent>,
+    q_selected: Query<(), With<Selected>>,
     mut ev_disconnect: EventWriter<DisconnectEvent>,
     mut ev_disconnect_undo: EventWriter<DisconnectEventUndo>,
     mut ev_conn: EventWriter<ReconnectGates>,
@@ -92,12 +344,12 @@ pub fn handle_undo_event_system(
                                 if e.0.len() == entities.len() {
                                     for i in 0..e.0.len() {
                                         replace_entity_id(
-                                            e.0[i].id, 
+                                            e.0[i].entity, 
                                             entities[i], 
                                             &mut stack
                                         );
                                         replace_entity_id_(
-                                            e.0[i].id, 
+                                            e.0[i].entity, 
                                             entities[i], 
                                             &mut e.1
                                         );
@@ -129,11 +381,34 @@ pub fn handle_undo_event_system(
                             });
                         }
                         Action::InsertConnection(con) => {
-                            let mut h = HashSet::new();
-                            h.insert(con);
-                            ev_conn.send(ReconnectGates(h, Some(UndoEvent::Redo)));
+                            ev_conn.send(ReconnectGates(vec![con], Some(UndoEvent::Redo)));
+                        },
+                        Action::Transform(transforms) => {
+                            let moved = transform_entities(&mut commands, transforms, &q_node);
+                            stack.redo.push(Action::Transform(moved));
+                        },
+                        Action::Replace(state) => {
+                            if let Some(current) = replace(
+                                &mut commands,
+                                font.clone(),
+                                state,
+                                &q_node,
+                                &q_selected,
+                                &children,
+                                &q_connectors,
+                                &q_line,
+                                &q_parent,
+                                &mut ev_disconnect,
+                                &mut ev_conn,
+                                &mut stack,
+                            ) {
+                                stack.redo.push(Action::Replace(current));
+                            }
+                        },
+                        Action::Waypoints(lines) => {
+                            let current = set_waypoints(&mut commands, lines, &q_line);
+                            stack.redo.push(Action::Waypoints(current));
                         },
-                        _ => { }
                     }
                 }
             }
@@ -149,12 +424,12 @@ pub fn handle_undo_event_system(
                                 if e.0.len() == entities.len() {
                                     for i in 0..e.0.len() {
                                         replace_entity_id(
-                                            e.0[i].id, 
+                                            e.0[i].entity, 
                                             entities[i], 
                                             &mut stack
                                         );
                                         replace_entity_id_(
-                                            e.0[i].id, 
+                                            e.0[i].entity, 
                                             entities[i], 
                                             &mut e.1
                                         );
@@ -187,11 +462,34 @@ pub fn handle_undo_event_system(
                             });
                         },
                         Action::InsertConnection(con) => {
-                            let mut h = HashSet::new();
-                            h.insert(con);
-                            ev_conn.send(ReconnectGates(h, Some(UndoEvent::Undo)));
+                            ev_conn.send(ReconnectGates(vec![con], Some(UndoEvent::Undo)));
+                        },
+                        Action::Transform(transforms) => {
+                            let moved = transform_entities(&mut commands, transforms, &q_node);
+                            stack.undo.push(Action::Transform(moved));
+                        },
+                        Action::Replace(state) => {
+                            if let Some(current) = replace(
+                                &mut commands,
+                                font.clone(),
+                                state,
+                                &q_node,
+                                &q_selected,
+                                &children,
+                                &q_connectors,
+                                &q_line,
+                                &q_parent,
+                                &mut ev_disconnect,
+                                &mut ev_conn,
+                                &mut stack,
+                            ) {
+                                stack.undo.push(Action::Replace(current));
+                            }
+                        },
+                        Action::Waypoints(lines) => {
+                            let current = set_waypoints(&mut commands, lines, &q_line);
+                            stack.undo.push(Action::Waypoints(current));
                         },
-                        _ => { }
                     }
                 }
             }
@@ -199,9 +497,106 @@ pub fn handle_undo_event_system(
     }
 }
 
+/// Apply the given transform to each entity and return
+/// the transforms the entities had before.
+fn transform_entities(
+    commands: &mut Commands,
+    transforms: Vec<(Entity, Transform)>,
+    q_node: &NodeQuery,
+) -> Vec<(Entity, Transform)> {
+    let mut moved = Vec::new();
+
+    for (e, transform) in transforms {
+        if let Ok((.., current, _)) = q_node.get(e) {
+            moved.push((e, *current));
+            commands.entity(e).insert(transform);
+        }
+    }
+
+    moved
+}
+
+/// Give each connection line the given waypoints and mark it as routed
+/// automatically if requested.
+///
+/// Returns the waypoints the lines had before.
+fn set_waypoints(
+    commands: &mut Commands,
+    lines: Vec<(Entity, Vec<Vec2>, bool)>,
+    q_line: &LineQuery,
+) -> Vec<(Entity, Vec<Vec2>, bool)> {
+    let mut current = Vec::new();
+
+    for (entity, via, auto) in lines {
+        if let Ok((_, line, routed)) = q_line.get(entity) {
+            current.push((entity, line.via.clone(), routed.is_some()));
+            commands.entity(entity).insert(ConnectionLine { via, ..line.clone() });
+            if auto {
+                commands.entity(entity).insert(AutoRoute);
+            } else {
+                commands.entity(entity).remove::<AutoRoute>();
+            }
+        }
+    }
+
+    current
+}
+
+/// Respawn the components of `state` with the captured configuration
+/// and restore the captured connections.
+///
+/// Returns the state the components had before, which reverts the
+/// replacement.
+fn replace(
+    commands: &mut Commands,
+    font: Handle<Font>,
+    (ncs, mut conns): (Vec<NodusComponent>, Vec<Link>),
+    q_node: &NodeQuery,
+    q_selected: &Query<(), With<Selected>>,
+    children: &Query<&Children>,
+    q_connectors: &Query<&Connections>,
+    q_line: &LineQuery,
+    q_parent: &Query<&Parent>,
+    ev_disconnect: &mut EventWriter<DisconnectEvent>,
+    ev_conn: &mut EventWriter<ReconnectGates>,
+    stack: &mut ResMut<UndoStack>,
+) -> Option<(Vec<NodusComponent>, Vec<Link>)> {
+    let selected: Vec<bool> = ncs.iter().map(|nc| q_selected.get(nc.entity).is_ok()).collect();
+    let (mut current, mut current_conns) = remove(
+        commands,
+        ncs.iter().map(|nc| nc.entity).collect(),
+        q_node,
+        children,
+        q_connectors,
+        q_line,
+        q_parent,
+        ev_disconnect,
+    )?;
+
+    if let Some(entities) = insert(commands, font, ncs.clone()) {
+        if ncs.len() == entities.len() {
+            for (i, &entity) in entities.iter().enumerate() {
+                let old = ncs[i].entity;
+                replace_entity_id(old, entity, stack);
+                replace_entity_id_(old, entity, &mut conns);
+                replace_entity_id_(old, entity, &mut current_conns);
+                for nc in current.iter_mut().filter(|nc| nc.entity == old) {
+                    nc.entity = entity;
+                }
+                if selected[i] {
+                    commands.entity(entity).insert(Selected);
+                }
+            }
+        }
+        ev_conn.send(ReconnectGates(conns, None));
+    }
+
+    Some((current, current_conns))
+}
+
 pub struct ReconnectGates(
-    pub HashSet<(ConnInfo, ConnInfo, Entity)>,
-    Option<UndoEvent>
+    pub Vec<Link>,
+    pub Option<UndoEvent>
 );
 
 fn reconnect_gates_event_system(
@@ -250,8 +645,9 @@ fn reconnect_gates(
     q_parent: &mut Query<&mut Targets>,
     
     stack: &mut ResMut<UndoStack>,
-    (lhs, rhs, old_id): &(ConnInfo, ConnInfo, Entity),
+    link: &Link,
 ) -> Result<Entity, ()> {
+    let (lhs, rhs) = (&link.output, &link.input);
     if let Ok(lhs_children) = q_children.get(lhs.entity) {
         if let Ok(rhs_children) = q_children.get(rhs.entity) {
             for &lhs_child in lhs_children.iter() {
@@ -271,15 +667,19 @@ fn reconnect_gates(
                                             output_index: lhs.index,
                                             input: rhs_e,
                                             input_index: rhs.index,
+                                            via: link.via.clone(),
+                                            auto: link.auto,
                                             signal_success: false,
                                         }
                                     );
 
-                                    replace_connection_entity_id_(
-                                        *old_id,
-                                        new_id,
-                                        stack,
-                                    );
+                                    if let Some(old_id) = link.line {
+                                        replace_connection_entity_id_(
+                                            old_id,
+                                            new_id,
+                                            stack,
+                                        );
+                                    }
                                     
                                     return Ok(new_id);
                                 }
@@ -294,48 +694,9 @@ fn reconnect_gates(
     Err(())
 }
 
-fn replace_entity_id_(old: Entity, new: Entity, con: &mut HashSet<(ConnInfo, ConnInfo, Entity)>) {
-    let mut tmp = Vec::new();
-
-    for t in con.iter() {
-        if let Some(t_new) = replace_entity_id3_(old, new, t.clone()) {
-            tmp.push((t.clone(), t_new));
-        }
-    }
-
-    for (o, n) in tmp.drain(..) {
-        con.remove(&o);
-        con.insert(n);
-    }
-}
-
-fn replace_entity_id3_(old: Entity, new: Entity, t: (ConnInfo, ConnInfo, Entity)) -> Option<(ConnInfo, ConnInfo, Entity)> {
-    if t.0.entity == old && t.1.entity != old {
-        Some(
-            (
-                ConnInfo { entity: new, index: t.0.index },
-                t.1.clone(),
-                t.2
-            )
-        )
-    } else if t.0.entity != old && t.1.entity == old {
-        Some(
-            (
-                t.0.clone(),
-                ConnInfo { entity: new, index: t.1.index },
-                t.2
-            )
-        )
-    } else if t.0.entity == old && t.1.entity == old {
-        Some(
-            (
-                ConnInfo { entity: new, index: t.0.index },
-                ConnInfo { entity: new, index: t.1.index },
-                t.2,
-            )
-        )
-    } else {
-        None
+fn replace_entity_id_(old: Entity, new: Entity, links: &mut [Link]) {
+    for link in links.iter_mut() {
+        link.replace_entity(old, new);
     }
 }
 
@@ -347,12 +708,28 @@ fn replace_entity_id2_(old: Entity, new: Entity, v: &mut Vec<Entity>) {
     }
 }
 
+/// Replace the id of the connection line `old` with `new` within the
+/// given connections.
+fn replace_line_id_(old: Entity, new: Entity, links: &mut [Link]) {
+    for link in links.iter_mut().filter(|link| link.line == Some(old)) {
+        link.line = Some(new);
+    }
+}
+
 fn replace_connection_entity_id_(old: Entity, new: Entity, stack: &mut ResMut<UndoStack>) {
     for action in &mut stack.undo {
         match action {
             Action::RemoveConnection(ref mut id) => { 
                 if *id == old { *id = new; }
             },
+            Action::Replace((_, conns)) => {
+                replace_line_id_(old, new, conns);
+            },
+            Action::Waypoints(ref mut lines) => {
+                for (e, ..) in lines.iter_mut() {
+                    if *e == old { *e = new; }
+                }
+            },
             _ => { }
         }
     }
@@ -362,6 +739,14 @@ fn replace_connection_entity_id_(old: Entity, new: Entity, stack: &mut ResMut<Un
             Action::RemoveConnection(ref mut id) => { 
                 if *id == old { *id = new; }
             },
+            Action::Replace((_, conns)) => {
+                replace_line_id_(old, new, conns);
+            },
+            Action::Waypoints(ref mut lines) => {
+                for (e, ..) in lines.iter_mut() {
+                    if *e == old { *e = new; }
+                }
+            },
             _ => { }
         }
     }
@@ -378,10 +763,19 @@ fn replace_entity_id(old: Entity, new: Entity, stack: &mut ResMut<UndoStack>) {
             Action::Remove(ref mut es) => { 
                 replace_entity_id2_(old, new, es);
             },
-            Action::InsertConnection(ref mut con) => {
-                if let Some(c_new) = replace_entity_id3_(old, new, con.clone()) {
-                    *con = c_new;
+            Action::Transform(ref mut transforms) => {
+                for (e, _) in transforms.iter_mut() {
+                    if *e == old { *e = new; }
+                }
+            },
+            Action::Replace((ncs, conns)) => {
+                for nc in ncs.iter_mut() {
+                    if nc.entity == old { nc.entity = new; }
                 }
+                replace_entity_id_(old, new, conns);
+            },
+            Action::InsertConnection(ref mut link) => {
+                link.replace_entity(old, new);
             },
             _ => { }
         }
@@ -395,17 +789,26 @@ fn replace_entity_id(old: Entity, new: Entity, stack: &mut ResMut<UndoStack>) {
             Action::Remove(ref mut es) => { 
                 replace_entity_id2_(old, new, es);
             },
-            Action::InsertConnection(ref mut con) => {
-                if let Some(c_new) = replace_entity_id3_(old, new, con.clone()) {
-                    *con = c_new;
+            Action::Transform(ref mut transforms) => {
+                for (e, _) in transforms.iter_mut() {
+                    if *e == old { *e = new; }
                 }
             },
+            Action::Replace((ncs, conns)) => {
+                for nc in ncs.iter_mut() {
+                    if nc.entity == old { nc.entity = new; }
+                }
+                replace_entity_id_(old, new, conns);
+            },
+            Action::InsertConnection(ref mut link) => {
+                link.replace_entity(old, new);
+            },
             _ => { }
         }
     }
 }
 
-fn insert(
+pub fn insert(
     commands: &mut Commands, 
     font: Handle<Font>, 
     components: Vec<NodusComponent>,
@@ -509,8 +912,8 @@ fn insert(
                 } else { None }
             }
             NodeType::Clock => {
-                if let Some(NodeState::Clock(x1, x2, x3)) = e.state {
-                    Some(Clk::spawn(commands, e.position, e.rotation.unwrap_or(Quat::IDENTITY), x1, x2, x3))
+                if let Some(NodeState::Clock(x1, x2, x3, config)) = e.state {
+                    Some(Clk::spawn(commands, e.position, e.rotation.unwrap_or(Quat::IDENTITY), Clk::from_state(x1, x2, config), x3))
                 } else { None }
             }
             NodeType::LightBulb => {
@@ -521,9 +924,72 @@ fn insert(
             NodeType::SevenSegmentDisplay => {
                 Some(SevenSegmentDisplay::spawn(commands, e.position, e.rotation.unwrap_or(Quat::IDENTITY)))
             }
+            NodeType::PushButton => {
+                Some(PushButton::spawn(commands, e.position, e.rotation.unwrap_or(Quat::IDENTITY)))
+            }
+            NodeType::DipSwitch => {
+                if let Some(NodeState::DipSwitch(states)) = &e.state {
+                    Some(DipSwitch::spawn(commands, e.position, e.rotation.unwrap_or(Quat::IDENTITY), states.clone()))
+                } else { None }
+            }
+            NodeType::HexKeypad => {
+                if let Some(NodeState::HexKeypad(code)) = e.state {
+                    Some(HexKeypad::spawn(commands, e.position, e.rotation.unwrap_or(Quat::IDENTITY), code, font.clone()))
+                } else { None }
+            }
+            NodeType::LedBar => {
+                if let Some(n) = e.inputs {
+                    Some(LedBar::spawn(commands, e.position, e.rotation.unwrap_or(Quat::IDENTITY), n))
+                } else { None }
+            }
+            NodeType::DotMatrix => {
+                if let Some(NodeState::DotMatrix(rows, cols, addressing, frame)) = &e.state {
+                    Some(DotMatrix::spawn(
+                        commands,
+                        e.position,
+                        e.rotation.unwrap_or(Quat::IDENTITY),
+                        *rows,
+                        *cols,
+                        *addressing,
+                        frame.clone(),
+                    ))
+                } else { None }
+            }
+            NodeType::SevenSegmentDirect => {
+                Some(SevenSegmentDirect::spawn(commands, e.position, e.rotation.unwrap_or(Quat::IDENTITY)))
+            }
+            NodeType::HexDisplay => {
+                if let Some(n) = e.inputs {
+                    Some(HexDisplay::spawn(commands, e.position, e.rotation.unwrap_or(Quat::IDENTITY), n / 4))
+                } else { None }
+            }
+            NodeType::Terminal => {
+                if let Some(NodeState::Terminal(text)) = &e.state {
+                    Some(Terminal::spawn(commands, e.position, e.rotation.unwrap_or(Quat::IDENTITY), text.clone(), font.clone()))
+                } else { None }
+            }
+            NodeType::AsciiKeyboard => {
+                if let Some(NodeState::AsciiKeyboard(buffer)) = &e.state {
+                    Some(AsciiKeyboard::spawn(commands, e.position, e.rotation.unwrap_or(Quat::IDENTITY), buffer.clone(), font.clone()))
+                } else { None }
+            }
+            NodeType::PatternGenerator => {
+                if let Some(NodeState::PatternGenerator(config)) = &e.state {
+                    Some(PatternGenerator::spawn(commands, e.position, e.rotation.unwrap_or(Quat::IDENTITY), config.clone(), font.clone()))
+                } else { None }
+            }
+            NodeType::NetLabel => {
+                if let Some(NodeState::NetLabel(name)) = &e.state {
+                    Some(NetLabel::spawn(commands, e.position, e.rotation.unwrap_or(Quat::IDENTITY), name.clone(), font.clone()))
+                } else { None }
+            }
         };
 
         if let Some(entity) = entity {
+            if let Some(key) = e.key.as_ref().and_then(|k| key_from_name(k)) {
+                commands.entity(entity).insert(KeyBinding(key));
+            }
+            commands.entity(entity).insert(e.id);
             res.push(entity);
         }
     }
@@ -532,100 +998,212 @@ fn insert(
     else { None }
 }
 
+/// Query for everything required to capture a logic component,
+/// see [`snapshot`].
+pub type NodeQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        Entity,
+        &'static Name,
+        Option<&'static Inputs>,
+        Option<&'static Outputs>,
+        Option<&'static Targets>,
+        Option<&'static Clk>,
+        Option<&'static KeyBinding>,
+        Option<&'static DotMatrix>,
+        Option<&'static Terminal>,
+        Option<&'static AsciiKeyboard>,
+        Option<&'static PatternGenerator>,
+        Option<&'static NetLabel>,
+        &'static ComponentId,
+        &'static Transform,
+        &'static NodeType,
+    ),
+>;
+
+/// Query for the connection lines captured by [`snapshot`], together
+/// with their [`AutoRoute`] marker.
+pub type LineQuery<'w, 's> =
+    Query<'w, 's, (Entity, &'static ConnectionLine, Option<&'static AutoRoute>)>;
+
+/// Capture the given component as a [`NodusComponent`], so
+/// it can be spawned again later on.
+///
+/// The waypoints of the connections starting at the component
+/// are captured as [`Wire`]s.
+pub fn snapshot(
+    entity: Entity,
+    q_node: &NodeQuery,
+    children: &Query<&Children>,
+    q_connectors: &Query<&Connections>,
+    q_line: &LineQuery,
+    q_parent: &Query<&Parent>,
+) -> Option<NodusComponent> {
+    let (e, n, ip, op, t, clk, kb, dm, term, akb, pg, nl, &id, tr, nt) = q_node.get(entity).ok()?;
+
+    let i = if let Some(i) = ip {
+        Some(i.len())
+    } else {
+        None
+    };
+    let o = if let Some(o) = op {
+        Some(o.len())
+    } else {
+        None
+    };
+    // Refer to the connected components by their ids, connections
+    // to components without an id are dropped.
+    let t = t.map(|t| {
+        TargetIds(
+            t.iter()
+                .map(|map| {
+                    TargetIdMap(
+                        map.iter()
+                            .filter_map(|(target, indices)| {
+                                let (.., &id, _, _) = q_node.get(*target).ok()?;
+                                Some((id, indices.clone()))
+                            })
+                            .collect(),
+                    )
+                })
+                .collect(),
+        )
+    });
+
+    let state = match &nt {
+        NodeType::ToggleSwitch => {
+            Some(NodeState::ToggleSwitch(op.unwrap()[0]))
+        }
+        NodeType::Clock => {
+            Some(clk.unwrap().state(op.unwrap()[0]))
+        }
+        NodeType::LightBulb => {
+            Some(NodeState::LightBulb(ip.unwrap()[0]))
+        }
+        NodeType::DipSwitch => {
+            Some(NodeState::DipSwitch(op.unwrap().0.clone()))
+        }
+        NodeType::HexKeypad => {
+            Some(NodeState::HexKeypad(HexKeypad::code(op.unwrap())))
+        }
+        NodeType::DotMatrix => {
+            Some(dm.unwrap().state())
+        }
+        NodeType::Terminal => {
+            Some(term.unwrap().state())
+        }
+        NodeType::AsciiKeyboard => {
+            Some(akb.unwrap().state())
+        }
+        NodeType::PatternGenerator => {
+            Some(pg.unwrap().state())
+        }
+        NodeType::NetLabel => {
+            Some(NodeState::NetLabel(nl.unwrap().0.clone()))
+        }
+        _ => None,
+    };
+
+    let mut wires: Vec<Wire> = connections(entity, children, q_connectors, q_line, q_parent)
+        .into_iter()
+        .filter(|link| link.output.entity == entity && (!link.via.is_empty() || link.auto))
+        .filter_map(|link| {
+            let (.., &target, _, _) = q_node.get(link.input.entity).ok()?;
+            Some(Wire {
+                output: link.output.index,
+                target,
+                input: link.input.index,
+                via: link.via,
+                auto: link.auto,
+            })
+        })
+        .collect();
+    // Keep the order stable, so files only change where the circuit did.
+    wires.sort_by_key(|w| (w.output, w.target, w.input));
+
+    Some(NodusComponent {
+        id,
+        entity: e,
+        name: n.0.to_string(),
+        inputs: i,
+        outputs: o,
+        targets: t,
+        position: Vec2::new(tr.translation.x, tr.translation.y),
+        rotation: Some(tr.rotation),
+        ntype: nt.clone(),
+        state: state,
+        key: kb.and_then(|kb| key_name(kb.0)).map(|k| k.to_string()),
+        wires,
+    })
+}
+
+/// Collect all connections of the given component.
+fn connections(
+    e: Entity,
+    children: &Query<&Children>,
+    q_connectors: &Query<&Connections>,
+    q_line: &LineQuery,
+    q_parent: &Query<&Parent>,
+) -> Vec<Link> {
+    let mut con: Vec<Link> = Vec::new();
+
+    if let Ok(children) = children.get(e) {
+        for &child in children.iter() {
+            if let Ok(conns) = q_connectors.get(child) {
+                for &connection in conns.iter() {
+                    // A component connected to itself lists the
+                    // line at both connectors.
+                    if con.iter().any(|link| link.line == Some(connection)) {
+                        continue;
+                    }
+                    if let Ok((_entity, line, auto)) = q_line.get(connection) {
+                        if let Ok(parent1) = q_parent.get(line.output.entity) {
+                            if let Ok(parent2) = q_parent.get(line.input.entity) {
+                                con.push(Link {
+                                    output: ConnInfo { entity: parent1.0, index: line.output.index },
+                                    input: ConnInfo { entity: parent2.0, index: line.input.index },
+                                    line: Some(connection),
+                                    via: line.via.clone(),
+                                    auto: auto.is_some(),
+                                });
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    con
+}
+
 pub fn remove(
     commands: &mut Commands, 
     entities: Vec<Entity>,
-    q_node: &Query<(
-        Entity,
-        &Name,
-        Option<&Inputs>,
-        Option<&Outputs>,
-        Option<&Targets>,
-        Option<&Clk>,
-        &Transform,
-        &NodeType,
-    )>,
+    q_node: &NodeQuery,
     children: &Query<&Children>,
     q_connectors: &Query<&Connections>,
-    q_line: &Query<(Entity, &ConnectionLine)>,
+    q_line: &LineQuery,
     q_parent: &Query<&Parent>,
     ev_disconnect: &mut EventWriter<DisconnectEvent>,
-) -> Option<(Vec<NodusComponent>, HashSet<(ConnInfo, ConnInfo, Entity)>)> {
+) -> Option<(Vec<NodusComponent>, Vec<Link>)> {
     let mut res = Vec::new();
-    let mut con = HashSet::new();
+    let mut con: Vec<Link> = Vec::new();
 
     for e in entities {
-        if let Ok((e, n, ip, op, t, clk, tr, nt)) = q_node.get(e) {
-            let i = if let Some(i) = ip {
-                Some(i.len())
-            } else {
-                None
-            };
-            let o = if let Some(o) = op {
-                Some(o.len())
-            } else {
-                None
-            };
-            let t = if let Some(t) = t {
-                Some(t.clone())
-            } else {
-                None
-            };
-
-            let state = match &nt {
-                NodeType::ToggleSwitch => {
-                    Some(NodeState::ToggleSwitch(op.unwrap()[0]))
-                }
-                NodeType::Clock => {
-                    let clk = clk.unwrap();
-                    Some(NodeState::Clock(clk.0, clk.1, op.unwrap()[0]))
+        if let Some(nc) = snapshot(e, q_node, children, q_connectors, q_line, q_parent) {
+            for link in connections(e, children, q_connectors, q_line, q_parent) {
+                if let Some(line) = link.line {
+                    ev_disconnect.send(DisconnectEvent {
+                        connection: line,
+                        in_parent: Some(e),
+                    });
                 }
-                NodeType::LightBulb => {
-                    Some(NodeState::LightBulb(ip.unwrap()[0]))
-                }
-                _ => None,
-            };
-            
-            let nc = NodusComponent {
-                    id: e,
-                    name: n.0.to_string(),
-                    inputs: i,
-                    outputs: o,
-                    targets: t,
-                    position: Vec2::new(tr.translation.x, tr.translation.y),
-                    rotation: Some(tr.rotation),
-                    ntype: nt.clone(),
-                    state: state,
-            };
-
-            if let Ok(children) = children.get(e) {
-                for &child in children.iter() {
-                    if let Ok(conns) = q_connectors.get(child) {
-                        for &connection in conns.iter() {
-                            if let Ok((_entity, line)) = q_line.get(connection) {
-                                if let Ok(parent1) = q_parent.get(line.output.entity) {
-                                    if let Ok(parent2) = q_parent.get(line.input.entity) {
-                                        // By using a HashSet we dont need to check
-                                        // if a connection already exists.
-                                        let c = (
-                                            // ConnInfo usually holds a reference to a connector
-                                            // and not the gate itself, but we gonna reuse it here.
-                                            ConnInfo { entity: parent1.0, index: line.output.index },
-                                            ConnInfo { entity: parent2.0, index: line.input.index },
-                                            connection
-                                        );
-                                        con.insert(c);
-                                        eprintln!("line");
-                                    }
-                                }
-                            }
-
-                            ev_disconnect.send(DisconnectEvent {
-                                connection,
-                                in_parent: Some(e),
-                            });
-                        }
-                    }
+                // Connections between the removed components
+                // are found at both ends.
+                if !con.iter().any(|c| c.line == link.line) {
+                    con.push(link);
                 }
             }
 
@@ -638,13 +1216,121 @@ pub fn remove(
     else { None }
 }
 
+/// Respawn a logical component with a different configuration,
+/// e.g. a different number of inputs.
+///
+/// All connections to connectors that still exist after the
+/// reconfiguration are restored.
+#[derive(Debug, Clone)]
+pub struct ReconfigureEvent {
+    pub entity: Entity,
+    pub inputs: Option<usize>,
+    pub state: Option<NodeState>,
+}
+
+fn reconfigure_event_system(
+    mut commands: Commands,
+    mut stack: ResMut<UndoStack>,
+    mut ev_reconf: EventReader<ReconfigureEvent>,
+    font: Res<FontAssets>,
+    q_node: NodeQuery,
+    children: Query<&Children>,
+    q_connectors: Query<&Connections>,
+    q_line: LineQuery,
+    q_parent: Query<&Parent>,
+    mut ev_disconnect: EventWriter<DisconnectEvent>,
+    mut ev_conn: EventWriter<ReconnectGates>,
+) {
+    for ev in ev_reconf.iter() {
+        if let Some((mut ncs, mut conns)) = remove(
+            &mut commands,
+            vec![ev.entity],
+            &q_node,
+            &children,
+            &q_connectors,
+            &q_line,
+            &q_parent,
+            &mut ev_disconnect
+        ) {
+            if ev.inputs.is_some() {
+                ncs[0].inputs = ev.inputs;
+            }
+            if ev.state.is_some() {
+                ncs[0].state = ev.state.clone();
+            }
+
+            if let Some(entities) = insert(&mut commands, font.main.clone(), ncs) {
+                replace_entity_id(ev.entity, entities[0], &mut stack);
+                replace_entity_id_(ev.entity, entities[0], &mut conns);
+                commands.entity(entities[0]).insert(Selected);
+                ev_conn.send(ReconnectGates(conns, None));
+            }
+        }
+    }
+}
+
+/// Signals that a property of the given component is about to
+/// change or has been changed during the current frame.
+///
+/// Edits of the same component are merged into a single undo step
+/// until the next click, i.e. dragging a value or typing a name can
+/// be undone at once.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EditEvent(pub Entity);
+
+/// Record the state of edited components on the undo stack.
+///
+/// The state of the selected components is captured at the end of each
+/// frame, i.e. the state before an edit is always available.
+fn record_edit_system(
+    egui_context: ResMut<EguiContext>,
+    mut ev_edit: EventReader<EditEvent>,
+    mut stack: ResMut<UndoStack>,
+    mut before: Local<HashMap<Entity, (NodusComponent, Vec<Link>)>>,
+    mut open: Local<bool>,
+    q_node: NodeQuery,
+    q_selected: Query<Entity, (With<Selected>, With<NodeType>)>,
+    children: Query<&Children>,
+    q_connectors: Query<&Connections>,
+    q_line: LineQuery,
+    q_parent: Query<&Parent>,
+) {
+    for &EditEvent(entity) in ev_edit.iter() {
+        // Continue the current edit if the last action already
+        // captured the state of the component.
+        let recorded = *open && matches!(
+            stack.undo.last(),
+            Some(Action::Replace((ncs, _))) if ncs.iter().any(|nc| nc.entity == entity)
+        );
+
+        if !recorded {
+            if let Some((nc, conns)) = before.get(&entity) {
+                stack.push(Action::Replace((vec![nc.clone()], conns.clone())));
+            }
+        }
+        *open = true;
+    }
+
+    let pointer = &egui_context.ctx().input().pointer;
+    if pointer.any_pressed() || pointer.any_released() {
+        *open = false;
+    }
+
+    before.clear();
+    for entity in q_selected.iter() {
+        if let Some(nc) = snapshot(entity, &q_node, &children, &q_connectors, &q_line, &q_parent) {
+            let conns = connections(entity, &children, &q_connectors, &q_line, &q_parent);
+            before.insert(entity, (nc, conns));
+        }
+    }
+}
+
 fn listen_for_new_connections_system(
     mut ev_est: EventReader<NewConnectionEstablishedEvent>,
     mut stack: ResMut<UndoStack>,
 ) {
     for ev in ev_est.iter() {
-        stack.undo.push(Action::RemoveConnection(ev.id));
-        stack.redo.clear();
+        stack.push(Action::RemoveConnection(ev.id));
     }
 }
 
@@ -662,10 +1348,17 @@ pub fn disconnect_event_system_undo(
     mut q_conn: Query<(&Parent, Entity, &mut Connections)>,
     mut q_parent: Query<&mut Targets>,
     mut q_input: Query<&mut Inputs>,
+    q_auto: Query<(), With<AutoRoute>>,
     mut stack: ResMut<UndoStack>,
 ) {
     for ev in ev_disconnect.iter() {
-        if let Some(con) = disconnect(
+        let auto = q_auto.get(ev.connection).is_ok();
+        let via = match q_line.get(ev.connection) {
+            Ok(line) => line.via.clone(),
+            Err(_) => continue,
+        };
+
+        if let Some((output, input, line)) = disconnect(
             &mut commands, 
             &q_line, 
             &mut q_conn, 
@@ -676,14 +1369,64 @@ pub fn disconnect_event_system_undo(
                 in_parent: ev.in_parent 
             }) 
         {
+            let link = Link { output, input, line: Some(line), via, auto };
             match ev.action {
                 UndoEvent::Undo => {
-                    stack.undo.push(Action::InsertConnection(con));
+                    stack.undo.push(Action::InsertConnection(link));
                 },
                 UndoEvent::Redo => {
-                    stack.redo.push(Action::InsertConnection(con));
+                    stack.redo.push(Action::InsertConnection(link));
                 }
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_saved_state() {
+        let mut stack = UndoStack {
+            undo: Vec::new(),
+            redo: Vec::new(),
+            max: 2,
+            saved: Some(0),
+        };
+        assert!(!stack.is_dirty());
+
+        stack.push(Action::Remove(Vec::new()));
+        assert!(stack.is_dirty());
+        stack.mark_saved();
+        assert!(!stack.is_dirty());
+
+        // Undoing and redoing back to the saved state.
+        stack.redo.push(stack.undo.pop().unwrap());
+        assert!(stack.is_dirty());
+        stack.undo.push(stack.redo.pop().unwrap());
+        assert!(!stack.is_dirty());
+
+        // A new action after undoing replaces the saved state.
+        stack.redo.push(stack.undo.pop().unwrap());
+        stack.push(Action::Remove(Vec::new()));
+        assert_eq!(stack.saved, None);
+        assert!(stack.is_dirty());
+    }
+
+    #[test]
+    fn test_reset() {
+        let mut stack = UndoStack {
+            undo: vec![Action::Remove(Vec::new()), Action::Remove(Vec::new())],
+            redo: vec![Action::Remove(Vec::new())],
+            max: UndoStack::DEFAULT_MAX,
+            saved: Some(1),
+        };
+
+        // Loading a file must not undo into the previous circuit.
+        stack.reset();
+        assert!(stack.undo.is_empty());
+        assert!(stack.redo.is_empty());
+        assert!(!stack.is_dirty());
+    }
+}
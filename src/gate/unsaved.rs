@@ -0,0 +1,175 @@
+use crate::gate::{
+    file_browser::*,
+    serialize::LoadEvent,
+    undo::UndoStack,
+};
+use bevy::app::AppExit;
+use bevy::ecs::event::Events;
+use bevy::prelude::*;
+use bevy::window::WindowCloseRequested;
+use std::path::Path;
+
+/// A request that replaces the current circuit and therefore
+/// discards unsaved changes.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FileAction {
+    New,
+    /// Open a file using the file browser.
+    Open,
+    /// Open the file at the given path.
+    Load(String),
+    Exit,
+}
+
+/// Progress of the decision about unsaved changes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PromptState {
+    /// Waiting for the user to choose between save, discard and cancel.
+    Ask,
+    /// The changes should be discarded.
+    Discard,
+    /// The changes are being saved.
+    Saving,
+    /// The file browser is open to choose where to save the changes.
+    Browsing,
+    /// The file browser has been closed.
+    Closed,
+}
+
+/// The [`FileAction`] waiting for the user to decide what happens
+/// to the unsaved changes.
+pub struct UnsavedChanges {
+    pub pending: Option<FileAction>,
+    pub state: PromptState,
+}
+
+impl UnsavedChanges {
+    /// Check if the user is currently asked what to do with the
+    /// unsaved changes.
+    pub fn asking(&self) -> bool {
+        self.pending.is_some() && self.state == PromptState::Ask
+    }
+}
+
+impl Default for UnsavedChanges {
+    fn default() -> Self {
+        Self {
+            pending: None,
+            state: PromptState::Ask,
+        }
+    }
+}
+
+/// Mark the current circuit as modified whenever the undo stack
+/// moves away from the saved state, i.e. on every edit, and as
+/// unmodified if undoing or redoing returns to it.
+pub fn track_changes_system(stack: Res<UndoStack>, mut curr_open: ResMut<CurrentlyOpen>) {
+    if stack.is_changed() && !stack.is_added() && curr_open.dirty != stack.is_dirty() {
+        curr_open.dirty = stack.is_dirty();
+    }
+}
+
+/// Carry out [`FileAction`]s, asking the user first if the current
+/// circuit has unsaved changes.
+///
+/// Must run after the save_event_system, so saving the changes is
+/// finished when checked.
+pub fn file_action_system(
+    mut ev_action: EventReader<FileAction>,
+    mut unsaved: ResMut<UnsavedChanges>,
+    curr_open: Res<CurrentlyOpen>,
+    fb: Res<FileBrowser>,
+    mut ev_new: EventWriter<NewFileEvent>,
+    mut fbe: EventWriter<OpenBrowserEvent>,
+    mut ev_load: EventWriter<LoadEvent>,
+    mut exit: EventWriter<AppExit>,
+) {
+    let mut perform = |action: FileAction| match action {
+        FileAction::New => ev_new.send(NewFileEvent),
+        FileAction::Open => fbe.send(OpenBrowserEvent(BrowserAction::Open)),
+        FileAction::Load(path) => ev_load.send(LoadEvent(path)),
+        FileAction::Exit => exit.send(AppExit),
+    };
+
+    for action in ev_action.iter() {
+        if curr_open.dirty {
+            unsaved.pending = Some(action.clone());
+            unsaved.state = PromptState::Ask;
+        } else {
+            perform(action.clone());
+        }
+    }
+
+    let next = match unsaved.state {
+        _ if unsaved.pending.is_none() => return,
+        PromptState::Ask => return,
+        PromptState::Discard => None,
+        _ if !curr_open.dirty => None,
+        PromptState::Saving if fb.open => Some(PromptState::Browsing),
+        // Saving to the current path failed.
+        PromptState::Saving if curr_open.path.is_some() => {
+            unsaved.pending = None;
+            return;
+        }
+        PromptState::Saving => return,
+        PromptState::Browsing if !fb.open => Some(PromptState::Closed),
+        PromptState::Browsing => return,
+        // The file browser has been closed without saving. The
+        // save request of the browser is handled within a frame.
+        PromptState::Closed => {
+            unsaved.pending = None;
+            return;
+        }
+    };
+
+    match next {
+        Some(state) => unsaved.state = state,
+        None => {
+            if let Some(action) = unsaved.pending.take() {
+                perform(action);
+            }
+        }
+    }
+}
+
+/// Ask before closing the window if there are unsaved changes.
+///
+/// Closing the window requests the exit immediately, so the request
+/// is dropped before the application handles it at the end of the frame.
+pub fn confirm_close_system(
+    mut ev_close: EventReader<WindowCloseRequested>,
+    mut exit: ResMut<Events<AppExit>>,
+    curr_open: Res<CurrentlyOpen>,
+    mut unsaved: ResMut<UnsavedChanges>,
+) {
+    if ev_close.iter().count() == 0 || !curr_open.dirty {
+        return;
+    }
+
+    exit.clear();
+    unsaved.pending = Some(FileAction::Exit);
+    unsaved.state = PromptState::Ask;
+}
+
+/// Show the name of the current file in the window title, marked
+/// with a `*` if it has unsaved changes.
+pub fn window_title_system(curr_open: Res<CurrentlyOpen>, mut windows: ResMut<Windows>) {
+    if !curr_open.is_changed() {
+        return;
+    }
+
+    let name = curr_open
+        .path
+        .as_ref()
+        .and_then(|path| Path::new(path).file_name())
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| "Untitled".to_string());
+
+    if let Some(window) = windows.get_primary_mut() {
+        window.set_title(format!(
+            "{}{} - nodus",
+            if curr_open.dirty { "*" } else { "" },
+            name
+        ));
+    }
+}
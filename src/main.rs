@@ -9,6 +9,8 @@ mod radial_menu;
 mod rmenu; // specific usage of the radial_menu
 
 use crate::gate::file_browser::*;
+use crate::gate::notify::Log;
+use crate::gate::preferences::Preferences;
 use gate::LogicComponentSystem;
 use nodus::world2d::NodusWorld2DPlugin;
 use rmenu::GateAssets;
@@ -23,6 +25,8 @@ enum GameState {
 
 fn main() {
     let mut app = App::new();
+    let mut log = Log::default();
+    let prefs = Preferences::load(&mut log);
 
     AssetLoader::new(GameState::AssetLoading)
         .continue_to_state(GameState::InGame)
@@ -32,14 +36,16 @@ fn main() {
 
     app.add_state(GameState::AssetLoading)
         .insert_resource(Msaa { samples: 1 })
-        .insert_resource(ClearColor(Color::rgb(0.75, 0.75, 0.75)))
+        .insert_resource(ClearColor(prefs.background_color()))
         .insert_resource(WindowDescriptor {
             title: "nodus".to_string(),
-            width: 1920.,
-            height: 1080.,
-            vsync: true,
+            width: prefs.window_width,
+            height: prefs.window_height,
+            vsync: prefs.vsync,
             ..Default::default()
         })
+        .insert_resource(prefs)
+        .insert_resource(log)
         .add_plugins(DefaultPlugins)
         .add_plugin(EguiPlugin)
         .add_plugin(EguiFileBrowserPlugin)
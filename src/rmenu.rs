@@ -90,6 +90,39 @@ pub struct GateAssets {
 
     #[asset(path = "gates/sevenseg.png")]
     pub seg: Handle<Image>,
+
+    #[asset(path = "gates/push.png")]
+    pub push: Handle<Image>,
+
+    #[asset(path = "gates/dip.png")]
+    pub dip: Handle<Image>,
+
+    #[asset(path = "gates/keypad.png")]
+    pub keypad: Handle<Image>,
+
+    #[asset(path = "gates/led.png")]
+    pub led: Handle<Image>,
+
+    #[asset(path = "gates/matrix.png")]
+    pub matrix: Handle<Image>,
+
+    #[asset(path = "gates/segdirect.png")]
+    pub segdirect: Handle<Image>,
+
+    #[asset(path = "gates/hex.png")]
+    pub hex: Handle<Image>,
+
+    #[asset(path = "gates/terminal.png")]
+    pub terminal: Handle<Image>,
+
+    #[asset(path = "gates/keyboard.png")]
+    pub keyboard: Handle<Image>,
+
+    #[asset(path = "gates/pattern.png")]
+    pub pattern: Handle<Image>,
+
+    #[asset(path = "gates/label.png")]
+    pub label: Handle<Image>,
 }
 
 #[derive(Debug, Eq, PartialEq)]
@@ -195,6 +228,11 @@ fn handle_radial_menu_event_system(
                                 "XOR gate".to_string(),
                                 Vec2::new(80., 80.),
                             ),
+                            (
+                                assets.label.clone(),
+                                "Net Label".to_string(),
+                                Vec2::new(80., 80.),
+                            ),
                         ],
                     });
                     ms.0 = MenuStates::LogicGates;
@@ -221,6 +259,31 @@ fn handle_radial_menu_event_system(
                                 Vec2::new(80., 80.),
                             ),
                             (assets.clk.clone(), "Clock".to_string(), Vec2::new(70., 70.)),
+                            (
+                                assets.push.clone(),
+                                "Push Button".to_string(),
+                                Vec2::new(80., 80.),
+                            ),
+                            (
+                                assets.dip.clone(),
+                                "DIP Switch".to_string(),
+                                Vec2::new(80., 80.),
+                            ),
+                            (
+                                assets.keypad.clone(),
+                                "Hex Keypad".to_string(),
+                                Vec2::new(80., 80.),
+                            ),
+                            (
+                                assets.keyboard.clone(),
+                                "ASCII Keyboard".to_string(),
+                                Vec2::new(80., 80.),
+                            ),
+                            (
+                                assets.pattern.clone(),
+                                "Pattern\nGenerator".to_string(),
+                                Vec2::new(80., 80.),
+                            ),
                         ],
                     });
                     ms.0 = MenuStates::Inputs;
@@ -241,6 +304,31 @@ fn handle_radial_menu_event_system(
                                 "7-Segment Display".to_string(),
                                 Vec2::new(80., 80.),
                             ),
+                            (
+                                assets.led.clone(),
+                                "LED Bar".to_string(),
+                                Vec2::new(80., 80.),
+                            ),
+                            (
+                                assets.matrix.clone(),
+                                "Dot Matrix".to_string(),
+                                Vec2::new(80., 80.),
+                            ),
+                            (
+                                assets.segdirect.clone(),
+                                "7-Segment\n(Direct)".to_string(),
+                                Vec2::new(80., 80.),
+                            ),
+                            (
+                                assets.hex.clone(),
+                                "Hex Display".to_string(),
+                                Vec2::new(80., 80.),
+                            ),
+                            (
+                                assets.terminal.clone(),
+                                "Terminal".to_string(),
+                                Vec2::new(80., 80.),
+                            ),
                         ],
                     });
                     ms.0 = MenuStates::Outputs;
@@ -274,6 +362,10 @@ fn handle_radial_menu_event_system(
                     ev_insert.send(InsertGateEvent::xor(ev.position));
                     ms.0 = MenuStates::Idle;
                 }
+                7 => {
+                    ev_insert.send(InsertGateEvent::label(ev.position));
+                    ms.0 = MenuStates::Idle;
+                }
                 _ => {
                     ev_open.send(OpenMenuEvent {
                         position: ev.position,
@@ -322,6 +414,26 @@ fn handle_radial_menu_event_system(
                     ev_insert.send(InsertGateEvent::clk(ev.position));
                     ms.0 = MenuStates::Idle;
                 }
+                5 => {
+                    ev_insert.send(InsertGateEvent::push(ev.position));
+                    ms.0 = MenuStates::Idle;
+                }
+                6 => {
+                    ev_insert.send(InsertGateEvent::dip(ev.position));
+                    ms.0 = MenuStates::Idle;
+                }
+                7 => {
+                    ev_insert.send(InsertGateEvent::keypad(ev.position));
+                    ms.0 = MenuStates::Idle;
+                }
+                8 => {
+                    ev_insert.send(InsertGateEvent::ascii_keyboard(ev.position));
+                    ms.0 = MenuStates::Idle;
+                }
+                9 => {
+                    ev_insert.send(InsertGateEvent::pattern(ev.position));
+                    ms.0 = MenuStates::Idle;
+                }
                 _ => {
                     ev_open.send(OpenMenuEvent {
                         position: ev.position,
@@ -362,6 +474,26 @@ fn handle_radial_menu_event_system(
                     ev_insert.send(InsertGateEvent::seg(ev.position));
                     ms.0 = MenuStates::Idle;
                 },
+                3 => {
+                    ev_insert.send(InsertGateEvent::ledbar(ev.position));
+                    ms.0 = MenuStates::Idle;
+                },
+                4 => {
+                    ev_insert.send(InsertGateEvent::matrix(ev.position));
+                    ms.0 = MenuStates::Idle;
+                },
+                5 => {
+                    ev_insert.send(InsertGateEvent::seg_direct(ev.position));
+                    ms.0 = MenuStates::Idle;
+                },
+                6 => {
+                    ev_insert.send(InsertGateEvent::hex(ev.position));
+                    ms.0 = MenuStates::Idle;
+                },
+                7 => {
+                    ev_insert.send(InsertGateEvent::terminal(ev.position));
+                    ms.0 = MenuStates::Idle;
+                },
                 _ => {
                     ev_open.send(OpenMenuEvent {
                         position: ev.position,
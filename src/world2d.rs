@@ -11,12 +11,47 @@ pub enum InteractionMode {
     Pan,
 }
 
+/// Snap the position of dragged entities to a grid.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GridSnap {
+    /// Snap to the grid by default. Holding `alt` inverts
+    /// this setting while dragging.
+    pub enabled: bool,
+    /// The edge length of a cell of the grid.
+    pub size: f32,
+}
+
+impl GridSnap {
+    /// Minimum and maximum edge length of a cell.
+    pub const RANGE: (f32, f32) = (4., 256.);
+
+    /// Check if positions should currently be snapped to the grid.
+    pub fn active(&self, keyboard: &Input<KeyCode>) -> bool {
+        self.enabled != (keyboard.pressed(KeyCode::LAlt) || keyboard.pressed(KeyCode::RAlt))
+    }
+
+    /// Get the grid point closest to `p`.
+    pub fn snap(&self, p: Vec2) -> Vec2 {
+        (p / self.size).round() * self.size
+    }
+
+    /// Get the position closest to `p` for which the point `anchor`,
+    /// given relative to the position, lies on the grid.
+    pub fn snap_anchor(&self, p: Vec2, anchor: Vec2) -> Vec2 {
+        self.snap(p + anchor) - anchor
+    }
+}
+
 pub struct NodusWorld2DPlugin;
 
 impl Plugin for NodusWorld2DPlugin {
     fn build(&self, app: &mut App) {
         app.insert_resource(InteractionMode::Select)
             .insert_resource(Lock(false))
+            .insert_resource(GridSnap {
+                enabled: false,
+                size: 32.,
+            })
             .add_plugin(Camera2DPlugin)
             .add_plugin(Interaction2DPlugin);
     }
@@ -168,7 +203,7 @@ pub mod camera2d {
 
 pub mod interaction2d {
     use super::camera2d::MouseWorldPos;
-    use super::{InteractionMode, Lock};
+    use super::{GridSnap, InteractionMode, Lock};
     use bevy::prelude::*;
 
     pub struct Interaction2DPlugin;
@@ -248,6 +283,13 @@ pub mod interaction2d {
             self.bounding_box.4 = Vec2::new(width, height);
         }
 
+        /// Get the center and the dimensions of the bounding box
+        /// relative to the entity.
+        pub fn bounds(&self) -> (Vec2, Vec2) {
+            let dimensions = self.bounding_box.4;
+            (self.bounding_box.0 + dimensions / 2., dimensions)
+        }
+
     }
 
     /// Marker component to indicate that the mouse
@@ -264,6 +306,13 @@ pub mod interaction2d {
         pub update: bool,
     }
 
+    /// The point of an entity that is aligned with the grid
+    /// while the entity is dragged, relative to the translation
+    /// of the unrotated entity. Without it, the translation
+    /// itself is aligned with the grid.
+    #[derive(Component, Debug, Clone, Copy, PartialEq)]
+    pub struct SnapAnchor(pub Vec2);
+
     /// Marker component to indicate that the
     /// given entity is currently dragged.
     #[derive(Component)]
@@ -444,13 +493,47 @@ pub mod interaction2d {
 
     pub fn drag_system(
         mw: Res<MouseWorldPos>,
-        mut q_drag: Query<(&mut Transform, &Draggable, &Drag), ()>,
+        snap: Res<GridSnap>,
+        keyboard: Res<Input<KeyCode>>,
+        mut q_drag: Query<(&mut Transform, &Draggable, &Drag, Option<&SnapAnchor>), ()>,
     ) {
-        for (mut transform, draggable, drag) in q_drag.iter_mut() {
+        let active = snap.active(&keyboard);
+
+        for (mut transform, draggable, drag, anchor) in q_drag.iter_mut() {
             if draggable.update {
-                transform.translation.x = mw.x + drag.click_offset.x;
-                transform.translation.y = mw.y + drag.click_offset.y;
+                let mut position = **mw + drag.click_offset;
+                if active {
+                    let anchor = anchor.map_or(Vec2::ZERO, |a| {
+                        transform.rotation.mul_vec3(a.0.extend(0.)).truncate()
+                    });
+                    position = snap.snap_anchor(position, anchor);
+                }
+
+                if transform.translation.truncate() != position {
+                    transform.translation.x = position.x;
+                    transform.translation.y = position.y;
+                }
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snap_anchor() {
+        let snap = GridSnap {
+            enabled: true,
+            size: 32.,
+        };
+
+        assert_eq!(snap.snap(Vec2::new(40., -20.)), Vec2::new(32., -32.));
+        // The anchor, e.g. a connector, lands on the grid point
+        // instead of the position itself.
+        let anchor = Vec2::new(-45., 10.);
+        let p = snap.snap_anchor(Vec2::new(40., -20.), anchor);
+        assert_eq!(p + anchor, Vec2::new(0., 0.));
+    }
+}